@@ -0,0 +1,714 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use crate::export::camel_case;
+use crate::parser::{Definition, Rule};
+use crate::scanner::{TokenKind, TokenPattern};
+
+const HELPERS: &str = r#"
+fn children(tree: &Tree) -> &[Tree] {
+    match tree {
+        Tree::Node { children, .. } => children,
+        Tree::Leaf(_) => &[],
+    }
+}
+
+fn production(tree: &Tree) -> usize {
+    match tree {
+        Tree::Node { production, .. } => *production,
+        Tree::Leaf(_) => 0,
+    }
+}
+
+fn text(tree: &Tree) -> String {
+    tree.text()
+}
+"#;
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn",
+];
+
+// 型の名前. 英字で始まらなければ R を付ける
+fn type_name(name: &str) -> String {
+    let name = camel_case(name);
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => name,
+        _ => format!("R{}", name),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if KEYWORDS.contains(&name.as_str()) || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.push('_');
+    }
+    name
+}
+
+// Ebnf2Gram で平坦化されたときに使う子の数. 列だけが親の生成規則に展開される
+fn width(rule: &Rule) -> usize {
+    match rule {
+        Rule::Sequence(rules) => rules.iter().map(|r| width(r)).sum(),
+        Rule::Action { .. } => width(rule.strip_action()),
+        _ => 1,
+    }
+}
+
+// 生成規則そのものに付く動作があるか. 括弧の中の動作は別の生成規則に付く
+fn has_action(rule: &Rule) -> bool {
+    match rule {
+        Rule::Action { .. } => true,
+        Rule::Sequence(rules) => rules.iter().any(|r| has_action(r)),
+        _ => false,
+    }
+}
+
+// 動作を外した文字列リテラル. 字句は構文規則の文字列から作った種類だけがリテラル
+fn literal(rule: &Rule, tokens: &[TokenKind]) -> Option<String> {
+    match rule.strip_action() {
+        Rule::Token(kind) => match &tokens[*kind].pattern {
+            TokenPattern::Literal(s) => Some(s.clone()),
+            TokenPattern::Rule(_) => None,
+        },
+        rule => rule.literal(),
+    }
+}
+
+// `%token` で宣言した字句規則の名前. 字句の値はその文字列
+fn token_rule(kind: usize, tokens: &[TokenKind]) -> Option<&str> {
+    match &tokens[kind].pattern {
+        TokenPattern::Rule(name) => Some(name),
+        TokenPattern::Literal(_) => None,
+    }
+}
+
+fn first_reference<'b>(rule: &'b Rule, tokens: &'b [TokenKind]) -> Option<&'b str> {
+    match rule {
+        Rule::IdentifierRef(name) => Some(name),
+        Rule::Token(kind) => token_rule(*kind, tokens),
+        Rule::Sequence(rules) | Rule::Or(rules) => {
+            rules.iter().find_map(|r| first_reference(r, tokens))
+        }
+        Rule::Repeat(rule) | Rule::Option(rule) | Rule::Group(rule) | Rule::Action { rule, .. } => {
+            first_reference(rule, tokens)
+        }
+        Rule::Character(_) | Rule::Exclude { .. } => None,
+    }
+}
+
+fn field_name(rule: &Rule, tokens: &[TokenKind]) -> String {
+    let base = first_reference(rule, tokens).map_or("value".to_string(), snake_case);
+    match rule {
+        Rule::Repeat(_) => format!("{}s", base.trim_end_matches('_')),
+        _ => base,
+    }
+}
+
+// 見出しの注釈に書くため, 字句を元の文字列と字句規則の参照に戻す
+fn restore_tokens(rule: &Rule, tokens: &[TokenKind]) -> Rule {
+    let restore = |rule: &Rule| Box::new(restore_tokens(rule, tokens));
+    match rule {
+        Rule::Token(kind) => match &tokens[*kind].pattern {
+            TokenPattern::Literal(s) => {
+                Rule::Sequence(s.chars().map(|c| Box::new(Rule::Character(c))).collect())
+            }
+            TokenPattern::Rule(name) => Rule::IdentifierRef(name.clone()),
+        },
+        Rule::Character(_) | Rule::IdentifierRef(_) => rule.clone(),
+        Rule::Exclude { from, target } => Rule::Exclude {
+            from: restore(from),
+            target: restore(target),
+        },
+        Rule::Sequence(rules) => Rule::Sequence(rules.iter().map(|r| restore(r)).collect()),
+        Rule::Or(rules) => Rule::Or(rules.iter().map(|r| restore(r)).collect()),
+        Rule::Repeat(rule) => Rule::Repeat(restore(rule)),
+        Rule::Option(rule) => Rule::Option(restore(rule)),
+        Rule::Group(rule) => Rule::Group(restore(rule)),
+        Rule::Action { rule, action } => Rule::Action {
+            rule: restore(rule),
+            action: action.clone(),
+        },
+    }
+}
+
+// 型と, 子の並び `children` の `offset` からその値を作る式
+type Value = (String, String);
+
+struct AstGenerator<'a> {
+    // 直接 (Vec を介さず) 含む規則. 互いに含むなら Box にする
+    contains: HashMap<&'a str, HashSet<&'a str>>,
+    // 字句の文法なら字句の種類. 文字の文法では空
+    tokens: &'a [TokenKind],
+    // 使った型の名前. 補助の列挙型の名前が規則の型と重ならないようにする
+    types: HashSet<String>,
+    items: String,
+    aux_counter: usize,
+    var_counter: usize,
+}
+
+impl<'a> AstGenerator<'a> {
+    fn collect_direct(rule: &'a Rule, output: &mut HashSet<&'a str>) {
+        match rule {
+            Rule::IdentifierRef(name) => {
+                output.insert(name);
+            }
+            Rule::Sequence(rules) | Rule::Or(rules) => {
+                for rule in rules {
+                    Self::collect_direct(rule, output);
+                }
+            }
+            Rule::Option(rule) | Rule::Group(rule) | Rule::Action { rule, .. } => {
+                Self::collect_direct(rule, output)
+            }
+            Rule::Exclude { from, target } => {
+                Self::collect_direct(from, output);
+                Self::collect_direct(target, output);
+            }
+            Rule::Repeat(_) | Rule::Character(_) | Rule::Token(_) => {}
+        }
+    }
+
+    fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(name) = stack.pop() {
+            if name == to {
+                return true;
+            }
+            if visited.insert(name) {
+                stack.extend(self.contains.get(name).into_iter().flatten().copied());
+            }
+        }
+        false
+    }
+
+    fn var(&mut self) -> String {
+        self.var_counter += 1;
+        format!("c{}", self.var_counter)
+    }
+
+    fn value(
+        &mut self,
+        owner: &str,
+        rule: &Rule,
+        in_vec: bool,
+        children: &str,
+        offset: usize,
+    ) -> Result<Option<Value>> {
+        let node = format!("&{}[{}]", children, offset);
+        Ok(match rule {
+            Rule::Character(_) => None,
+            // 字句規則の字句は読んだ文字列. 構文規則の文字列の字句は値を持たない
+            Rule::Token(kind) => token_rule(*kind, self.tokens)
+                .map(|_| ("String".to_string(), format!("text({})", node))),
+            Rule::IdentifierRef(name) => {
+                let ty = type_name(name);
+                let expr = format!("{}::from_tree({})", ty, node);
+                if !in_vec && self.reaches(name, owner) {
+                    Some((format!("Box<{}>", ty), format!("Box::new({})", expr)))
+                } else {
+                    Some((ty, expr))
+                }
+            }
+            Rule::Exclude { .. } => return Err(anyhow!("Exclusion is not supported")),
+            Rule::Action { .. } => {
+                self.value(owner, rule.strip_action(), in_vec, children, offset)?
+            }
+            Rule::Sequence(rules) => {
+                let mut values = Vec::new();
+                let mut offset = offset;
+                for rule in rules {
+                    values.extend(self.value(owner, rule, in_vec, children, offset)?);
+                    offset += width(rule);
+                }
+                match values.len() {
+                    0 => None,
+                    1 => values.pop(),
+                    _ => {
+                        let (types, exprs): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+                        Some((
+                            format!("({})", types.join(", ")),
+                            format!("({})", exprs.join(", ")),
+                        ))
+                    }
+                }
+            }
+            Rule::Group(rule) => {
+                let inner = self.var();
+                self.value(owner, rule, in_vec, &inner, 0)?
+                    .map(|(ty, expr)| {
+                        (
+                            ty,
+                            format!("{{ let {} = children({}); {} }}", inner, node, expr),
+                        )
+                    })
+            }
+            // wrap → ε | target
+            Rule::Option(rule) => {
+                let inner = self.var();
+                let condition = format!("production({}) == 1", node);
+                Some(match self.value(owner, rule, in_vec, &inner, 0)? {
+                    Some((ty, expr)) => (
+                        format!("Option<{}>", ty),
+                        format!(
+                            "if {} {{ let {} = children(&children({})[0]); Some({}) }} else {{ None }}",
+                            condition, inner, node, expr
+                        ),
+                    ),
+                    None => ("bool".to_string(), condition),
+                })
+            }
+            // wrap → ε | target, target → 本体 wrap
+            Rule::Repeat(rule) if !has_action(rule) => {
+                let inner = self.var();
+                let cursor = self.var();
+                let items = self.var();
+                let (ty, push, init) = match self.value(owner, rule, true, &inner, 0)? {
+                    Some((ty, expr)) => (
+                        format!("Vec<{}>", ty),
+                        format!("{}.push({});", items, expr),
+                        "Vec::new()",
+                    ),
+                    None => ("usize".to_string(), format!("{} += 1;", items), "0"),
+                };
+                Some((
+                    ty,
+                    format!(
+                        "{{ let mut {items} = {init}; let mut {cursor} = {node}; \
+                         while production({cursor}) == 1 {{ \
+                         let {inner} = children(&children({cursor})[0]); {push} \
+                         {cursor} = &{inner}[{width}]; }} {items} }}",
+                        width = width(rule),
+                    ),
+                ))
+            }
+            // 本体に動作があれば wrap → ε | wrap target, target → 本体. 後ろから集めて反転する
+            Rule::Repeat(rule) => {
+                let inner = self.var();
+                let cursor = self.var();
+                let items = self.var();
+                let (ty, push, init, finish) = match self.value(owner, rule, true, &inner, 0)? {
+                    Some((ty, expr)) => (
+                        format!("Vec<{}>", ty),
+                        format!("{}.push({});", items, expr),
+                        "Vec::new()",
+                        format!("{}.reverse();", items),
+                    ),
+                    None => (
+                        "usize".to_string(),
+                        format!("{} += 1;", items),
+                        "0",
+                        String::new(),
+                    ),
+                };
+                Some((
+                    ty,
+                    format!(
+                        "{{ let mut {items} = {init}; let mut {cursor} = {node}; \
+                         while production({cursor}) == 1 {{ \
+                         let {inner} = children(&children({cursor})[1]); {push} \
+                         {cursor} = &children({cursor})[0]; }} {finish} {items} }}",
+                    ),
+                ))
+            }
+            Rule::Or(rules) => {
+                if rules.iter().all(|r| literal(r, self.tokens).is_some()) {
+                    Some(("String".to_string(), format!("text({})", node)))
+                } else {
+                    self.aux_counter += 1;
+                    let name = Self::unique(
+                        &mut self.types,
+                        format!("{}{}", type_name(owner), self.aux_counter),
+                    );
+                    self.enum_item(owner, &name, rules)?;
+                    Some((name.clone(), format!("{}::from_tree({})", name, node)))
+                }
+            }
+        })
+    }
+
+    fn variant_name(&self, rule: &Rule, index: usize) -> String {
+        let rule = rule.strip_action();
+        match (rule, literal(rule, self.tokens)) {
+            (Rule::IdentifierRef(name), _) => type_name(name),
+            (Rule::Token(kind), None) => type_name(token_rule(*kind, self.tokens).unwrap()),
+            (_, Some(s)) if s.chars().all(|c| c.is_ascii_alphanumeric()) => type_name(&s),
+            _ => format!("Variant{}", index),
+        }
+    }
+
+    // 列の各要素を別々の値にした, 列挙子の中身と構築式
+    fn variant(
+        &mut self,
+        owner: &str,
+        ty: &str,
+        name: &str,
+        rule: &Rule,
+        children: &str,
+    ) -> Result<(String, String)> {
+        let mut values = Vec::new();
+        match rule.strip_action() {
+            Rule::Sequence(rules) => {
+                let mut offset = 0;
+                for rule in rules {
+                    values.extend(self.value(owner, rule, false, children, offset)?);
+                    offset += width(rule);
+                }
+            }
+            rule => values.extend(self.value(owner, rule, false, children, 0)?),
+        }
+        if values.is_empty() {
+            return Ok((name.to_string(), format!("{}::{}", ty, name)));
+        }
+        let (types, exprs): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+        Ok((
+            format!("{}({})", name, types.join(", ")),
+            format!("{}::{}({})", ty, name, exprs.join(", ")),
+        ))
+    }
+
+    fn unique(names: &mut HashSet<String>, name: String) -> String {
+        let mut candidate = name.clone();
+        let mut n = 2;
+        while !names.insert(candidate.clone()) {
+            candidate = format!("{}{}", name, n);
+            n += 1;
+        }
+        candidate
+    }
+
+    // 木の節が Or の節である列挙型
+    fn enum_item(&mut self, owner: &str, name: &str, rules: &[Box<Rule>]) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut variants = Vec::new();
+        let mut arms = Vec::new();
+        for (k, rule) in rules.iter().enumerate() {
+            let variant = Self::unique(&mut names, self.variant_name(rule, k));
+            let (declaration, expr) = self.variant(owner, name, &variant, rule, "c")?;
+            variants.push(declaration);
+            arms.push(format!("            {} => {},", k, expr));
+        }
+        self.items += &format!(
+            "\n#[derive(Debug, Clone, PartialEq, Eq)]\npub enum {} {{\n{}}}\n",
+            name,
+            variants
+                .iter()
+                .map(|v| format!("    {},\n", v))
+                .collect::<String>()
+        );
+        self.items += &format!(
+            "\nimpl {} {{\n    pub fn from_tree(tree: &Tree) -> Self {{\n        let c = children(tree);\n        match production(tree) {{\n{}\n            _ => unreachable!(),\n        }}\n    }}\n}}\n",
+            name,
+            arms.join("\n")
+        );
+        Ok(())
+    }
+
+    fn definition(&mut self, name: &str, rules: &[&Rule]) -> Result<()> {
+        let ty = type_name(name);
+        let header = rules
+            .iter()
+            .map(|r| format!("// {} = {} ;", name, restore_tokens(r, self.tokens)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // 文字列だけの選択なら一致した文字列
+        let alternatives = rules
+            .iter()
+            .flat_map(|r| match r {
+                Rule::Or(alternatives) => alternatives.iter().map(|a| a.as_ref()).collect(),
+                r => vec![*r],
+            })
+            .collect::<Vec<_>>();
+        if alternatives
+            .iter()
+            .all(|r| literal(r, self.tokens).is_some())
+            && alternatives.len() > 1
+        {
+            self.items += &format!(
+                "\n{}\n#[derive(Debug, Clone, PartialEq, Eq)]\npub struct {}(pub String);\n\nimpl {} {{\n    pub fn from_tree(tree: &Tree) -> Self {{\n        {}(text(tree))\n    }}\n}}\n",
+                header, ty, ty, ty
+            );
+            return Ok(());
+        }
+
+        // 生成規則 p が `=/` の p 番目, Or ならその中の選択肢が子の節
+        if rules.len() > 1 || matches!(rules[0], Rule::Or(_)) {
+            let mut names = HashSet::new();
+            let mut variants = Vec::new();
+            let mut arms = Vec::new();
+            let mut index = 0;
+            for (p, rule) in rules.iter().enumerate() {
+                match rule {
+                    Rule::Or(alternatives) => {
+                        let mut inner = Vec::new();
+                        for (k, alternative) in alternatives.iter().enumerate() {
+                            let variant =
+                                Self::unique(&mut names, self.variant_name(alternative, index));
+                            index += 1;
+                            let (declaration, expr) =
+                                self.variant(name, &ty, &variant, alternative, "c1")?;
+                            variants.push(declaration);
+                            inner.push(format!("                    {} => {},", k, expr));
+                        }
+                        arms.push(format!(
+                            "            {} => {{\n                let c1 = children(&c[0]);\n                match production(&c[0]) {{\n{}\n                    _ => unreachable!(),\n                }}\n            }}",
+                            p,
+                            inner.join("\n")
+                        ));
+                    }
+                    rule => {
+                        let variant = Self::unique(&mut names, self.variant_name(rule, index));
+                        index += 1;
+                        let (declaration, expr) = self.variant(name, &ty, &variant, rule, "c")?;
+                        variants.push(declaration);
+                        arms.push(format!("            {} => {},", p, expr));
+                    }
+                }
+            }
+            self.items += &format!(
+                "\n{}\n#[derive(Debug, Clone, PartialEq, Eq)]\npub enum {} {{\n{}}}\n",
+                header,
+                ty,
+                variants
+                    .iter()
+                    .map(|v| format!("    {},\n", v))
+                    .collect::<String>()
+            );
+            self.items += &format!(
+                "\nimpl {} {{\n    pub fn from_tree(tree: &Tree) -> Self {{\n        let c = children(tree);\n        match production(tree) {{\n{}\n            _ => unreachable!(),\n        }}\n    }}\n}}\n",
+                ty,
+                arms.join("\n")
+            );
+            return Ok(());
+        }
+
+        // 列は構造体. 文字だけの要素は値を持たないので省く
+        let elements = match rules[0] {
+            Rule::Sequence(rules) => rules.iter().map(|r| r.as_ref()).collect(),
+            rule => vec![rule],
+        };
+        let mut names = HashSet::new();
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        for element in elements {
+            if let Some((field_ty, expr)) = self.value(name, element, false, "c", offset)? {
+                let field = Self::unique(&mut names, field_name(element, self.tokens));
+                fields.push((field, field_ty, expr));
+            }
+            offset += width(element);
+        }
+        if fields.is_empty() {
+            self.items += &format!(
+                "\n{}\n#[derive(Debug, Clone, PartialEq, Eq)]\npub struct {};\n\nimpl {} {{\n    pub fn from_tree(_tree: &Tree) -> Self {{\n        {}\n    }}\n}}\n",
+                header, ty, ty, ty
+            );
+            return Ok(());
+        }
+        self.items += &format!(
+            "\n{}\n#[derive(Debug, Clone, PartialEq, Eq)]\npub struct {} {{\n{}}}\n",
+            header,
+            ty,
+            fields
+                .iter()
+                .map(|(field, field_ty, _)| format!("    pub {}: {},\n", field, field_ty))
+                .collect::<String>()
+        );
+        self.items += &format!(
+            "\nimpl {} {{\n    pub fn from_tree(tree: &Tree) -> Self {{\n        let c = children(tree);\n        {} {{\n{}        }}\n    }}\n}}\n",
+            ty,
+            ty,
+            fields
+                .iter()
+                .map(|(field, _, expr)| format!("            {}: {},\n", field, expr))
+                .collect::<String>()
+        );
+        Ok(())
+    }
+}
+
+// 規則ごとの AST の型と, 構文木からの変換を書き出す. 構文木は単純化していない文法
+// (`Ebnf2Gram::process` のまま) の LR 構文解析器が作るもので, `tree_path` にその型を指定する.
+// 字句の文法では definitions は字句規則を分けた後の構文規則, tokens はその字句の種類
+pub fn generate_ast(
+    definitions: &[Box<Definition>],
+    tokens: &[TokenKind],
+    tree_path: &str,
+) -> Result<String> {
+    let mut rules: Vec<(&str, Vec<&Rule>)> = Vec::new();
+    for definition in definitions {
+        match rules
+            .iter_mut()
+            .find(|(name, _)| *name == definition.identifier)
+        {
+            Some((_, alternatives)) => alternatives.push(&definition.rule),
+            None => rules.push((&definition.identifier, vec![&definition.rule])),
+        }
+    }
+
+    let mut contains: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for definition in definitions {
+        AstGenerator::collect_direct(
+            &definition.rule,
+            contains.entry(definition.identifier.as_str()).or_default(),
+        );
+    }
+    let mut generator = AstGenerator {
+        contains,
+        tokens,
+        types: rules.iter().map(|(name, _)| type_name(name)).collect(),
+        items: String::new(),
+        aux_counter: 0,
+        // 一番外側の子の並びは c と c1
+        var_counter: 1,
+    };
+    for (name, rules) in &rules {
+        generator.definition(name, rules)?;
+    }
+
+    let mut output = String::from("// Generated by rust-ebnf-cc. Do not edit.\n");
+    output += "// from_tree は `--rust-raw` で生成した, 単純化していない文法の構文解析器の構文木を受け取る.\n";
+    output += "// 単純化した文法 (`--rust`) の構文木とは生成規則の並びが違うので変換できない.\n";
+    output += "#![allow(dead_code, clippy::all)]\n\n";
+    output += &format!("use {};\n", tree_path);
+    output += HELPERS;
+    output += &generator.items;
+    Ok(output)
+}
+
+#[test]
+fn ast_generation_test() {
+    use crate::grammer::{Ebnf2Gram, GrammerIdentifier};
+    use crate::testing::parse_str;
+
+    let definitions = parse_str(
+        "list = '[' , [ item , { ',' , item } ] , ']' ;
+        item = list | number | ( '-' , number ) | 'nil' ;
+        number = digit , { digit @more } ;
+        digit = '0' | '1' | '2' ;",
+    )
+    .unwrap()
+    .definitions;
+    let ast = generate_ast(&definitions, &[], "crate::parser::Tree").unwrap();
+    assert!(ast.contains("pub struct List {\n    pub item: Option<(Box<Item>, Vec<Item>)>,\n}"));
+    assert!(ast
+        .contains("    List(Box<List>),\n    Number(Number),\n    Variant2(Number),\n    Nil,\n"));
+    assert!(
+        ast.contains("pub struct Number {\n    pub digit: Digit,\n    pub digits: Vec<Digit>,\n}")
+    );
+    assert!(ast.contains("pub struct Digit(pub String);"));
+    assert!(ast.contains("`--rust-raw`"));
+
+    let gramconv = Ebnf2Gram::process(definitions, &[]).unwrap();
+    let table = crate::slr::build_lalr_table(
+        gramconv.get_grammer_set(),
+        GrammerIdentifier(0),
+        gramconv.get_precedence_table(),
+    );
+    let parser = table.to_rust(gramconv.get_identifier_map()).unwrap();
+    let main = r#"mod ast;
+mod parser;
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        println!("{:?}", ast::List::from_tree(&parser::parse(&input).unwrap()));
+    }
+}
+"#;
+    let actual = crate::testing::run_generated_crate(
+        "ast",
+        &[("ast.rs", &ast), ("parser.rs", &parser), ("main.rs", main)],
+        &["[]", "[12,-0,[nil]]", "[2101]"],
+    );
+    assert_eq!(
+        actual.lines().collect::<Vec<_>>(),
+        [
+            "List { item: None }",
+            "List { item: Some((Number(Number { digit: Digit(\"1\"), digits: [Digit(\"2\")] }), \
+             [Variant2(Number { digit: Digit(\"0\"), digits: [] }), \
+             List(List { item: Some((Nil, [])) })])) }",
+            "List { item: Some((Number(Number { digit: Digit(\"2\"), \
+             digits: [Digit(\"1\"), Digit(\"0\"), Digit(\"1\")] }), [])) }",
+        ]
+    );
+}
+
+// 字句の文法では字句規則の字句が読んだ文字列の値になる
+#[test]
+fn token_ast_test() {
+    use crate::grammer::{Ebnf2Gram, GrammerIdentifier};
+    use crate::scanner::separate;
+    use crate::testing::parse_str;
+
+    let source = parse_str(include_str!("../tests/mppl.ebnf")).unwrap();
+    let (lexicon, syntactic) = separate(&source.definitions, &source.lexical_declarations).unwrap();
+    let precedences = lexicon.rewrite_precedences(&source.precedences);
+    let ast = generate_ast(&syntactic, lexicon.get_kinds(), "crate::parser::Tree").unwrap();
+    assert!(ast.contains("pub struct Program {\n    pub name: String,\n    pub block: Block,\n}"));
+    assert!(ast.contains("// program = \"program\" , name , \";\" , block , \".\" ;"));
+
+    let gramconv = Ebnf2Gram::process(syntactic, &precedences).unwrap();
+    let table = crate::slr::build_lalr_table(
+        gramconv.get_grammer_set(),
+        GrammerIdentifier(0),
+        gramconv.get_precedence_table(),
+    );
+    let parser = table.to_rust(gramconv.get_identifier_map()).unwrap();
+    let main = r#"mod ast;
+mod parser;
+mod scanner;
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        let tokens = scanner::tokenize(&input).unwrap();
+        let tree = parser::parse(&scanner::terminals(&tokens)).unwrap();
+        let program = ast::Program::from_tree(&tree);
+        println!("{} {}", program.name, format!("{:?}", program).contains("\"total\""));
+    }
+}
+"#;
+    let actual = crate::testing::run_generated_crate(
+        "token_ast",
+        &[
+            ("ast.rs", &ast),
+            ("parser.rs", &parser),
+            ("scanner.rs", &lexicon.to_rust()),
+            ("main.rs", main),
+        ],
+        &["program sample; var total : integer; begin total := 1 end."],
+    );
+    assert_eq!(actual, "sample true\n");
+}
+
+#[test]
+fn aux_type_name_test() {
+    use crate::testing::parse_str;
+
+    // 補助の列挙型 List1 は規則 list1 の型と重ならない名前にする
+    let definitions = parse_str(
+        "list = ( a | b , a ) ;
+         list1 = 'x' ;
+         a = 'a' ;
+         b = 'b' ;",
+    )
+    .unwrap()
+    .definitions;
+    let ast = generate_ast(&definitions, &[], "crate::parser::Tree").unwrap();
+    assert!(ast.contains("pub struct List1;"));
+    assert!(ast.contains("pub enum List12 {"));
+    assert!(ast.contains("pub a: (List12, A),"));
+}
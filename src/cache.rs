@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::grammer::{Ebnf2Gram, GrammerAnnotation, GrammerIdentifier, GrammerSet};
+use crate::lexer::Tokenizer;
+use crate::lint;
+use crate::parser::Parser;
+use crate::scanner::{separate, Lexicon};
+use crate::slr::{build_lalr_table, LRTable};
+
+// 形式を変えたら上げる. 版が違うキャッシュは作り直す
+pub const FORMAT_VERSION: u64 = 5;
+const MAGIC: &[u8; 4] = b"EBCC";
+
+// 元の文法の FNV-1a ハッシュ
+pub fn content_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// HashMap と HashSet は鍵で整列した列として保存する.
+// JSON の鍵にできない型 (組や列挙型) があり, 同じ内容から同じ出力を得るため
+pub mod sorted_map {
+    use super::*;
+
+    pub fn serialize<K: Ord + Serialize, V: Serialize, S: Serializer>(
+        map: &HashMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut entries = map.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Eq + Hash + Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+pub mod sorted_set {
+    use super::*;
+
+    pub fn serialize<T: Ord + Serialize, S: Serializer>(
+        set: &HashSet<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut items = set.iter().collect::<Vec<_>>();
+        items.sort();
+        serializer.collect_seq(items)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<HashSet<T>, D::Error>
+    where
+        T: Eq + Hash + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+// FIRST 集合などの, 値も集合の表
+pub mod sorted_set_map {
+    use super::*;
+
+    pub fn serialize<K: Ord + Serialize, T: Ord + Serialize, S: Serializer>(
+        map: &HashMap<K, HashSet<T>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut entries = map
+            .iter()
+            .map(|(key, set)| {
+                let mut items = set.iter().collect::<Vec<_>>();
+                items.sort();
+                (key, items)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, K, T, D>(deserializer: D) -> Result<HashMap<K, HashSet<T>>, D::Error>
+    where
+        K: Eq + Hash + Deserialize<'de>,
+        T: Eq + Hash + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, Vec<T>)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, items)| (key, items.into_iter().collect()))
+            .collect())
+    }
+}
+
+// オートマトンの状態の列
+pub mod sorted_sets {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        states: &[GrammerSet],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct State<'a>(#[serde(with = "sorted_map")] &'a GrammerSet);
+        serializer.collect_seq(states.iter().map(State))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<GrammerSet>, D::Error> {
+        #[derive(Deserialize)]
+        struct State(#[serde(with = "sorted_map")] GrammerSet);
+        Ok(Vec::<State>::deserialize(deserializer)?
+            .into_iter()
+            .map(|state| state.0)
+            .collect())
+    }
+}
+
+// 文法, 注釈, LALR(1) 表 (優先順位で解消済み) をまとめたもの.
+// `%token` があれば文法は字句の種類を終端記号にした構文規則で, 字句解析器も持つ
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompiledGrammer {
+    pub hash: u64,
+    pub lexicon: Option<Lexicon>,
+    #[serde(with = "sorted_map")]
+    pub identifier_map: HashMap<String, GrammerIdentifier>,
+    #[serde(with = "sorted_map")]
+    pub set: GrammerSet,
+    pub annotation: GrammerAnnotation,
+    pub table: LRTable,
+}
+
+// 版を先頭に置き, 中身を読む前に確かめる
+#[derive(Serialize, Deserialize)]
+struct CacheFile<T> {
+    version: u64,
+    grammer: T,
+}
+
+impl CompiledGrammer {
+    pub fn compile(source: &str) -> Result<Self> {
+        let input = source.chars().collect::<Vec<_>>();
+        let tokens = Tokenizer::new(&input)
+            .read_definition()
+            .ok_or_else(|| anyhow!("Tokenize failed"))?;
+        let mut parser = Parser::new(&tokens);
+        let rules = parser.eat()?;
+        if let Some(diagnostic) = lint::lint(&rules, parser.get_lexical_declarations())
+            .into_iter()
+            .find(|d| d.severity() == lint::Severity::Error)
+        {
+            return Err(anyhow!("{}", diagnostic.display(&input)));
+        }
+        let (lexicon, mut gramconv) = if parser.get_lexical_declarations().is_empty() {
+            (None, Ebnf2Gram::process(rules, parser.get_precedences())?)
+        } else {
+            let (lexicon, syntactic) = separate(&rules, parser.get_lexical_declarations())?;
+            let precedences = lexicon.rewrite_precedences(parser.get_precedences());
+            (Some(lexicon), Ebnf2Gram::process(syntactic, &precedences)?)
+        };
+        gramconv.simplify();
+
+        let set = gramconv.get_grammer_set().clone();
+        let table = build_lalr_table(&set, GrammerIdentifier(0), gramconv.get_precedence_table());
+        Ok(CompiledGrammer {
+            hash: content_hash(source),
+            lexicon,
+            identifier_map: gramconv.get_identifier_map().clone(),
+            annotation: gramconv.create_annotations(),
+            set,
+            table,
+        })
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut output = MAGIC.to_vec();
+        let file = CacheFile {
+            version: FORMAT_VERSION,
+            grammer: self,
+        };
+        output.extend(bincode::serialize(&file).unwrap());
+        output
+    }
+
+    pub fn from_binary(input: &[u8]) -> Result<Self> {
+        let Some(input) = input.strip_prefix(MAGIC) else {
+            return Err(anyhow!("Not a table cache"));
+        };
+        check_version(bincode::deserialize::<u64>(input)?)?;
+        Ok(bincode::deserialize::<CacheFile<Self>>(input)?.grammer)
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = CacheFile {
+            version: FORMAT_VERSION,
+            grammer: self,
+        };
+        serde_json::to_string(&file).unwrap()
+    }
+
+    pub fn from_json(input: &str) -> Result<Self> {
+        check_version(serde_json::from_str::<CacheFile<serde::de::IgnoredAny>>(input)?.version)?;
+        Ok(serde_json::from_str::<CacheFile<Self>>(input)?.grammer)
+    }
+
+    // 拡張子が .json なら JSON, それ以外はバイナリ. 読めない, 版が違う, ハッシュが違う場合は作り直して書き込む.
+    // 二つ目の値はキャッシュを使えたか
+    pub fn load_or_compile(source: &str, cache: &Path) -> Result<(Self, bool)> {
+        let json = cache.extension().is_some_and(|ext| ext == "json");
+        let cached = std::fs::read(cache).ok().and_then(|bytes| {
+            if json {
+                Self::from_json(std::str::from_utf8(&bytes).ok()?).ok()
+            } else {
+                Self::from_binary(&bytes).ok()
+            }
+        });
+        if let Some(compiled) = cached {
+            if compiled.hash == content_hash(source) {
+                return Ok((compiled, true));
+            }
+        }
+
+        let compiled = Self::compile(source)?;
+        if json {
+            std::fs::write(cache, compiled.to_json())?;
+        } else {
+            std::fs::write(cache, compiled.to_binary())?;
+        }
+        Ok((compiled, false))
+    }
+}
+
+fn check_version(version: u64) -> Result<()> {
+    if version != FORMAT_VERSION {
+        return Err(anyhow!(
+            "Cache format version {} is not {}",
+            version,
+            FORMAT_VERSION
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn cache_round_trip_test() {
+    let source = "%left '+' ; e = ( e , '+' , e ) | '\"' | [ 'x' ] ;";
+    let compiled = CompiledGrammer::compile(source).unwrap();
+    assert_eq!(
+        CompiledGrammer::from_binary(&compiled.to_binary()).unwrap(),
+        compiled
+    );
+    assert_eq!(
+        CompiledGrammer::from_json(&compiled.to_json()).unwrap(),
+        compiled
+    );
+    // 同じ内容なら出力も同じ
+    assert_eq!(
+        compiled.to_json(),
+        CompiledGrammer::compile(source).unwrap().to_json()
+    );
+
+    let path = std::env::temp_dir().join(format!("ebnf-cache-{}.bin", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (_, hit) = CompiledGrammer::load_or_compile(source, &path).unwrap();
+    assert!(!hit);
+    let (loaded, hit) = CompiledGrammer::load_or_compile(source, &path).unwrap();
+    assert!(hit);
+    assert_eq!(loaded, compiled);
+    // 文法が変わったら作り直す
+    let changed = "e = 'y' ;";
+    let (rebuilt, hit) = CompiledGrammer::load_or_compile(changed, &path).unwrap();
+    assert!(!hit);
+    assert_eq!(rebuilt.hash, content_hash(changed));
+    std::fs::remove_file(&path).unwrap();
+
+    // 版の違うキャッシュも作り直す
+    let path = path.with_extension("json");
+    let old = compiled.to_json().replacen(
+        &format!("\"version\":{}", FORMAT_VERSION),
+        "\"version\":0",
+        1,
+    );
+    std::fs::write(&path, old).unwrap();
+    assert!(!CompiledGrammer::load_or_compile(source, &path).unwrap().1);
+    assert!(CompiledGrammer::load_or_compile(source, &path).unwrap().1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn lexical_cache_test() {
+    use crate::scanner::Lexeme;
+
+    let source = include_str!("../tests/mppl.ebnf");
+    let compiled = CompiledGrammer::compile(source).unwrap();
+    assert_eq!(
+        CompiledGrammer::from_binary(&compiled.to_binary()).unwrap(),
+        compiled
+    );
+    let loaded = CompiledGrammer::from_json(&compiled.to_json()).unwrap();
+    assert_eq!(loaded, compiled);
+
+    // 読み込んだ字句解析器と表だけで構文解析できる
+    let lexicon = loaded.lexicon.as_ref().unwrap();
+    let terminals = lexicon
+        .scan("program p; var x : integer; begin x := 1 end.")
+        .unwrap()
+        .iter()
+        .map(Lexeme::terminal)
+        .collect::<Vec<_>>();
+    assert!(loaded.table.parse(&terminals).is_ok());
+    assert!(loaded.table.parse(&terminals[1..]).is_err());
+}
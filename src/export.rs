@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::grammer::{Grammer, GrammerIdentifier, GrammerSet, Terminal};
+
+// 表の出力形式に依らない中間表現. 欄の候補が複数あれば衝突
+#[derive(Debug, PartialEq, Eq)]
+pub struct TableView {
+    pub corner: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<(String, Vec<Vec<String>>)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TableFormat {
+    Csv,
+    Markdown,
+    Html,
+}
+
+impl std::str::FromStr for TableFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(TableFormat::Csv),
+            "md" | "markdown" => Ok(TableFormat::Markdown),
+            "html" => Ok(TableFormat::Html),
+            _ => Err(anyhow!("Unknown table format {}", s)),
+        }
+    }
+}
+
+// 非終端記号から EBNF の規則名を引く表. Ebnf2Gram の名前の表を逆にする
+pub fn nonterminal_names(
+    identifier_map: &HashMap<String, GrammerIdentifier>,
+) -> HashMap<GrammerIdentifier, &str> {
+    identifier_map
+        .iter()
+        .map(|(name, id)| (*id, name.as_str()))
+        .collect()
+}
+
+// 名前のない非終端記号は <n> と書く
+pub fn nonterminal_name(names: &HashMap<GrammerIdentifier, &str>, id: GrammerIdentifier) -> String {
+    match names.get(&id) {
+        Some(name) => name.to_string(),
+        None => format!("<{}>", id.0),
+    }
+}
+
+// 英数字以外で区切った語の先頭を大文字にしてつなげる. 数字で始まることもある
+pub fn camel_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+// 字句は tokens (Lexicon::token_names) の名前で書く. 名前が無ければ #n
+pub fn terminal_name(tokens: &[String], terminal: Terminal) -> String {
+    match terminal {
+        Terminal::Token(kind) if kind < tokens.len() => tokens[kind].clone(),
+        terminal => terminal.to_string(),
+    }
+}
+
+// 先読みの None は入力の終端 $
+pub fn lookahead_name(tokens: &[String], lookahead: Option<Terminal>) -> String {
+    match lookahead {
+        Some(terminal) => terminal_name(tokens, terminal),
+        None => "$".to_string(),
+    }
+}
+
+// 生成規則の中の記号一つ
+pub fn symbol_name(
+    names: &HashMap<GrammerIdentifier, &str>,
+    tokens: &[String],
+    g: &Grammer,
+) -> String {
+    match g {
+        Grammer::Empty => "ε".to_string(),
+        Grammer::Dot => "•".to_string(),
+        Grammer::Grammer(id) => nonterminal_name(names, *id),
+        Grammer::Character(c) => terminal_name(tokens, Terminal::Character(*c)),
+        Grammer::Token(kind) => terminal_name(tokens, Terminal::Token(*kind)),
+    }
+}
+
+// `e → e '+' e` の形で生成規則を書く
+pub fn production_label(
+    set: &GrammerSet,
+    names: &HashMap<GrammerIdentifier, &str>,
+    tokens: &[String],
+    id: GrammerIdentifier,
+    production: usize,
+) -> String {
+    let mut label = format!("{} →", nonterminal_name(names, id));
+    for g in &set[&id][production] {
+        label += &format!(" {}", symbol_name(names, tokens, g));
+    }
+    label
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_markdown(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn escape_html(field: &str) -> String {
+    field
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl TableView {
+    pub fn render(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Csv => self.to_csv(),
+            TableFormat::Markdown => self.to_markdown(),
+            TableFormat::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut output = String::new();
+        let header = std::iter::once(&self.corner)
+            .chain(&self.columns)
+            .map(|s| escape_csv(s))
+            .collect::<Vec<_>>();
+        output += &header.join(",");
+        output += "\n";
+        for (name, cells) in &self.rows {
+            let mut fields = vec![escape_csv(name)];
+            for cell in cells {
+                let field = match cell.len() {
+                    0 | 1 => cell.join(""),
+                    _ => format!("conflict: {}", cell.join(" / ")),
+                };
+                fields.push(escape_csv(&field));
+            }
+            output += &fields.join(",");
+            output += "\n";
+        }
+        output
+    }
+
+    // 衝突した欄は太字にする
+    pub fn to_markdown(&self) -> String {
+        let mut output = String::new();
+        let header = std::iter::once(&self.corner)
+            .chain(&self.columns)
+            .map(|s| escape_markdown(s))
+            .collect::<Vec<_>>();
+        output += &format!("| {} |\n", header.join(" | "));
+        output += &format!("|{}\n", "---|".repeat(header.len()));
+        for (name, cells) in &self.rows {
+            let mut fields = vec![escape_markdown(name)];
+            for cell in cells {
+                let cell = cell.iter().map(|s| escape_markdown(s)).collect::<Vec<_>>();
+                fields.push(match cell.len() {
+                    0 | 1 => cell.join(""),
+                    _ => format!("**{}**", cell.join("<br>")),
+                });
+            }
+            output += &format!("| {} |\n", fields.join(" | "));
+        }
+        output
+    }
+
+    // 衝突した欄には class="conflict" を付ける
+    pub fn to_html(&self) -> String {
+        let mut output = String::from("<table>\n  <tr>");
+        for column in std::iter::once(&self.corner).chain(&self.columns) {
+            output += &format!("<th>{}</th>", escape_html(column));
+        }
+        output += "</tr>\n";
+        for (name, cells) in &self.rows {
+            output += &format!("  <tr><th>{}</th>", escape_html(name));
+            for cell in cells {
+                let cell = cell.iter().map(|s| escape_html(s)).collect::<Vec<_>>();
+                if cell.len() > 1 {
+                    output += &format!("<td class=\"conflict\">{}</td>", cell.join("<br>"));
+                } else {
+                    output += &format!("<td>{}</td>", cell.join(""));
+                }
+            }
+            output += "</tr>\n";
+        }
+        output += "</table>\n";
+        output
+    }
+}
+
+#[test]
+fn table_view_test() {
+    let view = TableView {
+        corner: "state".to_string(),
+        columns: vec!["'|'".to_string(), "','".to_string()],
+        rows: vec![(
+            "0".to_string(),
+            vec![
+                vec!["s1".to_string(), "r e → 'x'".to_string()],
+                vec!["r e → <a>".to_string()],
+            ],
+        )],
+    };
+    assert_eq!(
+        view.to_csv(),
+        "state,'|',\"','\"\n0,conflict: s1 / r e → 'x',r e → <a>\n"
+    );
+    assert_eq!(
+        view.to_markdown(),
+        "| state | '\\|' | ',' |\n|---|---|---|\n| 0 | **s1<br>r e → 'x'** | r e → <a> |\n"
+    );
+    assert_eq!(
+        view.to_html(),
+        "<table>\n  <tr><th>state</th><th>'|'</th><th>','</th></tr>\n  \
+         <tr><th>0</th><td class=\"conflict\">s1<br>r e → 'x'</td><td>r e → &lt;a&gt;</td></tr>\n\
+         </table>\n"
+    );
+}
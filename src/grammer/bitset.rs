@@ -0,0 +1,420 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Grammer, GrammerIdentifier, GrammerSet, Terminal};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub fn insert(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        let changed = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        changed
+    }
+
+    // 変化があれば true
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            let next = *word | *other;
+            changed |= next != *word;
+            *word = next;
+        }
+        changed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(k, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| k * 64 + bit)
+        })
+    }
+}
+
+// 終端記号と非終端記号を連番にして FIRST/FOLLOW をビット集合と作業リストで求める
+#[derive(Debug)]
+pub struct FirstFollowEngine {
+    terminals: Vec<Terminal>,
+    terminal_index: HashMap<Terminal, usize>,
+    ids: Vec<GrammerIdentifier>,
+    id_index: HashMap<GrammerIdentifier, usize>,
+    nullable: Vec<bool>,
+    first: Vec<BitSet>,
+    follow: Vec<BitSet>,
+}
+
+// 連番に置き換えた記号
+#[derive(Clone, Copy)]
+enum Symbol {
+    Terminal(usize),
+    Nonterminal(usize),
+}
+
+impl FirstFollowEngine {
+    pub fn new(set: &GrammerSet) -> Self {
+        let mut engine = FirstFollowEngine {
+            terminals: Vec::new(),
+            terminal_index: HashMap::new(),
+            ids: Vec::new(),
+            id_index: HashMap::new(),
+            nullable: Vec::new(),
+            first: Vec::new(),
+            follow: Vec::new(),
+        };
+
+        let mut keys = set.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        for id in &keys {
+            engine.intern_id(*id);
+        }
+        let mut productions: Vec<(usize, Vec<Symbol>)> = Vec::new();
+        for id in &keys {
+            let owner = engine.id_index[id];
+            for grammer in &set[id] {
+                let symbols = grammer
+                    .iter()
+                    .filter_map(|g| match g {
+                        Grammer::Character(_) | Grammer::Token(_) => {
+                            Some(Symbol::Terminal(engine.intern_terminal(g.terminal()?)))
+                        }
+                        Grammer::Grammer(r) => Some(Symbol::Nonterminal(engine.intern_id(*r))),
+                        Grammer::Empty => None,
+                        Grammer::Dot => panic!("Dot not allowed"),
+                    })
+                    .collect();
+                productions.push((owner, symbols));
+            }
+        }
+
+        engine.compute_nullable(&productions);
+        engine.compute_first(&productions);
+        engine.compute_follow(&productions);
+        engine
+    }
+
+    fn intern_id(&mut self, id: GrammerIdentifier) -> usize {
+        if let Some(index) = self.id_index.get(&id) {
+            return *index;
+        }
+        self.ids.push(id);
+        self.id_index.insert(id, self.ids.len() - 1);
+        self.ids.len() - 1
+    }
+
+    fn intern_terminal(&mut self, c: Terminal) -> usize {
+        if let Some(index) = self.terminal_index.get(&c) {
+            return *index;
+        }
+        self.terminals.push(c);
+        self.terminal_index.insert(c, self.terminals.len() - 1);
+        self.terminals.len() - 1
+    }
+
+    // 生成規則ごとに空語になりえない記号の数を数え, 0 になったら nullable
+    fn compute_nullable(&mut self, productions: &[(usize, Vec<Symbol>)]) {
+        self.nullable = vec![false; self.ids.len()];
+        let mut remaining = vec![0; productions.len()];
+        let mut occurrences: Vec<Vec<usize>> = vec![Vec::new(); self.ids.len()];
+        let mut queue = Vec::new();
+        for (k, (owner, symbols)) in productions.iter().enumerate() {
+            if symbols.iter().any(|s| matches!(s, Symbol::Terminal(_))) {
+                remaining[k] = usize::MAX;
+                continue;
+            }
+            for symbol in symbols {
+                if let Symbol::Nonterminal(r) = symbol {
+                    remaining[k] += 1;
+                    occurrences[*r].push(k);
+                }
+            }
+            if remaining[k] == 0 && !self.nullable[*owner] {
+                self.nullable[*owner] = true;
+                queue.push(*owner);
+            }
+        }
+        while let Some(current) = queue.pop() {
+            for k in &occurrences[current] {
+                remaining[*k] -= 1;
+                let owner = productions[*k].0;
+                if remaining[*k] == 0 && !self.nullable[owner] {
+                    self.nullable[owner] = true;
+                    queue.push(owner);
+                }
+            }
+        }
+    }
+
+    fn compute_first(&mut self, productions: &[(usize, Vec<Symbol>)]) {
+        let len = self.terminals.len();
+        self.first = vec![BitSet::new(len); self.ids.len()];
+        // dependents[B]: FIRST(B) を含む非終端記号
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.ids.len()];
+        for (owner, symbols) in productions {
+            for symbol in symbols {
+                match symbol {
+                    Symbol::Terminal(t) => {
+                        self.first[*owner].insert(*t);
+                        break;
+                    }
+                    Symbol::Nonterminal(r) => {
+                        dependents[*r].push(*owner);
+                        if !self.nullable[*r] {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        propagate(&mut self.first, &dependents);
+    }
+
+    fn compute_follow(&mut self, productions: &[(usize, Vec<Symbol>)]) {
+        let len = self.terminals.len();
+        self.follow = vec![BitSet::new(len); self.ids.len()];
+        // dependents[A]: FOLLOW(A) を含む非終端記号
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.ids.len()];
+        for (owner, symbols) in productions {
+            // 後ろから見て残りの FIRST と空語になりうるかを持ち回る
+            let mut rest = BitSet::new(len);
+            let mut rest_nullable = true;
+            for symbol in symbols.iter().rev() {
+                match symbol {
+                    Symbol::Terminal(t) => {
+                        rest = BitSet::new(len);
+                        rest.insert(*t);
+                        rest_nullable = false;
+                    }
+                    Symbol::Nonterminal(r) => {
+                        self.follow[*r].union_with(&rest);
+                        if rest_nullable {
+                            dependents[*owner].push(*r);
+                        }
+                        if self.nullable[*r] {
+                            rest.union_with(&self.first[*r]);
+                        } else {
+                            rest = self.first[*r].clone();
+                            rest_nullable = false;
+                        }
+                    }
+                }
+            }
+        }
+        propagate(&mut self.follow, &dependents);
+    }
+
+    pub fn nullable_set(&self) -> HashSet<GrammerIdentifier> {
+        self.ids
+            .iter()
+            .zip(&self.nullable)
+            .filter(|(_, nullable)| **nullable)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn to_grammer_set(&self, bits: &BitSet) -> HashSet<Grammer> {
+        bits.iter()
+            .map(|t| Grammer::from(self.terminals[t]))
+            .collect()
+    }
+
+    pub fn first_set(&self, id: GrammerIdentifier) -> HashSet<Grammer> {
+        self.id_index
+            .get(&id)
+            .map(|index| self.to_grammer_set(&self.first[*index]))
+            .unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    pub fn follow_set(&self, id: GrammerIdentifier) -> HashSet<Grammer> {
+        self.id_index
+            .get(&id)
+            .map(|index| self.to_grammer_set(&self.follow[*index]))
+            .unwrap_or_default()
+    }
+
+    pub fn follow_set_map(&self) -> HashMap<GrammerIdentifier, HashSet<Grammer>> {
+        self.ids
+            .iter()
+            .zip(&self.follow)
+            .map(|(id, bits)| (*id, self.to_grammer_set(bits)))
+            .collect()
+    }
+}
+
+// sets[from] を sets[to] (to ∈ dependents[from]) に変化が無くなるまで流す
+fn propagate(sets: &mut [BitSet], dependents: &[Vec<usize>]) {
+    let mut queued = vec![true; sets.len()];
+    let mut queue = (0..sets.len()).collect::<Vec<_>>();
+    while let Some(from) = queue.pop() {
+        queued[from] = false;
+        let source = sets[from].clone();
+        for to in &dependents[from] {
+            if sets[*to].union_with(&source) && !queued[*to] {
+                queued[*to] = true;
+                queue.push(*to);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn assert_same_as_reference(set: &GrammerSet) {
+    use super::utils::{create_first_set, create_follow_set, create_nullable_set};
+
+    let engine = FirstFollowEngine::new(set);
+    let nullable = create_nullable_set(set);
+    assert_eq!(engine.nullable_set(), nullable);
+    let follow = create_follow_set(set, &nullable);
+    for id in set.keys() {
+        assert_eq!(
+            engine.first_set(*id),
+            create_first_set(set, &nullable, &Grammer::Grammer(*id))
+        );
+        assert_eq!(
+            engine.follow_set(*id),
+            follow.get(id).cloned().unwrap_or_default()
+        );
+    }
+}
+
+// MPPL 程度の大きさの文字単位の文法 (キーワード, 名前, 式, 文の入れ子)
+#[cfg(test)]
+fn create_large_grammer(copies: u64) -> GrammerSet {
+    let mut set = GrammerSet::new();
+    let mut next = 0;
+    let mut new_id = || {
+        next += 1;
+        GrammerIdentifier(next - 1)
+    };
+    let program = new_id();
+    let mut statements = Vec::new();
+    for k in 0..copies {
+        let (stmt, expr, term, name, name_tail, keyword) =
+            (new_id(), new_id(), new_id(), new_id(), new_id(), new_id());
+        let letter = char::from(b'a' + (k % 26) as u8);
+        set.insert(
+            keyword,
+            ["begin", "end", "while", "if", "then", "else", "procedure"]
+                .iter()
+                .map(|w| w.chars().map(Grammer::Character).collect())
+                .collect(),
+        );
+        set.insert(
+            name,
+            vec![vec![
+                Grammer::Character(letter),
+                Grammer::Grammer(name_tail),
+            ]],
+        );
+        set.insert(
+            name_tail,
+            vec![
+                vec![Grammer::Empty],
+                vec![Grammer::Character('0'), Grammer::Grammer(name_tail)],
+                vec![Grammer::Character(letter), Grammer::Grammer(name_tail)],
+            ],
+        );
+        set.insert(
+            term,
+            vec![
+                vec![Grammer::Grammer(name)],
+                vec![
+                    Grammer::Character('('),
+                    Grammer::Grammer(expr),
+                    Grammer::Character(')'),
+                ],
+            ],
+        );
+        set.insert(
+            expr,
+            vec![
+                vec![Grammer::Grammer(term)],
+                vec![
+                    Grammer::Grammer(expr),
+                    Grammer::Character('+'),
+                    Grammer::Grammer(term),
+                ],
+            ],
+        );
+        set.insert(
+            stmt,
+            vec![
+                vec![Grammer::Grammer(keyword), Grammer::Grammer(expr)],
+                vec![
+                    Grammer::Grammer(name),
+                    Grammer::Character(':'),
+                    Grammer::Grammer(expr),
+                    Grammer::Character(';'),
+                ],
+            ],
+        );
+        statements.push(vec![Grammer::Grammer(stmt), Grammer::Grammer(program)]);
+    }
+    statements.push(vec![Grammer::Empty]);
+    set.insert(program, statements);
+    set
+}
+
+#[test]
+fn first_follow_engine_test() {
+    assert_same_as_reference(&create_large_grammer(3));
+
+    let a = GrammerIdentifier(0);
+    let b = GrammerIdentifier(1);
+    let set = GrammerSet::from([
+        (
+            a,
+            vec![vec![
+                Grammer::Grammer(b),
+                Grammer::Grammer(b),
+                Grammer::Character('x'),
+            ]],
+        ),
+        (b, vec![vec![Grammer::Empty], vec![Grammer::Character('y')]]),
+    ]);
+    assert_same_as_reference(&set);
+    assert_eq!(
+        FirstFollowEngine::new(&set).follow_set(b),
+        HashSet::from([Grammer::Character('x'), Grammer::Character('y')])
+    );
+}
+
+// cargo test --release -- --ignored --nocapture first_follow_benchmark
+#[test]
+#[ignore]
+fn first_follow_benchmark() {
+    use super::utils::{create_first_set, create_follow_set, create_nullable_set};
+    use std::time::Instant;
+
+    let set = create_large_grammer(400);
+
+    let started = Instant::now();
+    let nullable = create_nullable_set(&set);
+    for id in set.keys() {
+        create_first_set(&set, &nullable, &Grammer::Grammer(*id));
+    }
+    create_follow_set(&set, &nullable);
+    let reference = started.elapsed();
+
+    let started = Instant::now();
+    FirstFollowEngine::new(&set);
+    let engine = started.elapsed();
+
+    println!(
+        "{} nonterminals: reference {:?}, bitset {:?} ({:.1}x)",
+        set.len(),
+        reference,
+        engine,
+        reference.as_secs_f64() / engine.as_secs_f64()
+    );
+}
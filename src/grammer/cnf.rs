@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+
+use super::simplify::{remove_unreachable, sorted_ids};
+use super::{eliminate_empty, Grammer, GrammerIdentifier, GrammerSet, Terminal};
+
+// CNF の非終端記号がどこから来たか
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CnfOrigin {
+    // 元の文法の非終端記号 (開始記号の分離や二分割で作ったものも元の規則を指す)
+    Rule(GrammerIdentifier),
+    // 終端記号を持ち上げた T -> c
+    Terminal(Terminal),
+}
+
+#[derive(Debug)]
+pub struct CnfGrammer {
+    pub set: GrammerSet,
+    pub start: GrammerIdentifier,
+    pub origins: HashMap<GrammerIdentifier, CnfOrigin>,
+}
+
+impl CnfGrammer {
+    // 開始記号の分離, 空語と単位規則と導出できない規則の除去, 終端記号の持ち上げ, 二分割の順に行う
+    pub fn convert(set: &GrammerSet, start: GrammerIdentifier) -> CnfGrammer {
+        let mut origins: HashMap<GrammerIdentifier, CnfOrigin> =
+            set.keys().map(|id| (*id, CnfOrigin::Rule(*id))).collect();
+
+        let (mut output, mut new_start) = eliminate_empty(set, start);
+        let mut next_id = output
+            .keys()
+            .chain(set.keys())
+            .map(|id| id.0 + 1)
+            .max()
+            .unwrap();
+        if new_start == start {
+            new_start = GrammerIdentifier(next_id);
+            next_id += 1;
+            output.insert(new_start, vec![vec![Grammer::Grammer(start)]]);
+        }
+        origins.insert(new_start, CnfOrigin::Rule(start));
+
+        remove_units(&mut output);
+        remove_non_generating(&mut output);
+        output.entry(new_start).or_default();
+        remove_unreachable(&mut output, new_start);
+
+        // 終端記号の持ち上げ
+        let mut terminals: HashMap<Terminal, GrammerIdentifier> = HashMap::new();
+        let ids = sorted_ids(&output);
+        for id in &ids {
+            let mut grammers = output.remove(id).unwrap();
+            for grammer in grammers.iter_mut().filter(|g| g.len() >= 2) {
+                for item in grammer.iter_mut() {
+                    if let Some(c) = item.terminal() {
+                        let lifted = *terminals.entry(c).or_insert_with(|| {
+                            next_id += 1;
+                            GrammerIdentifier(next_id - 1)
+                        });
+                        *item = Grammer::Grammer(lifted);
+                    }
+                }
+            }
+            output.insert(*id, grammers);
+        }
+        for (c, id) in terminals {
+            output.insert(id, vec![vec![Grammer::from(c)]]);
+            origins.insert(id, CnfOrigin::Terminal(c));
+        }
+
+        // 二分割. 同じ組の非終端記号は使い回すので, その由来は最初に作った規則
+        let mut pairs: HashMap<[Grammer; 2], GrammerIdentifier> = HashMap::new();
+        for id in ids {
+            let origin = origins[&id];
+            let mut grammers = output.remove(&id).unwrap();
+            for grammer in grammers.iter_mut().filter(|g| g.len() > 2) {
+                // A -> X1 P1, P1 -> X2 P2, ..., Pn-2 -> Xn-1 Xn を後ろから作る
+                let symbols = std::mem::take(grammer);
+                let mut tail = symbols[symbols.len() - 1];
+                for symbol in symbols[1..symbols.len() - 1].iter().rev() {
+                    let pair = [*symbol, tail];
+                    let part = *pairs.entry(pair).or_insert_with(|| {
+                        next_id += 1;
+                        let part = GrammerIdentifier(next_id - 1);
+                        output.insert(part, vec![pair.to_vec()]);
+                        origins.insert(part, origin);
+                        part
+                    });
+                    tail = Grammer::Grammer(part);
+                }
+                grammer.extend([symbols[0], tail]);
+            }
+            output.insert(id, grammers);
+        }
+
+        origins.retain(|id, _| output.contains_key(id));
+        let cnf = CnfGrammer {
+            set: output,
+            start: new_start,
+            origins,
+        };
+        debug_assert!(cnf.is_normal_form());
+        cnf
+    }
+
+    // CYK 法による所属判定
+    pub fn cyk<T: Copy + Into<Terminal>>(&self, input: &[T]) -> bool {
+        if input.is_empty() {
+            return self.set[&self.start].contains(&vec![Grammer::Empty]);
+        }
+
+        let n = input.len();
+        // table[i][l - 1]: input[i..i + l] を導出する非終端記号
+        let mut table = vec![vec![HashSet::new(); n]; n];
+        for (i, c) in input.iter().enumerate() {
+            for (id, grammers) in &self.set {
+                if grammers.contains(&vec![Grammer::from((*c).into())]) {
+                    table[i][0].insert(*id);
+                }
+            }
+        }
+        for len in 2..=n {
+            for i in 0..=n - len {
+                for split in 1..len {
+                    for (id, grammers) in &self.set {
+                        for grammer in grammers {
+                            if let [Grammer::Grammer(left), Grammer::Grammer(right)] = grammer[..] {
+                                if table[i][split - 1].contains(&left)
+                                    && table[i + split][len - split - 1].contains(&right)
+                                {
+                                    table[i][len - 1].insert(*id);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        table[0][n - 1].contains(&self.start)
+    }
+
+    pub fn is_normal_form(&self) -> bool {
+        self.set.iter().all(|(id, grammers)| {
+            grammers.iter().all(|grammer| match grammer[..] {
+                [Grammer::Character(_) | Grammer::Token(_)] => true,
+                [Grammer::Grammer(a), Grammer::Grammer(b)] => a != self.start && b != self.start,
+                [Grammer::Empty] => *id == self.start,
+                _ => false,
+            })
+        })
+    }
+}
+
+// A =>* B (単位規則) の B の非単位規則を A に集める
+fn remove_units(set: &mut GrammerSet) {
+    let mut ids = set.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    let original = set.clone();
+    for id in ids {
+        let mut visited = HashSet::from([id]);
+        let mut queue = vec![id];
+        let mut replaced: Vec<Vec<Grammer>> = Vec::new();
+        while let Some(current) = queue.pop() {
+            for grammer in original.get(&current).into_iter().flatten() {
+                if let [Grammer::Grammer(target)] = grammer[..] {
+                    if visited.insert(target) {
+                        queue.push(target);
+                    }
+                } else if !replaced.contains(grammer) {
+                    replaced.push(grammer.clone());
+                }
+            }
+        }
+        set.insert(id, replaced);
+    }
+}
+
+// 終端記号の列を導出できない規則と, それを含む生成規則を取り除く
+pub(super) fn remove_non_generating(set: &mut GrammerSet) {
+    let mut generating = HashSet::new();
+    loop {
+        let mut found = false;
+        for (id, grammers) in set.iter() {
+            if !generating.contains(id)
+                && grammers.iter().any(|grammer| {
+                    grammer.iter().all(|g| match g {
+                        Grammer::Grammer(r) => generating.contains(r),
+                        _ => true,
+                    })
+                })
+            {
+                generating.insert(*id);
+                found = true;
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+
+    set.retain(|id, _| generating.contains(id));
+    for grammers in set.values_mut() {
+        grammers.retain(|grammer| {
+            grammer
+                .iter()
+                .all(|g| !matches!(g, Grammer::Grammer(r) if !generating.contains(r)))
+        });
+    }
+}
+
+#[test]
+fn cnf_test() {
+    use super::enumerate_sentences;
+    use crate::testing::process_str;
+
+    let gramconv = process_str("s = ( 'a' , s , 'b' ) | [ 'c' ] , { 'de' } ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = gramconv.get_identifier_map()["s"];
+
+    let cnf = CnfGrammer::convert(set, s);
+    assert!(cnf.is_normal_form());
+    assert_eq!(cnf.origins[&cnf.start], CnfOrigin::Rule(s));
+    assert_eq!(cnf.origins.len(), cnf.set.len());
+
+    let expected = enumerate_sentences(set, s, 7);
+    assert_eq!(enumerate_sentences(&cnf.set, cnf.start, 7), expected);
+    for sentence in ["", "c", "acb", "aadedebb", "acdeb"] {
+        let input = sentence.chars().collect::<Vec<_>>();
+        assert!(cnf.cyk(&input), "{}", sentence);
+    }
+    for sentence in ["a", "ab c", "acbb", "dec"] {
+        let input = sentence.chars().collect::<Vec<_>>();
+        assert!(!cnf.cyk(&input), "{}", sentence);
+    }
+
+    // 同じ組は一つの非終端記号にまとめ, 導出できない規則は二分割の前に除く
+    let gramconv = process_str(
+        "s = ( 'a' , 'b' , 'c' ) | ( 'b' , 'b' , 'c' ) | ( 'a' , t , 'c' , 'c' ) ;
+                 t = t , 'a' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = gramconv.get_identifier_map()["s"];
+    let t = gramconv.get_identifier_map()["t"];
+
+    let cnf = CnfGrammer::convert(set, s);
+    assert!(cnf.is_normal_form());
+    assert!(!cnf
+        .origins
+        .values()
+        .any(|origin| *origin == CnfOrigin::Rule(t)));
+    let pairs = cnf
+        .set
+        .values()
+        .filter(|grammers| grammers.len() == 1 && grammers[0].len() == 2)
+        .collect::<Vec<_>>();
+    assert_eq!(pairs.len(), 1);
+    assert!(cnf.cyk(&"bbc".chars().collect::<Vec<_>>()));
+    assert!(!cnf.cyk(&"acc".chars().collect::<Vec<_>>()));
+}
@@ -1,178 +1,394 @@
-use std::collections::{HashMap, HashSet};
-
-use super::{
-    create_first_set, create_follow_set, Grammer, GrammerAnnotation, GrammerIdentifier, GrammerSet,
-};
-use crate::parser::{Definition, Rule};
-use anyhow::Result;
-
-pub struct Ebnf2Gram {
-    grammer_set: GrammerSet,
-    identifier_map: HashMap<String, GrammerIdentifier>,
-    end_characters: HashSet<char>,
-    identifiers: HashSet<u64>,
-    identifier_counter: u64,
-}
-
-impl Ebnf2Gram {
-    pub fn process(base: Vec<Box<Definition>>) -> Result<Self> {
-        let identifier_map = base
-            .iter()
-            .enumerate()
-            .map(|(i, x)| (x.identifier.clone(), GrammerIdentifier(i as u64)))
-            .collect::<HashMap<_, _>>();
-        let identifier_counter = identifier_map.len() as u64 + 1;
-
-        let mut state = Self {
-            grammer_set: HashMap::new(),
-            end_characters: HashSet::new(),
-            identifiers: identifier_map.iter().map(|(_, x)| x.0).collect(),
-            identifier_map,
-            identifier_counter,
-        };
-
-        for Definition { identifier, rule } in base.iter().map(|d| d.as_ref()) {
-            let mut grammer = Vec::new();
-            let selfref = state
-                .identifier_map
-                .get(identifier)
-                .ok_or(anyhow::anyhow!("Unknown identifier {}", identifier))?
-                .clone();
-            state.iterate(&mut grammer, rule.as_ref())?;
-            state.grammer_set.entry(selfref).or_default().push(grammer);
-        }
-
-        Ok(state)
-    }
-
-    fn iterate(&mut self, grammer: &mut Vec<Grammer>, rule: &Rule) -> Result<()> {
-        match rule {
-            Rule::Character(c) => {
-                self.end_characters.insert(*c);
-                grammer.push(Grammer::Character(*c));
-            }
-            Rule::IdentifierRef(i) => {
-                let gref = self
-                    .identifier_map
-                    .get(i)
-                    .ok_or(anyhow::anyhow!("Unknown identifier {}", i))?;
-                grammer.push(Grammer::Grammer(*gref));
-            }
-            Rule::Exclude { from: _, target: _ } => todo!(),
-            Rule::Sequence(inside_rule) => {
-                for rule in inside_rule {
-                    self.iterate(grammer, rule.as_ref())?;
-                }
-            }
-            Rule::Or(rules) => {
-                let next = self.next_identifier();
-                for rule in rules {
-                    let mut grammer: Vec<Grammer> = Vec::new();
-                    self.iterate(&mut grammer, rule.as_ref())?;
-                    self.grammer_set.entry(next).or_default().push(grammer);
-                }
-                grammer.push(Grammer::Grammer(next));
-            }
-            Rule::Repeat(rule) => {
-                let target_id = self.next_identifier();
-                let wrap_id = self.next_identifier();
-                // Target grammer
-                let mut new_grammer: Vec<Grammer> = Vec::new();
-                self.iterate(&mut new_grammer, rule.as_ref())?;
-                new_grammer.push(Grammer::Grammer(wrap_id.clone()));
-                self.grammer_set
-                    .entry(target_id)
-                    .or_default()
-                    .push(new_grammer);
-
-                // Ref grammer
-                let new_grammer = vec![Grammer::Empty];
-                self.grammer_set
-                    .entry(wrap_id)
-                    .or_default()
-                    .push(new_grammer);
-                let new_grammer = vec![Grammer::Grammer(target_id)];
-                self.grammer_set
-                    .entry(wrap_id)
-                    .or_default()
-                    .push(new_grammer);
-
-                grammer.push(Grammer::Grammer(wrap_id));
-            }
-            Rule::Option(rule) => {
-                let target_id = self.next_identifier();
-                let wrap_id = self.next_identifier();
-                // Once grammer grammer
-                let mut new_grammer: Vec<Grammer> = Vec::new();
-                self.iterate(&mut new_grammer, rule.as_ref())?;
-                self.grammer_set
-                    .entry(target_id)
-                    .or_default()
-                    .push(new_grammer);
-                // Ref grammer
-                let new_grammer = vec![Grammer::Empty];
-                self.grammer_set
-                    .entry(wrap_id)
-                    .or_default()
-                    .push(new_grammer);
-                let new_grammer = vec![Grammer::Grammer(target_id)];
-                self.grammer_set
-                    .entry(wrap_id)
-                    .or_default()
-                    .push(new_grammer);
-
-                grammer.push(Grammer::Grammer(wrap_id));
-            }
-            Rule::Group(rule) => {
-                let next = self.next_identifier();
-                // Once grammer grammer
-                let mut new_grammer: Vec<Grammer> = Vec::new();
-                self.iterate(&mut new_grammer, rule.as_ref())?;
-                self.grammer_set.entry(next).or_default().push(new_grammer);
-
-                grammer.push(Grammer::Grammer(next));
-            }
-        }
-
-        Ok(())
-    }
-
-    fn next_identifier(&mut self) -> GrammerIdentifier {
-        let next = self.identifier_counter;
-        self.identifier_counter += 1;
-        self.identifiers.insert(next);
-        GrammerIdentifier(next)
-    }
-
-    pub fn get_grammer_set(&self) -> &GrammerSet {
-        &self.grammer_set
-    }
-
-    pub fn get_identifier_map(&self) -> &HashMap<String, GrammerIdentifier> {
-        &self.identifier_map
-    }
-
-    pub fn create_annotations(&self) -> GrammerAnnotation {
-        let mut first_set = HashMap::new();
-
-        for char in self.end_characters.iter() {
-            let first = create_first_set(&self.grammer_set, &Grammer::Character(*char));
-            first_set.insert(Grammer::Character(*char), first);
-        }
-
-        for id in self.identifiers.iter() {
-            let first =
-                create_first_set(&self.grammer_set, &Grammer::Grammer(GrammerIdentifier(*id)));
-            first_set.insert(Grammer::Grammer(GrammerIdentifier(*id)), first);
-        }
-
-        let follow = create_follow_set(&self.grammer_set);
-
-        GrammerAnnotation {
-            endchars: self.end_characters.clone(),
-            identifiers: self.identifiers.clone(),
-            first_set,
-            follow_set: follow,
-        }
-    }
-}
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    simplify_grammer_set, Grammer, GrammerAnnotation, GrammerIdentifier, GrammerSet,
+    PrecedenceTable, SimplifyReport, Terminal,
+};
+use crate::parser::{Definition, PrecedenceDeclaration, Rule, SemanticAction};
+use anyhow::Result;
+
+pub struct Ebnf2Gram {
+    grammer_set: GrammerSet,
+    identifier_map: HashMap<String, GrammerIdentifier>,
+    end_characters: HashSet<Terminal>,
+    identifiers: HashSet<u64>,
+    identifier_counter: u64,
+    precedence_table: PrecedenceTable,
+    // (非終端記号, 生成規則の番号) ごとの動作
+    actions: HashMap<(GrammerIdentifier, usize), SemanticAction>,
+    // 組み立て中の生成規則に付いた動作
+    pending_action: Option<SemanticAction>,
+}
+
+impl Ebnf2Gram {
+    // precedences は LR 表を作るときの衝突の解消に使う
+    #[allow(clippy::vec_box)]
+    pub fn process(
+        base: Vec<Box<Definition>>,
+        precedences: &[PrecedenceDeclaration],
+    ) -> Result<Self> {
+        let identifier_map = Self::assign_identifiers(&base)?;
+        let identifier_counter = identifier_map.len() as u64 + 1;
+
+        let mut state = Self {
+            grammer_set: HashMap::new(),
+            end_characters: HashSet::new(),
+            identifiers: identifier_map.values().map(|x| x.0).collect(),
+            identifier_map,
+            identifier_counter,
+            precedence_table: Self::create_precedence_table(precedences)?,
+            actions: HashMap::new(),
+            pending_action: None,
+        };
+
+        for Definition {
+            identifier, rule, ..
+        } in base.iter().map(|d| d.as_ref())
+        {
+            let selfref = *state
+                .identifier_map
+                .get(identifier)
+                .ok_or(anyhow::anyhow!("Unknown identifier {}", identifier))?;
+            state.push_production(selfref, rule.as_ref())?;
+        }
+
+        Ok(state)
+    }
+
+    // 同名の定義は `=/` の場合のみ選択肢として併合する
+    fn assign_identifiers(base: &[Box<Definition>]) -> Result<HashMap<String, GrammerIdentifier>> {
+        let mut identifier_map = HashMap::new();
+        let mut first_spans = HashMap::new();
+        for def in base {
+            match (first_spans.get(&def.identifier), def.incremental) {
+                (None, false) => {
+                    let id = GrammerIdentifier(identifier_map.len() as u64);
+                    identifier_map.insert(def.identifier.clone(), id);
+                    first_spans.insert(def.identifier.clone(), def.span);
+                }
+                (None, true) => {
+                    return Err(anyhow::anyhow!(
+                        "{} `{}` is extended with `=/` before it is defined",
+                        def.span,
+                        def.identifier
+                    ));
+                }
+                (Some(first), false) => {
+                    return Err(anyhow::anyhow!(
+                        "{} `{}` is already defined at {} (use `=/` to add alternatives)",
+                        def.span,
+                        def.identifier,
+                        first
+                    ));
+                }
+                (Some(_), true) => {}
+            }
+        }
+        Ok(identifier_map)
+    }
+
+    // 生成規則を一つ組み立てて追加し, その中の動作を結び付ける
+    fn push_production(&mut self, id: GrammerIdentifier, rule: &Rule) -> Result<()> {
+        let outer = self.pending_action.take();
+        let mut grammer = Vec::new();
+        self.iterate(&mut grammer, rule)?;
+        let productions = self.grammer_set.entry(id).or_default();
+        productions.push(grammer);
+        if let Some(action) = std::mem::replace(&mut self.pending_action, outer) {
+            self.actions.insert((id, productions.len() - 1), action);
+        }
+        Ok(())
+    }
+
+    fn iterate(&mut self, grammer: &mut Vec<Grammer>, rule: &Rule) -> Result<()> {
+        match rule {
+            Rule::Character(c) => {
+                self.end_characters.insert(Terminal::Character(*c));
+                grammer.push(Grammer::Character(*c));
+            }
+            Rule::Token(kind) => {
+                self.end_characters.insert(Terminal::Token(*kind));
+                grammer.push(Grammer::Token(*kind));
+            }
+            Rule::IdentifierRef(i) => {
+                let gref = self
+                    .identifier_map
+                    .get(i)
+                    .ok_or(anyhow::anyhow!("Unknown identifier {}", i))?;
+                grammer.push(Grammer::Grammer(*gref));
+            }
+            // 差は文脈自由文法では書けないので字句規則にだけ使える
+            Rule::Exclude { .. } => {
+                return Err(anyhow::anyhow!(
+                    "Exclusion is only supported in lexical rules: {}",
+                    rule
+                ));
+            }
+            Rule::Sequence(inside_rule) => {
+                for rule in inside_rule {
+                    self.iterate(grammer, rule)?;
+                }
+            }
+            Rule::Or(rules) => {
+                let next = self.next_identifier();
+                for rule in rules {
+                    self.push_production(next, rule)?;
+                }
+                grammer.push(Grammer::Grammer(next));
+            }
+            // wrap → ε | target, target → 本体 wrap. 本体に動作があれば左から順に還元されるよう
+            // wrap → ε | wrap target, target → 本体 とする
+            Rule::Repeat(rule) => {
+                let target_id = self.next_identifier();
+                let wrap_id = self.next_identifier();
+                // Target grammer
+                self.push_production(target_id, rule.as_ref())?;
+                let left_recursive = self.actions.contains_key(&(target_id, 0));
+                if !left_recursive {
+                    self.grammer_set.get_mut(&target_id).unwrap()[0]
+                        .push(Grammer::Grammer(wrap_id));
+                }
+
+                // Ref grammer
+                let new_grammer = vec![Grammer::Empty];
+                self.grammer_set
+                    .entry(wrap_id)
+                    .or_default()
+                    .push(new_grammer);
+                let new_grammer = if left_recursive {
+                    vec![Grammer::Grammer(wrap_id), Grammer::Grammer(target_id)]
+                } else {
+                    vec![Grammer::Grammer(target_id)]
+                };
+                self.grammer_set
+                    .entry(wrap_id)
+                    .or_default()
+                    .push(new_grammer);
+
+                grammer.push(Grammer::Grammer(wrap_id));
+            }
+            Rule::Option(rule) => {
+                let target_id = self.next_identifier();
+                let wrap_id = self.next_identifier();
+                // Once grammer grammer
+                self.push_production(target_id, rule.as_ref())?;
+                // Ref grammer
+                let new_grammer = vec![Grammer::Empty];
+                self.grammer_set
+                    .entry(wrap_id)
+                    .or_default()
+                    .push(new_grammer);
+                let new_grammer = vec![Grammer::Grammer(target_id)];
+                self.grammer_set
+                    .entry(wrap_id)
+                    .or_default()
+                    .push(new_grammer);
+
+                grammer.push(Grammer::Grammer(wrap_id));
+            }
+            Rule::Action { action, .. } => {
+                let outer = self.pending_action.take();
+                self.iterate(grammer, rule.strip_action())?;
+                if outer.is_some() || self.pending_action.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Only one action is allowed per alternative: {}",
+                        rule
+                    ));
+                }
+                self.pending_action = Some(action.clone());
+            }
+            Rule::Group(rule) => {
+                let next = self.next_identifier();
+                // Once grammer grammer
+                self.push_production(next, rule.as_ref())?;
+
+                grammer.push(Grammer::Grammer(next));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_identifier(&mut self) -> GrammerIdentifier {
+        let next = self.identifier_counter;
+        self.identifier_counter += 1;
+        self.identifiers.insert(next);
+        GrammerIdentifier(next)
+    }
+
+    // 開始記号は最初の定義
+    // 動作は生成規則の番号と記号の位置 ($n) に結び付いているので,
+    // 動作のある非終端記号は展開も書き換えもしない
+    pub fn simplify(&mut self) -> SimplifyReport {
+        let frozen = self
+            .actions
+            .keys()
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        let keep = self
+            .identifier_map
+            .values()
+            .copied()
+            .chain(frozen.iter().copied())
+            .collect::<HashSet<_>>();
+        let report =
+            simplify_grammer_set(&mut self.grammer_set, GrammerIdentifier(0), &keep, &frozen);
+
+        let grammer_set = &self.grammer_set;
+        self.identifiers
+            .retain(|id| grammer_set.contains_key(&GrammerIdentifier(*id)));
+        self.identifier_map
+            .retain(|_, id| grammer_set.contains_key(id));
+        self.actions
+            .retain(|(id, _), _| grammer_set.contains_key(id));
+        report
+    }
+
+    pub fn get_grammer_set(&self) -> &GrammerSet {
+        &self.grammer_set
+    }
+
+    pub fn get_identifier_map(&self) -> &HashMap<String, GrammerIdentifier> {
+        &self.identifier_map
+    }
+
+    // 終端記号は 1 文字なので演算子も 1 文字に限る
+    fn create_precedence_table(declarations: &[PrecedenceDeclaration]) -> Result<PrecedenceTable> {
+        let mut precedence_table = PrecedenceTable::new();
+        for (level, declaration) in declarations.iter().enumerate() {
+            for operator in &declaration.operators {
+                // 字句規則を分けた文法では演算子は字句になっている
+                let terminal = match operator {
+                    Rule::Token(kind) => Terminal::Token(*kind),
+                    rule => {
+                        let literal = rule.literal().unwrap_or_default();
+                        let mut chars = literal.chars();
+                        let (Some(c), None) = (chars.next(), chars.next()) else {
+                            return Err(anyhow::anyhow!(
+                                "{} operator `{}` must be a single character",
+                                declaration.span,
+                                literal
+                            ));
+                        };
+                        Terminal::Character(c)
+                    }
+                };
+                precedence_table.insert(terminal, (level + 1, declaration.associativity));
+            }
+        }
+        Ok(precedence_table)
+    }
+
+    pub fn get_actions(&self) -> &HashMap<(GrammerIdentifier, usize), SemanticAction> {
+        &self.actions
+    }
+
+    pub fn get_precedence_table(&self) -> &PrecedenceTable {
+        &self.precedence_table
+    }
+
+    pub fn create_annotations(&self) -> GrammerAnnotation {
+        GrammerAnnotation::with_symbols(
+            &self.grammer_set,
+            self.end_characters.clone(),
+            self.identifiers.clone(),
+        )
+    }
+}
+
+#[test]
+fn incremental_definition_test() {
+    use crate::testing::process_str;
+
+    let gramconv = process_str("a = 'x' ; a =/ 'y' ;").unwrap();
+    let a = gramconv.get_identifier_map()["a"];
+    assert_eq!(
+        gramconv.get_grammer_set()[&a],
+        vec![vec![Grammer::Character('x')], vec![Grammer::Character('y')]]
+    );
+
+    let err = process_str("a = 'x' ; a = 'y' ;").err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "10..19 `a` is already defined at 0..9 (use `=/` to add alternatives)"
+    );
+    assert!(process_str("a =/ 'x' ;").is_err());
+}
+
+#[test]
+fn precedence_table_test() {
+    use crate::parser::Associativity;
+    use crate::testing::parse_str;
+
+    let source = parse_str("%left '+' '-' ; %right '^' ; %left '**' ; e = 'x' ;").unwrap();
+    assert!(Ebnf2Gram::process(source.definitions.clone(), &source.precedences).is_err());
+    let gramconv = Ebnf2Gram::process(source.definitions, &source.precedences[..2]).unwrap();
+    let table = gramconv.get_precedence_table();
+    assert_eq!(table[&Terminal::Character('-')], (1, Associativity::Left));
+    assert_eq!(table[&Terminal::Character('^')], (2, Associativity::Right));
+}
+
+#[test]
+fn semantic_action_test() {
+    use crate::testing::process_str;
+
+    let gramconv = process_str(
+        "s = e , { ',' , e @item } , [ ';' ] ;
+         e = ( e , '+' , 'x' ) {{ $1 + 1 }} | 'x' @one ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let actions = gramconv.get_actions();
+    assert_eq!(actions.len(), 3);
+
+    // 動作の付いた括弧は選択肢の生成規則に展開される
+    let e = gramconv.get_identifier_map()["e"];
+    let [Grammer::Grammer(alternatives)] = set[&e][0][..] else {
+        panic!("{:?}", set[&e]);
+    };
+    let e_id = Grammer::Grammer(e);
+    assert_eq!(
+        set[&alternatives][0],
+        vec![e_id, Grammer::Character('+'), Grammer::Character('x')]
+    );
+    assert_eq!(
+        actions[&(alternatives, 0)],
+        SemanticAction::Code("$1 + 1".to_string())
+    );
+    assert_eq!(
+        actions[&(alternatives, 1)],
+        SemanticAction::Named("one".to_string())
+    );
+
+    // 繰り返しの中の動作は本体の生成規則に付き, 繰り返しは左再帰になる
+    let (&(id, production), _) = actions
+        .iter()
+        .find(|(_, action)| **action == SemanticAction::Named("item".to_string()))
+        .unwrap();
+    assert_eq!(set[&id][production], [Grammer::Character(','), e_id]);
+    let [Grammer::Grammer(_), Grammer::Grammer(wrap), ..] =
+        set[&gramconv.get_identifier_map()["s"]][0][..]
+    else {
+        panic!();
+    };
+    assert_eq!(
+        set[&wrap][1],
+        [Grammer::Grammer(wrap), Grammer::Grammer(id)]
+    );
+
+    // 単純化しても動作の付いた生成規則はそのまま残る
+    let before = actions
+        .keys()
+        .map(|(id, production)| ((*id, *production), set[id][*production].clone()))
+        .collect::<HashMap<_, _>>();
+    let mut gramconv = gramconv;
+    let report = gramconv.simplify();
+    assert!(report.inlined + report.unit_removed > 0);
+    assert_eq!(gramconv.get_actions().len(), 3);
+    for (key, production) in before {
+        assert!(gramconv.get_actions().contains_key(&key));
+        assert_eq!(gramconv.get_grammer_set()[&key.0][key.1], production);
+    }
+    assert!(process_str("e = ( 'x' {{ 1 }} ) {{ 2 }} ;").is_err());
+    assert!(process_str("e = 'x' {{ 1 }} , 'y' {{ 2 }} ;").is_err());
+}
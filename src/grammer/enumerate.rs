@@ -0,0 +1,72 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{Grammer, GrammerIdentifier, GrammerSet};
+
+// `max_len` 文字以下の文を全て列挙する (変換前後の文法の比較用)
+pub fn enumerate_sentences(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    max_len: usize,
+) -> BTreeSet<String> {
+    let mut language: HashMap<GrammerIdentifier, BTreeSet<String>> = HashMap::new();
+
+    loop {
+        let mut found = false;
+        for (id, grammers) in set {
+            for grammer in grammers {
+                let mut sentences = BTreeSet::from([String::new()]);
+                for item in grammer {
+                    sentences = match item {
+                        Grammer::Empty => sentences,
+                        Grammer::Character(c) => sentences
+                            .into_iter()
+                            .filter(|s| s.chars().count() < max_len)
+                            .map(|s| format!("{}{}", s, c))
+                            .collect(),
+                        Grammer::Grammer(r) => {
+                            let Some(tails) = language.get(r) else {
+                                sentences = BTreeSet::new();
+                                break;
+                            };
+                            let mut next = BTreeSet::new();
+                            for s in &sentences {
+                                for t in tails {
+                                    if s.chars().count() + t.chars().count() <= max_len {
+                                        next.insert(format!("{}{}", s, t));
+                                    }
+                                }
+                            }
+                            next
+                        }
+                        Grammer::Dot => panic!("Dot not allowed"),
+                        Grammer::Token(_) => panic!("Tokens not allowed"),
+                    };
+                }
+                let target = language.entry(*id).or_default();
+                for s in sentences {
+                    found |= target.insert(s);
+                }
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+
+    language.remove(&start).unwrap_or_default()
+}
+
+// `max_len` 文字以下で一方にしか無い最短の文を返す
+pub fn compare_languages(
+    left: &GrammerSet,
+    left_start: GrammerIdentifier,
+    right: &GrammerSet,
+    right_start: GrammerIdentifier,
+    max_len: usize,
+) -> Option<String> {
+    let left = enumerate_sentences(left, left_start, max_len);
+    let right = enumerate_sentences(right, right_start, max_len);
+    left.symmetric_difference(&right)
+        .min_by_key(|s| (s.chars().count(), s.to_string()))
+        .cloned()
+}
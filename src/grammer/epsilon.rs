@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use super::{create_nullable_set, Grammer, GrammerIdentifier, GrammerSet};
+
+// 一つの生成規則で省くかどうかを組み合わせる, 空語になりうる記号の数の上限.
+// これより多い生成規則は後ろを新しい非終端記号に分けるので, 候補は 2^MAX_NULLABLE 個まで
+const MAX_NULLABLE: usize = 8;
+
+// 空語の生成規則を取り除いた文法と開始記号を返す
+// 開始記号が空語を導出する場合のみ, 新しい開始記号 S' -> S | ε を追加する.
+// 開始記号が空語しか導出しない場合は S -> ε だけを残す
+pub fn eliminate_empty(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+) -> (GrammerSet, GrammerIdentifier) {
+    let set = &split_nullable(set, &create_nullable_set(set));
+    let nullable = create_nullable_set(set);
+    let mut output = GrammerSet::new();
+
+    for (id, grammers) in set {
+        let mut replaced: Vec<Vec<Grammer>> = Vec::new();
+        for grammer in grammers {
+            for candidate in expand_nullable(grammer, &nullable) {
+                if candidate.is_empty() || candidate == [Grammer::Grammer(*id)] {
+                    continue;
+                }
+                if !replaced.contains(&candidate) {
+                    replaced.push(candidate);
+                }
+            }
+        }
+        output.insert(*id, replaced);
+    }
+
+    // 空語しか導出しない非終端記号への参照を取り除く
+    loop {
+        let empty = output
+            .iter()
+            .filter(|(_, grammers)| grammers.is_empty())
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        let mut found = false;
+        for grammers in output.values_mut() {
+            let before = grammers.len();
+            grammers.retain(|grammer| {
+                !grammer
+                    .iter()
+                    .any(|g| matches!(g, Grammer::Grammer(r) if empty.contains(r)))
+            });
+            found |= before != grammers.len();
+        }
+        if !found {
+            break;
+        }
+    }
+    output.retain(|id, grammers| !grammers.is_empty() || *id == start);
+
+    if !nullable.contains(&start) {
+        return (output, start);
+    }
+    if output.get(&start).is_some_and(|g| g.is_empty()) {
+        output.insert(start, vec![vec![Grammer::Empty]]);
+        return (output, start);
+    }
+
+    let new_start = GrammerIdentifier(
+        output
+            .keys()
+            .chain(set.keys())
+            .map(|id| id.0 + 1)
+            .max()
+            .unwrap(),
+    );
+    let mut grammers = vec![vec![Grammer::Empty]];
+    if output.get(&start).is_some_and(|g| !g.is_empty()) {
+        grammers.insert(0, vec![Grammer::Grammer(start)]);
+    }
+    output.insert(new_start, grammers);
+    (output, new_start)
+}
+
+// 空語になりうる記号が MAX_NULLABLE 個を超える生成規則を A -> α T, T -> β に分ける
+fn split_nullable(set: &GrammerSet, nullable: &HashSet<GrammerIdentifier>) -> GrammerSet {
+    let mut next_id = set.keys().map(|id| id.0 + 1).max().unwrap_or(0);
+    let mut ids = set.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    let mut output = GrammerSet::new();
+    for id in ids {
+        let mut grammers = Vec::new();
+        for grammer in &set[&id] {
+            let mut grammer = grammer.clone();
+            let mut owner = id;
+            loop {
+                let positions = grammer
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, g)| matches!(g, Grammer::Grammer(r) if nullable.contains(r)))
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+                if positions.len() <= MAX_NULLABLE {
+                    break;
+                }
+                // α には MAX_NULLABLE - 1 個と T を残す
+                let tail = GrammerIdentifier(next_id);
+                next_id += 1;
+                let rest = grammer.split_off(positions[MAX_NULLABLE - 1]);
+                grammer.push(Grammer::Grammer(tail));
+                if owner == id {
+                    grammers.push(grammer);
+                } else {
+                    output.insert(owner, vec![grammer]);
+                }
+                owner = tail;
+                grammer = rest;
+            }
+            if owner == id {
+                grammers.push(grammer);
+            } else {
+                output.insert(owner, vec![grammer]);
+            }
+        }
+        output.insert(id, grammers);
+    }
+    output
+}
+
+// 空語になりうる記号を省いた全ての組み合わせ
+fn expand_nullable(
+    grammer: &[Grammer],
+    nullable: &HashSet<GrammerIdentifier>,
+) -> Vec<Vec<Grammer>> {
+    let mut candidates = vec![Vec::new()];
+    for item in grammer {
+        match item {
+            Grammer::Empty => {}
+            Grammer::Grammer(r) if nullable.contains(r) => {
+                let mut with = candidates.clone();
+                for candidate in with.iter_mut() {
+                    candidate.push(*item);
+                }
+                candidates.extend(with);
+            }
+            _ => {
+                for candidate in candidates.iter_mut() {
+                    candidate.push(*item);
+                }
+            }
+        }
+    }
+    candidates
+}
+
+#[test]
+fn eliminate_empty_test() {
+    use super::enumerate_sentences;
+    use crate::testing::process_str;
+
+    let gramconv = process_str("a = [ 'x' ] , { 'y' } , b ; b = [ 'z' ] ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let a = gramconv.get_identifier_map()["a"];
+
+    let (output, start) = eliminate_empty(set, a);
+    assert_ne!(start, a);
+    assert_eq!(
+        output[&start],
+        vec![vec![Grammer::Grammer(a)], vec![Grammer::Empty]]
+    );
+    for (id, grammers) in &output {
+        for grammer in grammers {
+            assert!(*id == start || !grammer.contains(&Grammer::Empty));
+        }
+    }
+    assert_eq!(
+        enumerate_sentences(set, a, 4),
+        enumerate_sentences(&output, start, 4)
+    );
+    assert!(enumerate_sentences(&output, start, 4).contains("xyyz"));
+
+    // 空語になりうる記号が多くても生成規則は指数的に増えない
+    let many = format!("a = {} ;", vec!["[ 'x' ]"; 24].join(" , "));
+    let gramconv = process_str(&many).unwrap();
+    let set = gramconv.get_grammer_set();
+    let (output, start) = eliminate_empty(set, GrammerIdentifier(0));
+    let count = output.values().map(|g| g.len()).sum::<usize>();
+    assert!(count < 4 * (1 << MAX_NULLABLE), "{}", count);
+    assert_eq!(
+        enumerate_sentences(set, GrammerIdentifier(0), 3),
+        enumerate_sentences(&output, start, 3)
+    );
+
+    // 空語しか導出しない開始記号は S -> ε になる
+    let set = GrammerSet::from([
+        (
+            GrammerIdentifier(0),
+            vec![vec![Grammer::Grammer(GrammerIdentifier(1))]],
+        ),
+        (GrammerIdentifier(1), vec![vec![Grammer::Empty]]),
+    ]);
+    let (output, start) = eliminate_empty(&set, GrammerIdentifier(0));
+    assert_eq!(start, GrammerIdentifier(0));
+    assert_eq!(
+        output,
+        GrammerSet::from([(start, vec![vec![Grammer::Empty]])])
+    );
+}
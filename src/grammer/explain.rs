@@ -0,0 +1,240 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::simplify::sorted_ids;
+use super::{Grammer, GrammerAnnotation, GrammerIdentifier, GrammerSet, Terminal};
+use crate::export::{nonterminal_name, nonterminal_names, symbol_name};
+
+// 生成規則 set[id][production] の position 番目の記号に注目する
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DerivationStep {
+    pub id: GrammerIdentifier,
+    pub production: usize,
+    pub position: usize,
+}
+
+impl GrammerAnnotation {
+    fn first_contains(&self, id: GrammerIdentifier, c: Terminal) -> bool {
+        self.first_set
+            .get(&Grammer::Grammer(id))
+            .is_some_and(|first| first.contains(&Grammer::from(c)))
+    }
+
+    // `c` ∈ FIRST(id) の根拠. 最後の手順は終端記号 `c` を指す
+    pub fn explain_first(
+        &self,
+        set: &GrammerSet,
+        id: GrammerIdentifier,
+        c: Terminal,
+    ) -> Option<Vec<DerivationStep>> {
+        let mut parent: HashMap<GrammerIdentifier, Option<DerivationStep>> =
+            HashMap::from([(id, None)]);
+        let mut queue = VecDeque::from([id]);
+        while let Some(current) = queue.pop_front() {
+            for (production, grammer) in set.get(&current).into_iter().flatten().enumerate() {
+                for (position, item) in grammer.iter().enumerate() {
+                    let step = DerivationStep {
+                        id: current,
+                        production,
+                        position,
+                    };
+                    match item {
+                        item if item.terminal() == Some(c) => {
+                            let mut trace = vec![step];
+                            let mut back = parent[&current];
+                            while let Some(step) = back {
+                                trace.push(step);
+                                back = parent[&step.id];
+                            }
+                            trace.reverse();
+                            return Some(trace);
+                        }
+                        Grammer::Grammer(next)
+                            if !parent.contains_key(next) && self.first_contains(*next, c) =>
+                        {
+                            parent.insert(*next, Some(step));
+                            queue.push_back(*next);
+                        }
+                        _ => {}
+                    }
+                    if !self.is_nullable(std::slice::from_ref(item)) {
+                        break;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // `c` ∈ FOLLOW(id) の根拠
+    // id の出現 (と, 末尾なら外側の出現) を辿り, 後続の記号から FIRST の根拠へ続く
+    pub fn explain_follow(
+        &self,
+        set: &GrammerSet,
+        id: GrammerIdentifier,
+        c: Terminal,
+    ) -> Option<Vec<DerivationStep>> {
+        let mut parent: HashMap<GrammerIdentifier, Option<(GrammerIdentifier, DerivationStep)>> =
+            HashMap::from([(id, None)]);
+        let mut queue = VecDeque::from([id]);
+        let ids = sorted_ids(set);
+        while let Some(current) = queue.pop_front() {
+            for owner in &ids {
+                for (production, grammer) in set[owner].iter().enumerate() {
+                    for (position, item) in grammer.iter().enumerate() {
+                        if *item != Grammer::Grammer(current) {
+                            continue;
+                        }
+                        let occurrence = DerivationStep {
+                            id: *owner,
+                            production,
+                            position,
+                        };
+                        let mut rest_nullable = true;
+                        for (next, rest) in grammer.iter().enumerate().skip(position + 1) {
+                            let tail = match rest {
+                                rest if rest.terminal() == Some(c) => Some(Vec::new()),
+                                Grammer::Grammer(r) if self.first_contains(*r, c) => {
+                                    self.explain_first(set, *r, c)
+                                }
+                                _ => None,
+                            };
+                            if let Some(tail) = tail {
+                                let mut trace = Vec::new();
+                                let mut back = parent[&current];
+                                while let Some((child, step)) = back {
+                                    trace.push(step);
+                                    back = parent[&child];
+                                }
+                                trace.reverse();
+                                trace.push(occurrence);
+                                trace.push(DerivationStep {
+                                    position: next,
+                                    ..occurrence
+                                });
+                                trace.extend(tail);
+                                return Some(trace);
+                            }
+                            if !self.is_nullable(std::slice::from_ref(rest)) {
+                                rest_nullable = false;
+                                break;
+                            }
+                        }
+                        if rest_nullable && !parent.contains_key(owner) {
+                            parent.insert(*owner, Some((current, occurrence)));
+                            queue.push_back(*owner);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // LL(1) 表で set[id][production] が先読み `c` で選ばれる根拠
+    pub fn explain_lookahead(
+        &self,
+        set: &GrammerSet,
+        id: GrammerIdentifier,
+        production: usize,
+        c: Terminal,
+    ) -> Option<Vec<DerivationStep>> {
+        let grammer = &set[&id][production];
+        for (position, item) in grammer.iter().enumerate() {
+            let step = DerivationStep {
+                id,
+                production,
+                position,
+            };
+            match item {
+                item if item.terminal() == Some(c) => return Some(vec![step]),
+                Grammer::Grammer(r) if self.first_contains(*r, c) => {
+                    let mut trace = vec![step];
+                    trace.extend(self.explain_first(set, *r, c)?);
+                    return Some(trace);
+                }
+                _ => {}
+            }
+            if !self.is_nullable(std::slice::from_ref(item)) {
+                return None;
+            }
+        }
+        self.explain_follow(set, id, c)
+    }
+}
+
+// 注目する記号を [ ] で囲み, 名前の付いた規則は EBNF の名前で表示する
+pub fn display_trace(
+    set: &GrammerSet,
+    trace: &[DerivationStep],
+    identifier_map: &HashMap<String, GrammerIdentifier>,
+    tokens: &[String],
+) -> String {
+    let names = nonterminal_names(identifier_map);
+
+    let mut output = String::new();
+    for step in trace {
+        output += &format!("{} =", nonterminal_name(&names, step.id));
+        for (position, item) in set[&step.id][step.production].iter().enumerate() {
+            let symbol = symbol_name(&names, tokens, item);
+            if position == step.position {
+                output += &format!(" [{}]", symbol);
+            } else {
+                output += &format!(" {}", symbol);
+            }
+        }
+        output += "\n";
+    }
+    output
+}
+
+#[test]
+fn explain_test() {
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str(
+        "stmts = stmt , { ';' , stmt } ;
+                 stmt = ( name , ':' , expr ) | ( 'if' , expr ) ;
+                 expr = name , [ '+' , expr ] ;
+                 name = 'x' | 'y' ;",
+    )
+    .unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let map = gramconv.get_identifier_map();
+    let annotation = GrammerAnnotation::new(set);
+
+    let trace = annotation
+        .explain_first(set, map["stmt"], 'y'.into())
+        .unwrap();
+    assert_eq!(
+        display_trace(set, &trace, map, &[]),
+        "stmt = [name] ':' expr\nname = ['y']\n"
+    );
+
+    // name の後ろの <11> が空語になりうるので stmts の繰り返しまで遡る
+    let trace = annotation
+        .explain_follow(set, map["name"], ';'.into())
+        .unwrap();
+    assert_eq!(
+        display_trace(set, &trace, map, &[]),
+        "expr = [name] <11>\n\
+         stmt = name ':' [expr]\n\
+         stmts = [stmt] <6>\n\
+         stmts = stmt [<6>]\n\
+         <6> = [';'] stmt <6>\n"
+    );
+
+    let trace = annotation
+        .explain_lookahead(set, map["stmt"], 1, 'i'.into())
+        .unwrap();
+    assert_eq!(
+        display_trace(set, &trace, map, &[]),
+        "stmt = ['i'] 'f' expr\n"
+    );
+    assert!(annotation
+        .explain_first(set, map["expr"], 'i'.into())
+        .is_none());
+    assert!(annotation
+        .explain_follow(set, map["stmts"], 'x'.into())
+        .is_none());
+}
@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+
+use super::cnf::remove_non_generating;
+use super::simplify::{remove_unreachable, sorted_ids};
+use super::{
+    eliminate_empty, eliminate_left_recursion, CnfGrammer, Grammer, GrammerIdentifier, GrammerSet,
+    Terminal,
+};
+
+// 全ての生成規則が A -> a B1 B2 ... Bn の形 (空語は開始記号のみ)
+#[derive(Debug)]
+pub struct GnfGrammer {
+    pub set: GrammerSet,
+    pub start: GrammerIdentifier,
+}
+
+impl GnfGrammer {
+    // CNF にしてから左再帰を除去し, 先頭の非終端記号を後ろから順に展開する
+    pub fn convert(set: &GrammerSet, start: GrammerIdentifier) -> Result<GnfGrammer> {
+        let cnf = CnfGrammer::convert(set, start);
+        let start = cnf.start;
+        let mut base = cnf.set;
+        let grammers = base.get_mut(&start).unwrap();
+        let accepts_empty = grammers.contains(&vec![Grammer::Empty]);
+        grammers.retain(|g| *g != [Grammer::Empty]);
+        remove_non_generating(&mut base);
+        base.entry(start).or_default();
+
+        let (output, _) = eliminate_left_recursion(&base)?;
+        // 左再帰の除去で入った A' -> ε を取り除く
+        let (mut output, _) = eliminate_empty(&output, start);
+
+        for id in leading_postorder(&output) {
+            let grammers = output.remove(&id).unwrap();
+            let mut replaced: Vec<Vec<Grammer>> = Vec::new();
+            for grammer in grammers {
+                let expanded = match grammer[0] {
+                    Grammer::Grammer(head) => output[&head]
+                        .iter()
+                        .map(|delta| delta.iter().chain(&grammer[1..]).copied().collect())
+                        .collect(),
+                    _ => vec![grammer],
+                };
+                for grammer in expanded {
+                    if !replaced.contains(&grammer) {
+                        replaced.push(grammer);
+                    }
+                }
+            }
+            output.insert(id, replaced);
+        }
+
+        // 2 番目以降の終端記号を持ち上げる
+        let mut next_id = output.keys().map(|id| id.0 + 1).max().unwrap_or(0);
+        let mut terminals: HashMap<Terminal, GrammerIdentifier> = HashMap::new();
+        for grammers in output.values_mut() {
+            for grammer in grammers.iter_mut() {
+                for item in grammer.iter_mut().skip(1) {
+                    if let Some(c) = item.terminal() {
+                        let lifted = *terminals.entry(c).or_insert_with(|| {
+                            next_id += 1;
+                            GrammerIdentifier(next_id - 1)
+                        });
+                        *item = Grammer::Grammer(lifted);
+                    }
+                }
+            }
+        }
+        for (c, id) in terminals {
+            output.insert(id, vec![vec![Grammer::from(c)]]);
+        }
+
+        if accepts_empty {
+            output.get_mut(&start).unwrap().push(vec![Grammer::Empty]);
+        }
+        remove_unreachable(&mut output, start);
+
+        let gnf = GnfGrammer { set: output, start };
+        debug_assert!(gnf.is_normal_form());
+        Ok(gnf)
+    }
+
+    pub fn is_normal_form(&self) -> bool {
+        self.set.iter().all(|(id, grammers)| {
+            grammers.iter().all(|grammer| match &grammer[..] {
+                [Grammer::Empty] => *id == self.start,
+                [Grammer::Character(_) | Grammer::Token(_), rest @ ..] => rest
+                    .iter()
+                    .all(|g| matches!(g, Grammer::Grammer(r) if *r != self.start)),
+                _ => false,
+            })
+        })
+    }
+}
+
+// 先頭の非終端記号が先に来る順 (左再帰が無いので必ず順序が付く)
+fn leading_postorder(set: &GrammerSet) -> Vec<GrammerIdentifier> {
+    fn visit(
+        set: &GrammerSet,
+        id: GrammerIdentifier,
+        visited: &mut HashSet<GrammerIdentifier>,
+        order: &mut Vec<GrammerIdentifier>,
+    ) {
+        if !visited.insert(id) {
+            return;
+        }
+        for grammer in &set[&id] {
+            if let Some(Grammer::Grammer(head)) = grammer.first() {
+                visit(set, *head, visited, order);
+            }
+        }
+        order.push(id);
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for id in sorted_ids(set) {
+        visit(set, id, &mut visited, &mut order);
+    }
+    order
+}
+
+#[test]
+fn gnf_test() {
+    use super::enumerate::compare_languages;
+    use crate::testing::process_str;
+
+    let gramconv = process_str(
+        "expr = ( expr , '+' , term ) | term ; term = ( '(' , expr , ')' ) | [ '-' ] , 'x' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let expr = gramconv.get_identifier_map()["expr"];
+
+    let gnf = GnfGrammer::convert(set, expr).unwrap();
+    assert!(gnf.is_normal_form());
+    assert_eq!(compare_languages(set, expr, &gnf.set, gnf.start, 7), None);
+}
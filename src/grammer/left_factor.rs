@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    create_nullable_set, create_sequence_first_set, find_left_recursion, Grammer,
+    GrammerIdentifier, GrammerSet,
+};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LeftFactorReport {
+    // 追加した非終端記号と, その元になった非終端記号
+    pub factored: HashMap<GrammerIdentifier, GrammerIdentifier>,
+    pub substituted: usize,
+    // FIRST が重なる選択肢が残った非終端記号
+    pub remaining_conflicts: Vec<GrammerIdentifier>,
+}
+
+// 共通接頭辞をくくり出し, それでも FIRST が重なる選択肢は先頭の非終端記号を展開して繰り返す
+// 展開は `max_substitutions` 回まで
+pub fn left_factor(
+    set: &GrammerSet,
+    max_substitutions: usize,
+) -> Result<(GrammerSet, LeftFactorReport)> {
+    if let Some(id) = find_left_recursion(set) {
+        return Err(anyhow!(
+            "<{}> is left recursive; eliminate left recursion first",
+            id.0
+        ));
+    }
+
+    let mut output = set.clone();
+    let mut report = LeftFactorReport::default();
+    let mut next_id = output.keys().map(|id| id.0 + 1).max().unwrap_or(0);
+
+    loop {
+        // くくり出した非終端記号の選択肢にも共通接頭辞が残りうるので, それも調べる
+        let mut ids = output.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+        let mut worklist = VecDeque::from(ids);
+        while let Some(id) = worklist.pop_front() {
+            while let Some((index, prefix_len)) = find_common_prefix(&output[&id]) {
+                let factored = GrammerIdentifier(next_id);
+                next_id += 1;
+                report.factored.insert(factored, id);
+                worklist.push_back(factored);
+
+                let grammers = output.remove(&id).unwrap();
+                let head = grammers[index][0];
+                let prefix = grammers[index][..prefix_len].to_vec();
+                let mut replaced = Vec::new();
+                let mut suffixes = Vec::new();
+                for grammer in grammers {
+                    if grammer.first() != Some(&head) {
+                        replaced.push(grammer);
+                        continue;
+                    }
+                    if suffixes.is_empty() {
+                        let mut factored_grammer = prefix.clone();
+                        factored_grammer.push(Grammer::Grammer(factored));
+                        replaced.push(factored_grammer);
+                    }
+                    let suffix = grammer[prefix_len..].to_vec();
+                    if suffix.is_empty() {
+                        suffixes.push(vec![Grammer::Empty]);
+                    } else {
+                        suffixes.push(suffix);
+                    }
+                }
+                output.insert(id, replaced);
+                output.insert(factored, suffixes);
+            }
+        }
+
+        if report.substituted >= max_substitutions || !substitute_conflicts(&mut output) {
+            break;
+        }
+        report.substituted += 1;
+    }
+
+    let mut ids = output.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    report.remaining_conflicts = ids
+        .into_iter()
+        .filter(|id| find_conflict(&output, *id).is_some())
+        .collect();
+
+    Ok((output, report))
+}
+
+// 先頭記号が同じ選択肢の組の最長共通接頭辞
+fn find_common_prefix(grammers: &[Vec<Grammer>]) -> Option<(usize, usize)> {
+    for (i, grammer) in grammers.iter().enumerate() {
+        let head = match grammer.first() {
+            Some(Grammer::Empty) | None => continue,
+            Some(head) => head,
+        };
+        let same = grammers
+            .iter()
+            .skip(i + 1)
+            .filter(|g| g.first() == Some(head))
+            .collect::<Vec<_>>();
+        if same.is_empty() {
+            continue;
+        }
+        let prefix_len = same
+            .iter()
+            .map(|g| {
+                g.iter()
+                    .zip(grammer.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .min()
+            .unwrap();
+        return Some((i, prefix_len));
+    }
+    None
+}
+
+fn find_conflict(set: &GrammerSet, id: GrammerIdentifier) -> Option<(usize, usize)> {
+    let nullable = create_nullable_set(set);
+    let firsts = set[&id]
+        .iter()
+        .map(|g| create_sequence_first_set(set, &nullable, g))
+        .collect::<Vec<HashSet<_>>>();
+    for i in 0..firsts.len() {
+        for j in i + 1..firsts.len() {
+            if !firsts[i].is_disjoint(&firsts[j]) {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+// FIRST が重なる選択肢の先頭の非終端記号を一つ展開する
+fn substitute_conflicts(set: &mut GrammerSet) -> bool {
+    let mut ids = set.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    for id in ids {
+        let Some((i, j)) = find_conflict(set, id) else {
+            continue;
+        };
+        let Some(index) = [i, j]
+            .into_iter()
+            .find(|k| matches!(set[&id][*k].first(), Some(Grammer::Grammer(_))))
+        else {
+            continue;
+        };
+
+        let mut grammers = set.remove(&id).unwrap();
+        let grammer = grammers.remove(index);
+        let Grammer::Grammer(head) = grammer[0] else {
+            unreachable!()
+        };
+        let expanded = set[&head]
+            .iter()
+            .map(|delta| {
+                let grammer = delta
+                    .iter()
+                    .chain(&grammer[1..])
+                    .filter(|g| **g != Grammer::Empty)
+                    .copied()
+                    .collect::<Vec<_>>();
+                if grammer.is_empty() {
+                    vec![Grammer::Empty]
+                } else {
+                    grammer
+                }
+            })
+            .collect::<Vec<_>>();
+        for (k, grammer) in expanded.into_iter().enumerate() {
+            grammers.insert(index + k, grammer);
+        }
+        set.insert(id, grammers);
+        return true;
+    }
+    false
+}
+
+#[test]
+fn left_factor_keyword_test() {
+    use crate::ll::LL1Table;
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str(
+        "keyword = 'program' | 'print' | 'var' | procedure ; procedure = 'procedure' ;",
+    )
+    .unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let keyword = gramconv.get_identifier_map()["keyword"];
+
+    let table = LL1Table::build(set, keyword).unwrap();
+    assert!(!table.conflicts().is_empty());
+
+    let (factored, report) = left_factor(set, 8).unwrap();
+    assert_eq!(report.substituted, 2);
+    assert!(report.remaining_conflicts.is_empty());
+
+    let table = LL1Table::build(&factored, keyword).unwrap();
+    assert!(table.conflicts().is_empty());
+    for word in ["program", "print", "var", "procedure"] {
+        let input = word.chars().collect::<Vec<_>>();
+        let tree = table.parse(&factored, &input).unwrap();
+        assert_eq!(tree.text(), word);
+    }
+}
+
+#[test]
+fn nested_prefix_test() {
+    use crate::ll::LL1Table;
+    use crate::testing::process_str;
+
+    // 'p' をくくり出した後の選択肢にも 'r' 'o' が共通する
+    let mut gramconv = process_str("k = 'prog' | 'proc' | 'pa' ;").unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let k = gramconv.get_identifier_map()["k"];
+
+    let (factored, report) = left_factor(set, 8).unwrap();
+    assert_eq!(report.substituted, 0);
+    assert_eq!(report.factored.len(), 2);
+    assert!(report.remaining_conflicts.is_empty());
+
+    let table = LL1Table::build(&factored, k).unwrap();
+    assert!(table.conflicts().is_empty());
+    for word in ["prog", "proc", "pa"] {
+        let input = word.chars().collect::<Vec<_>>();
+        let tree = table.parse(&factored, &input).unwrap();
+        assert_eq!(tree.text(), word);
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use super::{create_nullable_set, Grammer, GrammerIdentifier, GrammerSet, ParseTree};
+
+// 変換後の生成規則が元の文法のどの生成規則から作られたか
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Origin {
+    // 元の生成規則の番号
+    Original(usize),
+    // 先頭の `head` を展開した. 先頭 `len` 個の子は `inner` による `head` の木になる
+    Substituted {
+        outer: Box<Origin>,
+        head: GrammerIdentifier,
+        len: usize,
+        inner: Box<Origin>,
+    },
+    // A -> β A' の形. 最後の子が A' の木
+    Head(Box<Origin>),
+    // A' -> α A' の形. 元は A -> A α
+    Tail(Box<Origin>),
+    // A' -> ε
+    TailEnd,
+}
+
+#[derive(Debug, Default)]
+pub struct LeftRecursionMap {
+    pub origins: HashMap<GrammerIdentifier, Vec<Origin>>,
+}
+
+enum Restored<'a> {
+    Tree(ParseTree),
+    Tail(Vec<(&'a Origin, Vec<Restored<'a>>)>),
+}
+
+impl LeftRecursionMap {
+    // 変換後の文法で作った構文木を元の文法の形に戻す
+    pub fn restore(&self, tree: &ParseTree) -> Result<ParseTree> {
+        match self.restore_node(tree)? {
+            Restored::Tree(tree) => Ok(tree),
+            Restored::Tail(_) => Err(anyhow!("Tail rule cannot be the root of a tree")),
+        }
+    }
+
+    fn restore_node(&self, tree: &ParseTree) -> Result<Restored<'_>> {
+        let (id, production, children) = match tree {
+            ParseTree::Leaf(c) => return Ok(Restored::Tree(ParseTree::Leaf(*c))),
+            ParseTree::Node {
+                id,
+                production,
+                children,
+            } => (*id, *production, children),
+        };
+        let origin = self
+            .origins
+            .get(&id)
+            .and_then(|o| o.get(production))
+            .ok_or(anyhow!("Unknown production {} of <{}>", production, id.0))?;
+        let mut items = children
+            .iter()
+            .map(|c| self.restore_node(c))
+            .collect::<Result<Vec<_>>>()?;
+
+        match origin {
+            Origin::Tail(origin) => {
+                let Some(Restored::Tail(mut chain)) = items.pop() else {
+                    return Err(anyhow!("Tail rule <{}> must end with itself", id.0));
+                };
+                chain.insert(0, (origin.as_ref(), items));
+                Ok(Restored::Tail(chain))
+            }
+            Origin::TailEnd => Ok(Restored::Tail(Vec::new())),
+            _ => Ok(Restored::Tree(Self::apply(origin, id, items)?)),
+        }
+    }
+
+    fn apply<'a>(
+        origin: &'a Origin,
+        id: GrammerIdentifier,
+        mut items: Vec<Restored<'a>>,
+    ) -> Result<ParseTree> {
+        match origin {
+            Origin::Original(production) => {
+                let children = items
+                    .into_iter()
+                    .map(|item| match item {
+                        Restored::Tree(tree) => Ok(tree),
+                        Restored::Tail(_) => Err(anyhow!("Unexpected tail rule under <{}>", id.0)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(ParseTree::Node {
+                    id,
+                    production: *production,
+                    children,
+                })
+            }
+            Origin::Substituted {
+                outer,
+                head,
+                len,
+                inner,
+            } => {
+                let rest = items.split_off(*len);
+                let head_tree = Self::apply(inner, *head, items)?;
+                let mut items = vec![Restored::Tree(head_tree)];
+                items.extend(rest);
+                Self::apply(outer, id, items)
+            }
+            Origin::Head(base) => {
+                let Some(Restored::Tail(chain)) = items.pop() else {
+                    return Err(anyhow!("Rule <{}> must end with its tail rule", id.0));
+                };
+                let mut tree = Self::apply(base, id, items)?;
+                for (origin, alpha) in chain {
+                    let mut items = vec![Restored::Tree(tree)];
+                    items.extend(alpha);
+                    tree = Self::apply(origin, id, items)?;
+                }
+                Ok(tree)
+            }
+            Origin::Tail(_) | Origin::TailEnd => {
+                Err(anyhow!("Tail rule used outside of <{}>", id.0))
+            }
+        }
+    }
+}
+
+// 左再帰している非終端記号を一つ返す (空語になりうる接頭辞を通るものも含む)
+pub fn find_left_recursion(set: &GrammerSet) -> Option<GrammerIdentifier> {
+    let nullable = create_nullable_set(set);
+    let mut edges: HashMap<GrammerIdentifier, HashSet<GrammerIdentifier>> = HashMap::new();
+    for (id, grammers) in set {
+        for grammer in grammers {
+            for item in grammer {
+                match item {
+                    Grammer::Grammer(r) => {
+                        edges.entry(*id).or_default().insert(*r);
+                        if !nullable.contains(r) {
+                            break;
+                        }
+                    }
+                    Grammer::Empty => {}
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    let mut ids = set.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    ids.into_iter().find(|id| left_reaches(&edges, *id, *id))
+}
+
+fn left_reaches(
+    edges: &HashMap<GrammerIdentifier, HashSet<GrammerIdentifier>>,
+    from: GrammerIdentifier,
+    target: GrammerIdentifier,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut queue = vec![from];
+    while let Some(current) = queue.pop() {
+        for next in edges.get(&current).into_iter().flatten() {
+            if *next == target {
+                return true;
+            }
+            if visited.insert(*next) {
+                queue.push(*next);
+            }
+        }
+    }
+    false
+}
+
+type Productions = HashMap<GrammerIdentifier, Vec<(Vec<Grammer>, Origin)>>;
+
+fn leading_edges(work: &Productions) -> HashMap<GrammerIdentifier, HashSet<GrammerIdentifier>> {
+    work.iter()
+        .map(|(id, grammers)| {
+            let heads = grammers
+                .iter()
+                .filter_map(|(grammer, _)| match grammer.first() {
+                    Some(Grammer::Grammer(r)) => Some(*r),
+                    _ => None,
+                })
+                .collect();
+            (*id, heads)
+        })
+        .collect()
+}
+
+// 先頭を展開した後の列の空語を整理する
+fn concat(head: &[Grammer], rest: &[Grammer]) -> Vec<Grammer> {
+    let grammer = head
+        .iter()
+        .chain(rest)
+        .filter(|g| **g != Grammer::Empty)
+        .copied()
+        .collect::<Vec<_>>();
+    if grammer.is_empty() {
+        vec![Grammer::Empty]
+    } else {
+        grammer
+    }
+}
+
+fn symbol_len(grammer: &[Grammer]) -> usize {
+    grammer.iter().filter(|g| **g != Grammer::Empty).count()
+}
+
+// 直接・間接の左再帰を除去する
+// 先頭の非終端記号の展開は左再帰の解消に必要なものだけ行う
+pub fn eliminate_left_recursion(set: &GrammerSet) -> Result<(GrammerSet, LeftRecursionMap)> {
+    let mut order = set.keys().copied().collect::<Vec<_>>();
+    order.sort_by_key(|id| id.0);
+    let mut next_id = order.last().map(|id| id.0 + 1).unwrap_or(0);
+
+    let mut work: Productions = set
+        .iter()
+        .map(|(id, grammers)| {
+            let grammers = grammers
+                .iter()
+                .enumerate()
+                .map(|(i, g)| (g.clone(), Origin::Original(i)))
+                .collect();
+            (*id, grammers)
+        })
+        .collect();
+
+    for i in 0..order.len() {
+        let target = order[i];
+        for head in order.iter().take(i) {
+            let edges = leading_edges(&work);
+            if !left_reaches(&edges, *head, target) {
+                continue;
+            }
+            let head_grammers = work[head].clone();
+            let mut replaced = Vec::new();
+            for (grammer, origin) in work.remove(&target).unwrap() {
+                if grammer.first() != Some(&Grammer::Grammer(*head)) {
+                    replaced.push((grammer, origin));
+                    continue;
+                }
+                for (delta, inner) in &head_grammers {
+                    replaced.push((
+                        concat(delta, &grammer[1..]),
+                        Origin::Substituted {
+                            outer: Box::new(origin.clone()),
+                            head: *head,
+                            len: symbol_len(delta),
+                            inner: Box::new(inner.clone()),
+                        },
+                    ));
+                }
+            }
+            work.insert(target, replaced);
+        }
+
+        let (recursive, base): (Vec<_>, Vec<_>) = work
+            .remove(&target)
+            .unwrap()
+            .into_iter()
+            .partition(|(grammer, _)| grammer.first() == Some(&Grammer::Grammer(target)));
+        if recursive.is_empty() {
+            work.insert(target, base);
+            continue;
+        }
+        if base.is_empty() {
+            return Err(anyhow!("<{}> has no non-recursive alternative", target.0));
+        }
+
+        let tail = GrammerIdentifier(next_id);
+        next_id += 1;
+        let mut tail_grammers = Vec::new();
+        for (grammer, origin) in recursive {
+            if grammer.len() == 1 {
+                return Err(anyhow!("<{}> derives itself", target.0));
+            }
+            let mut alpha = grammer[1..].to_vec();
+            alpha.push(Grammer::Grammer(tail));
+            tail_grammers.push((alpha, Origin::Tail(Box::new(origin))));
+        }
+        tail_grammers.push((vec![Grammer::Empty], Origin::TailEnd));
+        let base = base
+            .into_iter()
+            .map(|(grammer, origin)| {
+                (
+                    concat(&grammer, &[Grammer::Grammer(tail)]),
+                    Origin::Head(Box::new(origin)),
+                )
+            })
+            .collect();
+        work.insert(target, base);
+        work.insert(tail, tail_grammers);
+    }
+
+    let mut output = GrammerSet::new();
+    let mut map = LeftRecursionMap::default();
+    for (id, grammers) in work {
+        let (grammers, origins) = grammers.into_iter().unzip();
+        output.insert(id, grammers);
+        map.origins.insert(id, origins);
+    }
+
+    if let Some(id) = find_left_recursion(&output) {
+        return Err(anyhow!(
+            "Left recursion through a nullable prefix remains in <{}>",
+            id.0
+        ));
+    }
+
+    Ok((output, map))
+}
+
+#[test]
+fn eliminate_left_recursion_test() {
+    use crate::ll::LL1Table;
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str("expr = ( expr , '+' , term ) | term ; term = 'x' ;").unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let expr = gramconv.get_identifier_map()["expr"];
+
+    assert_eq!(find_left_recursion(set), Some(expr));
+    assert!(LL1Table::build(set, expr).is_err());
+
+    let (transformed, map) = eliminate_left_recursion(set).unwrap();
+    assert_eq!(find_left_recursion(&transformed), None);
+    let table = LL1Table::build(&transformed, expr).unwrap();
+    assert!(table.conflicts().is_empty());
+
+    let tree = table
+        .parse(&transformed, &['x', '+', 'x', '+', 'x'])
+        .unwrap();
+    let restored = map.restore(&tree).unwrap();
+    assert_eq!(restored.text(), "x+x+x");
+
+    // ((x + x) + x) の形に戻る
+    let ParseTree::Node { id, children, .. } = &restored else {
+        panic!("unexpected leaf");
+    };
+    assert_eq!(*id, expr);
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0].text(), "x+x");
+    assert!(matches!(&children[0], ParseTree::Node { id, .. } if *id == expr));
+}
+
+#[test]
+fn eliminate_indirect_left_recursion_test() {
+    let a = GrammerIdentifier(0);
+    let b = GrammerIdentifier(1);
+    // a = b 'x' | 'y' ; b = a 'z' | 'w'
+    let set = GrammerSet::from([
+        (
+            a,
+            vec![
+                vec![Grammer::Grammer(b), Grammer::Character('x')],
+                vec![Grammer::Character('y')],
+            ],
+        ),
+        (
+            b,
+            vec![
+                vec![Grammer::Grammer(a), Grammer::Character('z')],
+                vec![Grammer::Character('w')],
+            ],
+        ),
+    ]);
+    assert!(find_left_recursion(&set).is_some());
+    let (transformed, map) = eliminate_left_recursion(&set).unwrap();
+    assert_eq!(find_left_recursion(&transformed), None);
+
+    let tail = GrammerIdentifier(2);
+    let index = |id: GrammerIdentifier, grammer: &[Grammer]| {
+        transformed[&id].iter().position(|p| p == grammer).unwrap()
+    };
+    // "wxzx" を変換後の文法で導出した木
+    let tree = ParseTree::Node {
+        id: a,
+        production: index(a, &[Grammer::Grammer(b), Grammer::Character('x')]),
+        children: vec![
+            ParseTree::Node {
+                id: b,
+                production: index(b, &[Grammer::Character('w'), Grammer::Grammer(tail)]),
+                children: vec![
+                    ParseTree::Leaf('w'.into()),
+                    ParseTree::Node {
+                        id: tail,
+                        production: index(
+                            tail,
+                            &[
+                                Grammer::Character('x'),
+                                Grammer::Character('z'),
+                                Grammer::Grammer(tail),
+                            ],
+                        ),
+                        children: vec![
+                            ParseTree::Leaf('x'.into()),
+                            ParseTree::Leaf('z'.into()),
+                            ParseTree::Node {
+                                id: tail,
+                                production: index(tail, &[Grammer::Empty]),
+                                children: vec![],
+                            },
+                        ],
+                    },
+                ],
+            },
+            ParseTree::Leaf('x'.into()),
+        ],
+    };
+    let restored = map.restore(&tree).unwrap();
+    // a -> b 'x', b -> a 'z', a -> b 'x', b -> 'w'
+    let expected = ParseTree::Node {
+        id: a,
+        production: 0,
+        children: vec![
+            ParseTree::Node {
+                id: b,
+                production: 0,
+                children: vec![
+                    ParseTree::Node {
+                        id: a,
+                        production: 0,
+                        children: vec![
+                            ParseTree::Node {
+                                id: b,
+                                production: 1,
+                                children: vec![ParseTree::Leaf('w'.into())],
+                            },
+                            ParseTree::Leaf('x'.into()),
+                        ],
+                    },
+                    ParseTree::Leaf('z'.into()),
+                ],
+            },
+            ParseTree::Leaf('x'.into()),
+        ],
+    };
+    assert_eq!(restored, expected);
+}
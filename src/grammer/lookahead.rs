@@ -0,0 +1,293 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Grammer, GrammerIdentifier, GrammerSet, Terminal};
+
+// 長さ k 未満の終端記号列は入力 (または導出) がそこで終わることを表す
+pub type LookaheadSet = HashSet<Vec<Terminal>>;
+
+// 連接して先頭 k 文字で切る
+fn concat_k(left: &LookaheadSet, right: &LookaheadSet, k: usize) -> LookaheadSet {
+    let mut output = HashSet::new();
+    for l in left {
+        if l.len() >= k {
+            output.insert(l.clone());
+            continue;
+        }
+        for r in right {
+            output.insert(l.iter().chain(r).take(k).copied().collect());
+        }
+    }
+    output
+}
+
+pub fn create_sequence_first_k_set(
+    first_k: &HashMap<GrammerIdentifier, LookaheadSet>,
+    grammer: &[Grammer],
+    k: usize,
+) -> LookaheadSet {
+    let mut output = HashSet::from([Vec::new()]);
+    for item in grammer {
+        let next = match item {
+            Grammer::Empty => continue,
+            Grammer::Character(_) | Grammer::Token(_) => {
+                HashSet::from([item.terminal().into_iter().collect()])
+            }
+            Grammer::Grammer(id) => first_k.get(id).cloned().unwrap_or_default(),
+            Grammer::Dot => panic!("Dot not allowed"),
+        };
+        output = concat_k(&output, &next, k);
+    }
+    output
+}
+
+// 一つの先読み集合に入れる文字列の上限. 文字単位の文法では k に対して指数的に増えるので,
+// これを超えたらその k は調べない
+const MAX_LOOKAHEAD_STRINGS: usize = 1 << 12;
+
+// FIRST_k を不動点で求める
+#[cfg(test)]
+pub fn create_first_k_set(set: &GrammerSet, k: usize) -> HashMap<GrammerIdentifier, LookaheadSet> {
+    let ids = set.keys().copied().collect();
+    first_k_within(set, &ids, k, usize::MAX).unwrap()
+}
+
+// ids の FIRST_k. どれかが limit を超えたら None
+fn first_k_within(
+    set: &GrammerSet,
+    ids: &HashSet<GrammerIdentifier>,
+    k: usize,
+    limit: usize,
+) -> Option<HashMap<GrammerIdentifier, LookaheadSet>> {
+    let mut first_k: HashMap<GrammerIdentifier, LookaheadSet> = HashMap::new();
+    loop {
+        let mut found = false;
+        for id in ids {
+            for grammer in &set[id] {
+                let first = create_sequence_first_k_set(&first_k, grammer, k);
+                let target = first_k.entry(*id).or_default();
+                for s in first {
+                    found |= target.insert(s);
+                }
+                if target.len() > limit {
+                    return None;
+                }
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+    Some(first_k)
+}
+
+// FOLLOW_k を不動点で求める (開始記号の後ろは入力の終端)
+#[cfg(test)]
+pub fn create_follow_k_set(
+    set: &GrammerSet,
+    first_k: &HashMap<GrammerIdentifier, LookaheadSet>,
+    start: GrammerIdentifier,
+    k: usize,
+) -> HashMap<GrammerIdentifier, LookaheadSet> {
+    let ids = set.keys().copied().collect();
+    follow_k_within(set, first_k, start, &ids, k, usize::MAX).unwrap()
+}
+
+// ids の FOLLOW_k. ids は ids の記号を右辺に持つ規則の左辺を全て含むこと
+fn follow_k_within(
+    set: &GrammerSet,
+    first_k: &HashMap<GrammerIdentifier, LookaheadSet>,
+    start: GrammerIdentifier,
+    ids: &HashSet<GrammerIdentifier>,
+    k: usize,
+    limit: usize,
+) -> Option<HashMap<GrammerIdentifier, LookaheadSet>> {
+    let mut follow_k: HashMap<GrammerIdentifier, LookaheadSet> =
+        HashMap::from([(start, HashSet::from([Vec::new()]))]);
+    loop {
+        let mut found = false;
+        for id in ids {
+            for grammer in &set[id] {
+                for (i, item) in grammer.iter().enumerate() {
+                    let Grammer::Grammer(target_id) = item else {
+                        continue;
+                    };
+                    if !ids.contains(target_id) {
+                        continue;
+                    }
+                    let rest = create_sequence_first_k_set(first_k, &grammer[i + 1..], k);
+                    let owner = follow_k.get(id).cloned().unwrap_or_default();
+                    let follow = concat_k(&rest, &owner, k);
+                    let target = follow_k.entry(*target_id).or_default();
+                    for s in follow {
+                        found |= target.insert(s);
+                    }
+                    if target.len() > limit {
+                        return None;
+                    }
+                }
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+    Some(follow_k)
+}
+
+// 選択肢を区別するのに要る先読みの長さ
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Lookahead {
+    // k 文字で区別できる. 選択肢が一つなら 0
+    Within(usize),
+    // k 文字までは区別できない (それより先は調べていない)
+    NotWithin(usize),
+}
+
+// conflicted の FOLLOW_k に要る非終端記号 (conflicted を右辺に持つ規則の左辺を辿る) と,
+// それらの右辺の FIRST_k に要る非終端記号
+fn dependencies(
+    set: &GrammerSet,
+    conflicted: &[GrammerIdentifier],
+) -> (HashSet<GrammerIdentifier>, HashSet<GrammerIdentifier>) {
+    let mut follow_ids = conflicted.iter().copied().collect::<HashSet<_>>();
+    loop {
+        let owners = set
+            .iter()
+            .filter(|(id, grammers)| {
+                !follow_ids.contains(*id)
+                    && grammers
+                        .iter()
+                        .flatten()
+                        .any(|g| matches!(g, Grammer::Grammer(r) if follow_ids.contains(r)))
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if owners.is_empty() {
+            break;
+        }
+        follow_ids.extend(owners);
+    }
+
+    let mut first_ids = HashSet::new();
+    let mut queue = follow_ids.iter().copied().collect::<Vec<_>>();
+    while let Some(id) = queue.pop() {
+        for g in set[&id].iter().flatten() {
+            if let Grammer::Grammer(r) = g {
+                if first_ids.insert(*r) {
+                    queue.push(*r);
+                }
+            }
+        }
+    }
+    (follow_ids, first_ids)
+}
+
+// 各非終端記号の選択肢を区別できる最小の k (`max_k` まで, 強 LL(k) の意味で).
+// k を増やすのは区別できていない非終端記号だけで, 先読み集合が大きくなりすぎたらそこで止める
+pub fn find_minimum_lookahead(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    max_k: usize,
+) -> HashMap<GrammerIdentifier, Lookahead> {
+    let mut minimum = HashMap::new();
+    let mut conflicted = Vec::new();
+    for (id, grammers) in set {
+        if grammers.len() <= 1 {
+            minimum.insert(*id, Lookahead::Within(0));
+        } else {
+            conflicted.push(*id);
+        }
+    }
+
+    let mut checked = 0;
+    for k in 1..=max_k {
+        if conflicted.is_empty() {
+            break;
+        }
+        let (follow_ids, first_ids) = dependencies(set, &conflicted);
+        let Some(first_k) = first_k_within(set, &first_ids, k, MAX_LOOKAHEAD_STRINGS) else {
+            break;
+        };
+        let Some(follow_k) =
+            follow_k_within(set, &first_k, start, &follow_ids, k, MAX_LOOKAHEAD_STRINGS)
+        else {
+            break;
+        };
+        conflicted.retain(|id| {
+            let follow = follow_k.get(id).cloned().unwrap_or_default();
+            let mut seen = HashSet::new();
+            let distinct = set[id].iter().all(|grammer| {
+                let lookahead = concat_k(
+                    &create_sequence_first_k_set(&first_k, grammer, k),
+                    &follow,
+                    k,
+                );
+                lookahead.into_iter().all(|s| seen.insert(s))
+            });
+            if distinct {
+                minimum.insert(*id, Lookahead::Within(k));
+            }
+            !distinct
+        });
+        checked = k;
+    }
+    for id in conflicted {
+        minimum.insert(id, Lookahead::NotWithin(checked));
+    }
+    minimum
+}
+
+#[test]
+fn minimum_lookahead_test() {
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str(
+        "keyword = 'program' | 'print' | 'var' | ( procedure , 'x' ) ;
+                 procedure = 'procedure' ;",
+    )
+    .unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let keyword = gramconv.get_identifier_map()["keyword"];
+    let procedure = gramconv.get_identifier_map()["procedure"];
+
+    let first_2 = create_first_k_set(set, 2);
+    let terminals = |s: &str| s.chars().map(Terminal::from).collect::<Vec<_>>();
+    assert_eq!(
+        first_2[&keyword],
+        HashSet::from([terminals("pr"), terminals("va")])
+    );
+    let follow_2 = create_follow_k_set(set, &first_2, keyword, 2);
+    assert_eq!(follow_2[&procedure], HashSet::from([terminals("x")]));
+
+    let minimum = find_minimum_lookahead(set, keyword, 5);
+    assert_eq!(minimum[&keyword], Lookahead::Within(4));
+    assert_eq!(minimum[&procedure], Lookahead::Within(0));
+
+    // 左再帰はどの k でも区別できない
+    let list = GrammerIdentifier(0);
+    let set = GrammerSet::from([(
+        list,
+        vec![
+            vec![Grammer::Grammer(list), Grammer::Character('x')],
+            vec![Grammer::Character('x')],
+        ],
+    )]);
+    assert_eq!(
+        find_minimum_lookahead(&set, list, 5)[&list],
+        Lookahead::NotWithin(5)
+    );
+
+    // --llk と同じ k の上限でも文字単位の文法で先読み集合が爆発しない
+    let mut gramconv = process_str(include_str!("../../test.ebnf")).unwrap();
+    gramconv.simplify();
+    let set = gramconv.get_grammer_set();
+    let minimum = find_minimum_lookahead(set, GrammerIdentifier(0), 8);
+    assert_eq!(minimum.len(), set.len());
+    let digit = gramconv.get_identifier_map()["digit"];
+    assert_eq!(minimum[&digit], Lookahead::Within(1));
+    // [ { x } ] は空の導出が二通りあるのでどの k でも区別できない
+    assert!(minimum
+        .values()
+        .any(|m| matches!(m, Lookahead::NotWithin(k) if *k < 8)));
+}
@@ -1,53 +1,216 @@
-use std::collections::{HashMap, HashSet};
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-pub struct GrammerIdentifier(pub u64);
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-pub enum Grammer {
-    Empty, // 空語
-    Dot,
-    Grammer(GrammerIdentifier),
-    Character(char),
-}
-
-pub type GrammerSet = HashMap<GrammerIdentifier, Vec<Vec<Grammer>>>;
-
-#[derive(Debug, PartialEq, Eq)]
-pub struct GrammerAnnotation {
-    pub endchars: HashSet<char>,
-    pub identifiers: HashSet<u64>,
-    pub first_set: HashMap<Grammer, HashSet<Grammer>>,
-    pub follow_set: HashMap<GrammerIdentifier, HashSet<Grammer>>,
-}
-
-pub fn display_grammer_set(set: &GrammerSet) {
-    for (id, grammers) in set {
-        for grammer in grammers {
-            print!("{} = ", id.0);
-            for item in grammer {
-                match item {
-                    Grammer::Empty => {
-                        print!("ε ");
-                    }
-                    Grammer::Dot => {
-                        print!("* ");
-                    }
-                    Grammer::Grammer(GrammerIdentifier(id)) => {
-                        print!("<{}> ", id);
-                    }
-                    Grammer::Character(c) => {
-                        print!("'{}' ", c);
-                    }
-                }
-            }
-            println!("");
-        }
-    }
-}
-
-mod ebnf2gram;
-mod utils;
-
-pub use ebnf2gram::Ebnf2Gram;
-pub use utils::{create_closure_set, create_first_set, create_follow_set};
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{sorted_set, sorted_set_map};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GrammerIdentifier(pub u64);
+
+// Grammer::Grammer は非終端記号
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grammer {
+    Empty, // 空語
+    Dot,
+    Grammer(GrammerIdentifier),
+    Character(char),
+    // 字句解析器が返す字句の種類の番号. 文字とは別の終端記号
+    Token(usize),
+}
+
+impl Grammer {
+    pub fn terminal(&self) -> Option<Terminal> {
+        match self {
+            Grammer::Character(c) => Some(Terminal::Character(*c)),
+            Grammer::Token(kind) => Some(Terminal::Token(*kind)),
+            _ => None,
+        }
+    }
+}
+
+// 構文解析器の入力と先読みになる終端記号
+#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Terminal {
+    Character(char),
+    Token(usize),
+}
+
+impl From<char> for Terminal {
+    fn from(c: char) -> Self {
+        Terminal::Character(c)
+    }
+}
+
+impl From<Terminal> for Grammer {
+    fn from(terminal: Terminal) -> Self {
+        match terminal {
+            Terminal::Character(c) => Grammer::Character(c),
+            Terminal::Token(kind) => Grammer::Token(kind),
+        }
+    }
+}
+
+// 文字は 'x', 字句は #3 と書く. 表の出力で使う
+impl std::fmt::Display for Terminal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminal::Character(c) => write!(f, "'{}'", c),
+            Terminal::Token(kind) => write!(f, "#{}", kind),
+        }
+    }
+}
+
+// 先読みの表示が文字のときと変わらないよう, 文字は char と同じに書く
+impl std::fmt::Debug for Terminal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminal::Character(c) => write!(f, "{:?}", c),
+            Terminal::Token(kind) => write!(f, "#{}", kind),
+        }
+    }
+}
+
+pub type GrammerSet = HashMap<GrammerIdentifier, Vec<Vec<Grammer>>>;
+
+// 演算子の終端記号ごとの (優先順位, 結合性). 大きいほど強く結合する
+pub type PrecedenceTable = HashMap<Terminal, (usize, crate::parser::Associativity)>;
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrammerAnnotation {
+    #[serde(with = "sorted_set")]
+    pub endchars: HashSet<Terminal>,
+    #[serde(with = "sorted_set")]
+    pub identifiers: HashSet<u64>,
+    #[serde(with = "sorted_set")]
+    pub nullable: HashSet<GrammerIdentifier>,
+    #[serde(with = "sorted_set_map")]
+    pub first_set: HashMap<Grammer, HashSet<Grammer>>,
+    #[serde(with = "sorted_set_map")]
+    pub follow_set: HashMap<GrammerIdentifier, HashSet<Grammer>>,
+}
+
+impl GrammerAnnotation {
+    pub fn new(set: &GrammerSet) -> Self {
+        let mut endchars = HashSet::new();
+        for grammers in set.values() {
+            for grammer in grammers {
+                for item in grammer {
+                    if let Some(terminal) = item.terminal() {
+                        endchars.insert(terminal);
+                    }
+                }
+            }
+        }
+        let identifiers = set.keys().map(|id| id.0).collect();
+        Self::with_symbols(set, endchars, identifiers)
+    }
+
+    pub fn with_symbols(
+        set: &GrammerSet,
+        endchars: HashSet<Terminal>,
+        identifiers: HashSet<u64>,
+    ) -> Self {
+        let engine = FirstFollowEngine::new(set);
+        let mut first_set = HashMap::new();
+
+        for terminal in endchars.iter() {
+            let terminal = Grammer::from(*terminal);
+            first_set.insert(terminal, HashSet::from([terminal]));
+        }
+
+        for id in identifiers.iter() {
+            let id = GrammerIdentifier(*id);
+            first_set.insert(Grammer::Grammer(id), engine.first_set(id));
+        }
+
+        let nullable = engine.nullable_set();
+        let follow_set = engine.follow_set_map();
+
+        GrammerAnnotation {
+            endchars,
+            identifiers,
+            nullable,
+            first_set,
+            follow_set,
+        }
+    }
+
+    pub fn is_nullable(&self, grammer: &[Grammer]) -> bool {
+        is_nullable_sequence(&self.nullable, grammer)
+    }
+
+    pub fn sequence_first_set(&self, grammer: &[Grammer]) -> HashSet<Grammer> {
+        let mut first_set = HashSet::new();
+        for char in grammer {
+            if let Some(first) = self.first_set.get(char) {
+                first_set.extend(first.iter().copied());
+            }
+            if !is_nullable(&self.nullable, char) {
+                break;
+            }
+        }
+        first_set
+    }
+}
+
+pub fn display_grammer_set(set: &GrammerSet) {
+    for (id, grammers) in set {
+        for grammer in grammers {
+            print!("{} = ", id.0);
+            for item in grammer {
+                match item {
+                    Grammer::Empty => {
+                        print!("ε ");
+                    }
+                    Grammer::Dot => {
+                        print!("* ");
+                    }
+                    Grammer::Grammer(GrammerIdentifier(id)) => {
+                        print!("<{}> ", id);
+                    }
+                    Grammer::Character(c) => {
+                        print!("'{}' ", c);
+                    }
+                    Grammer::Token(kind) => {
+                        print!("#{} ", kind);
+                    }
+                }
+            }
+            println!();
+        }
+    }
+}
+
+mod bitset;
+mod cnf;
+mod ebnf2gram;
+#[cfg(test)]
+mod enumerate;
+mod epsilon;
+mod explain;
+mod gnf;
+mod left_factor;
+mod left_recursion;
+mod lookahead;
+mod simplify;
+mod tree;
+mod utils;
+
+pub use bitset::FirstFollowEngine;
+pub use cnf::{CnfGrammer, CnfOrigin};
+pub use ebnf2gram::Ebnf2Gram;
+#[cfg(test)]
+pub use enumerate::enumerate_sentences;
+pub use epsilon::eliminate_empty;
+pub use explain::display_trace;
+pub use gnf::GnfGrammer;
+pub use left_factor::left_factor;
+pub use left_recursion::{eliminate_left_recursion, find_left_recursion};
+pub use lookahead::{find_minimum_lookahead, Lookahead};
+pub use simplify::{simplify_grammer_set, SimplifyReport};
+pub use tree::{sentence, ParseTree};
+pub use utils::{
+    create_closure_set, create_end_follow_set, create_goto_set, create_initial_items,
+    create_nullable_set, create_sequence_first_set, is_nullable, is_nullable_sequence,
+};
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Grammer, GrammerIdentifier, GrammerSet};
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct SimplifyReport {
+    pub inlined: usize,
+    pub unit_removed: usize,
+    pub unreachable_removed: usize,
+}
+
+// `keep` にある非終端記号 (EBNF で名前の付いた規則) は展開しない.
+// `frozen` にある非終端記号は生成規則も書き換えない (`keep` に含めること)
+pub fn simplify_grammer_set(
+    set: &mut GrammerSet,
+    start: GrammerIdentifier,
+    keep: &HashSet<GrammerIdentifier>,
+    frozen: &HashSet<GrammerIdentifier>,
+) -> SimplifyReport {
+    let mut report = SimplifyReport::default();
+
+    loop {
+        let inlined = inline_single_productions(set, keep, frozen);
+        // 埋め込まれて参照されなくなった規則の単位規則は数えない
+        report.unreachable_removed += remove_unreachable(set, start);
+        let unit_removed = remove_unit_productions(set, keep, frozen);
+        report.unit_removed += unit_removed;
+        report.inlined += inlined;
+        if unit_removed == 0 && inlined == 0 {
+            break;
+        }
+    }
+
+    report
+}
+
+pub(crate) fn sorted_ids(set: &GrammerSet) -> Vec<GrammerIdentifier> {
+    let mut ids = set.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    ids
+}
+
+fn unit_target(grammer: &[Grammer]) -> Option<GrammerIdentifier> {
+    if let [Grammer::Grammer(id)] = grammer {
+        Some(*id)
+    } else {
+        None
+    }
+}
+
+// A -> B (B は生成された規則) を B の生成規則で置き換える
+fn remove_unit_productions(
+    set: &mut GrammerSet,
+    keep: &HashSet<GrammerIdentifier>,
+    frozen: &HashSet<GrammerIdentifier>,
+) -> usize {
+    let removable = |id: &GrammerIdentifier| !keep.contains(id);
+    let mut removed = 0;
+
+    for id in sorted_ids(set) {
+        if frozen.contains(&id) {
+            continue;
+        }
+        let grammers = &set[&id];
+        if !grammers
+            .iter()
+            .any(|g| unit_target(g).filter(removable).is_some())
+        {
+            continue;
+        }
+
+        // 単位規則で到達できる生成規則を全て集める
+        let mut visited = HashSet::from([id]);
+        let mut queue = vec![id];
+        let mut replaced: Vec<Vec<Grammer>> = Vec::new();
+        while let Some(current) = queue.pop() {
+            for grammer in set.get(&current).into_iter().flatten() {
+                match unit_target(grammer).filter(removable) {
+                    Some(target) => {
+                        if current == id {
+                            removed += 1;
+                        }
+                        if visited.insert(target) {
+                            queue.push(target);
+                        }
+                    }
+                    None => {
+                        if !replaced.contains(grammer) {
+                            replaced.push(grammer.clone());
+                        }
+                    }
+                }
+            }
+        }
+        set.insert(id, replaced);
+    }
+
+    removed
+}
+
+// 生成規則が一つしかない生成された規則を参照元に埋め込む
+fn inline_single_productions(
+    set: &mut GrammerSet,
+    keep: &HashSet<GrammerIdentifier>,
+    frozen: &HashSet<GrammerIdentifier>,
+) -> usize {
+    let targets = sorted_ids(set)
+        .into_iter()
+        .filter(|id| !keep.contains(id))
+        .filter_map(|id| match set[&id].as_slice() {
+            [single] if !single.contains(&Grammer::Grammer(id)) => Some((id, single.clone())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut inlined = HashSet::new();
+    for id in sorted_ids(set) {
+        if frozen.contains(&id) {
+            continue;
+        }
+        let grammers = set.get_mut(&id).unwrap();
+        for grammer in grammers.iter_mut() {
+            if !grammer
+                .iter()
+                .any(|g| matches!(g, Grammer::Grammer(r) if *r != id && targets.contains_key(r)))
+            {
+                continue;
+            }
+            let mut expanded = Vec::new();
+            for item in grammer.iter() {
+                match item {
+                    Grammer::Grammer(r) if *r != id && targets.contains_key(r) => {
+                        inlined.insert(*r);
+                        expanded.extend(targets[r].iter().copied());
+                    }
+                    _ => expanded.push(*item),
+                }
+            }
+            *grammer = normalize_empty(expanded);
+        }
+    }
+
+    inlined.len()
+}
+
+// 列の途中の空語を取り除く
+fn normalize_empty(grammer: Vec<Grammer>) -> Vec<Grammer> {
+    let grammer = grammer
+        .into_iter()
+        .filter(|g| *g != Grammer::Empty)
+        .collect::<Vec<_>>();
+    if grammer.is_empty() {
+        vec![Grammer::Empty]
+    } else {
+        grammer
+    }
+}
+
+pub(crate) fn remove_unreachable(set: &mut GrammerSet, start: GrammerIdentifier) -> usize {
+    let mut reachable = HashSet::from([start]);
+    let mut queue = vec![start];
+    while let Some(current) = queue.pop() {
+        for grammer in set.get(&current).into_iter().flatten() {
+            for item in grammer {
+                if let Grammer::Grammer(id) = item {
+                    if reachable.insert(*id) {
+                        queue.push(*id);
+                    }
+                }
+            }
+        }
+    }
+
+    let before = set.len();
+    set.retain(|id, _| reachable.contains(id));
+    before - set.len()
+}
+
+#[test]
+fn simplify_option_group_test() {
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str("a = [ 'x' ] , ( 'y' | 'z' ) ;").unwrap();
+    let report = gramconv.simplify();
+
+    assert_eq!(
+        report,
+        SimplifyReport {
+            inlined: 2,
+            unit_removed: 0,
+            unreachable_removed: 2,
+        }
+    );
+
+    let set = gramconv.get_grammer_set();
+    assert_eq!(set.len(), 3);
+    let start = &set[&GrammerIdentifier(0)];
+    assert_eq!(start.len(), 1);
+    let [Grammer::Grammer(option), Grammer::Grammer(group)] = start[0][..] else {
+        panic!("unexpected production {:?}", start[0]);
+    };
+    assert_eq!(
+        set[&option],
+        vec![vec![Grammer::Empty], vec![Grammer::Character('x')]]
+    );
+    assert_eq!(
+        set[&group],
+        vec![vec![Grammer::Character('y')], vec![Grammer::Character('z')]]
+    );
+}
+
+#[test]
+fn simplify_unit_production_test() {
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str("a = 'x' | b ; b = 'y' ;").unwrap();
+    let report = gramconv.simplify();
+
+    assert_eq!(report.unit_removed, 1);
+    assert_eq!(report.unreachable_removed, 1);
+    let a = gramconv.get_identifier_map()["a"];
+    let b = gramconv.get_identifier_map()["b"];
+    assert_eq!(
+        gramconv.get_grammer_set()[&a],
+        vec![vec![Grammer::Character('x')], vec![Grammer::Grammer(b)]]
+    );
+}
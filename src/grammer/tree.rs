@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use super::{GrammerIdentifier, Terminal};
+use crate::export::{nonterminal_name, terminal_name};
+
+// production は GrammerSet 内の生成規則の番号
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseTree {
+    Node {
+        id: GrammerIdentifier,
+        production: usize,
+        children: Vec<ParseTree>,
+    },
+    Leaf(Terminal),
+}
+
+// 文字はそのまま並べ, 字句は tokens の名前 (無ければ #3) を空白で区切って書く
+pub fn sentence(tokens: &[String], input: &[Terminal]) -> String {
+    let mut output = String::new();
+    for terminal in input {
+        match terminal {
+            Terminal::Character(c) => output.push(*c),
+            Terminal::Token(_) => {
+                if !output.is_empty() {
+                    output.push(' ');
+                }
+                output += &terminal_name(tokens, *terminal);
+            }
+        }
+    }
+    output
+}
+
+impl ParseTree {
+    pub fn leaves(&self) -> Vec<Terminal> {
+        match self {
+            ParseTree::Node { children, .. } => children.iter().flat_map(|c| c.leaves()).collect(),
+            ParseTree::Leaf(terminal) => vec![*terminal],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        sentence(&[], &self.leaves())
+    }
+
+    // 節を非終端記号の名前で括った形. 名前のない記号は <n>
+    pub fn display(&self, names: &HashMap<GrammerIdentifier, &str>, tokens: &[String]) -> String {
+        match self {
+            ParseTree::Node { id, children, .. } => {
+                let children = children
+                    .iter()
+                    .map(|c| c.display(names, tokens))
+                    .collect::<String>();
+                format!("{}({})", nonterminal_name(names, *id), children)
+            }
+            ParseTree::Leaf(Terminal::Character(c)) => c.to_string(),
+            ParseTree::Leaf(terminal) => terminal_name(tokens, *terminal),
+        }
+    }
+}
@@ -1,109 +1,251 @@
-use std::collections::{HashMap, HashSet};
-
-use super::{Grammer, GrammerIdentifier, GrammerSet};
-
-pub fn create_first_set(set: &GrammerSet, char: &Grammer) -> HashSet<Grammer> {
-    let mut first_set = HashSet::new();
-    match char {
-        Grammer::Empty => {
-            first_set.insert(Grammer::Empty);
-        }
-        Grammer::Grammer(id) => {
-            for grammer in set.get(id).unwrap() {
-                for char in grammer {
-                    let first = create_first_set(set, char);
-                    // :thinking_face:
-                    if first.contains(&Grammer::Empty) {
-                        first_set.extend(first.into_iter());
-                    } else {
-                        first_set.extend(first.into_iter());
-                        break;
-                    }
-                }
-            }
-        }
-        Grammer::Character(c) => {
-            first_set.insert(Grammer::Character(*c));
-        }
-        Grammer::Dot => {
-            panic!("Unexpected dot");
-        }
-    }
-    first_set
-}
-
-pub fn create_follow_set(set: &GrammerSet) -> HashMap<GrammerIdentifier, HashSet<Grammer>> {
-    let mut follow_set_map: HashMap<GrammerIdentifier, HashSet<Grammer>> = HashMap::new();
-
-    loop {
-        let mut found = false;
-        for (grammer_id, grammers) in set {
-            for grammer in grammers {
-                for i in 0..grammer.len() {
-                    if let Some(Grammer::Grammer(target_id)) = grammer.get(i) {
-                        let target_map = follow_set_map.entry(*target_id).or_default();
-                        let mut has_nonempty = false;
-                        if let Some(remain) = grammer.get(i + 1..grammer.len()) {
-                            for next in remain {
-                                match next {
-                                    Grammer::Character(c) => {
-                                        found |= !target_map.contains(&Grammer::Character(*c));
-
-                                        target_map.insert(Grammer::Character(*c));
-                                        has_nonempty = true;
-                                        break;
-                                    }
-                                    Grammer::Empty => {
-                                        panic!("Empty word not allowed between chars")
-                                    }
-                                    Grammer::Dot => panic!("Dot not allowed"),
-                                    Grammer::Grammer(next_id) => {
-                                        let first =
-                                            create_first_set(set, &Grammer::Grammer(*next_id));
-                                        found |= !target_map.is_superset(&first);
-                                        if first.contains(&Grammer::Empty) {
-                                            target_map.extend(first);
-                                        } else {
-                                            target_map.extend(first);
-                                            has_nonempty = true;
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        if !has_nonempty {
-                            let cloned = follow_set_map.entry(*grammer_id).or_default().clone();
-                            // TODO: Re-borrow
-                            let target_map = follow_set_map.entry(*target_id).or_default();
-                            found |= !target_map.is_superset(&cloned);
-                            target_map.extend(cloned);
-                        }
-                    }
-                }
-            }
-        }
-
-        if !found {
-            break;
-        }
-    }
-    follow_set_map
-}
-
-// 再帰かなあ
-pub fn create_closure_set(set: &GrammerSet, input: &GrammerSet) -> GrammerSet {
-    let mut output = input.clone();
-    for (id, grammers) in input {
-        for grammer in grammers {
-            for i in 0..grammer.len() - 1 {
-                if grammer[i] != Grammer::Dot {
-                    continue;
-                }
-                if let Grammer::Grammer(ref_id) = grammer[i + 1] {}
-            }
-        }
-    }
-    output
-}
+#[cfg(test)]
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use super::{Grammer, GrammerIdentifier, GrammerSet};
+
+// 空語は含めない (空語になりうるかは nullable で判定する)
+pub fn create_first_set(
+    set: &GrammerSet,
+    nullable: &HashSet<GrammerIdentifier>,
+    char: &Grammer,
+) -> HashSet<Grammer> {
+    let mut first_set = HashSet::new();
+    match char {
+        Grammer::Empty => {}
+        Grammer::Grammer(id) => {
+            // 左再帰でも止まるよう, 先頭に来うる非終端記号を辿る
+            let mut visited = HashSet::from([*id]);
+            let mut queue = vec![*id];
+            while let Some(current) = queue.pop() {
+                for grammer in set.get(&current).unwrap() {
+                    for item in grammer {
+                        match item {
+                            Grammer::Character(_) | Grammer::Token(_) => {
+                                first_set.insert(*item);
+                            }
+                            Grammer::Grammer(next) => {
+                                if visited.insert(*next) {
+                                    queue.push(*next);
+                                }
+                            }
+                            Grammer::Empty => {}
+                            Grammer::Dot => panic!("Unexpected dot"),
+                        }
+                        if !is_nullable(nullable, item) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Grammer::Character(_) | Grammer::Token(_) => {
+            first_set.insert(*char);
+        }
+        Grammer::Dot => {
+            panic!("Unexpected dot");
+        }
+    }
+    first_set
+}
+
+pub fn create_nullable_set(set: &GrammerSet) -> HashSet<GrammerIdentifier> {
+    let mut nullable = HashSet::new();
+    loop {
+        let mut found = false;
+        for (id, grammers) in set {
+            if nullable.contains(id) {
+                continue;
+            }
+            if grammers.iter().any(|grammer| {
+                grammer.iter().all(|g| match g {
+                    Grammer::Empty => true,
+                    Grammer::Grammer(r) => nullable.contains(r),
+                    _ => false,
+                })
+            }) {
+                nullable.insert(*id);
+                found = true;
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+    nullable
+}
+
+pub fn is_nullable(nullable: &HashSet<GrammerIdentifier>, char: &Grammer) -> bool {
+    match char {
+        Grammer::Empty => true,
+        Grammer::Grammer(id) => nullable.contains(id),
+        Grammer::Character(_) | Grammer::Token(_) => false,
+        Grammer::Dot => panic!("Unexpected dot"),
+    }
+}
+
+pub fn is_nullable_sequence(nullable: &HashSet<GrammerIdentifier>, grammer: &[Grammer]) -> bool {
+    grammer.iter().all(|g| is_nullable(nullable, g))
+}
+
+pub fn create_sequence_first_set(
+    set: &GrammerSet,
+    nullable: &HashSet<GrammerIdentifier>,
+    grammer: &[Grammer],
+) -> HashSet<Grammer> {
+    let mut first_set = HashSet::new();
+    for char in grammer {
+        first_set.extend(create_first_set(set, nullable, char));
+        if !is_nullable(nullable, char) {
+            break;
+        }
+    }
+    first_set
+}
+
+// FIRST/FOLLOW は FirstFollowEngine で求める. これは比較用の素朴な実装
+#[cfg(test)]
+pub fn create_follow_set(
+    set: &GrammerSet,
+    nullable: &HashSet<GrammerIdentifier>,
+) -> HashMap<GrammerIdentifier, HashSet<Grammer>> {
+    let mut follow_set_map: HashMap<GrammerIdentifier, HashSet<Grammer>> = HashMap::new();
+
+    loop {
+        let mut found = false;
+        for (grammer_id, grammers) in set {
+            for grammer in grammers {
+                for i in 0..grammer.len() {
+                    if let Some(Grammer::Grammer(target_id)) = grammer.get(i) {
+                        let target_map = follow_set_map.entry(*target_id).or_default();
+                        let mut has_nonempty = false;
+                        if let Some(remain) = grammer.get(i + 1..grammer.len()) {
+                            for next in remain {
+                                match next {
+                                    Grammer::Character(_) | Grammer::Token(_) => {
+                                        found |= !target_map.contains(next);
+
+                                        target_map.insert(*next);
+                                        has_nonempty = true;
+                                        break;
+                                    }
+                                    Grammer::Empty => {}
+                                    Grammer::Dot => panic!("Dot not allowed"),
+                                    Grammer::Grammer(next_id) => {
+                                        let first = create_first_set(set, nullable, next);
+                                        found |= !target_map.is_superset(&first);
+                                        target_map.extend(first);
+                                        if !nullable.contains(next_id) {
+                                            has_nonempty = true;
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        if !has_nonempty {
+                            let cloned = follow_set_map.entry(*grammer_id).or_default().clone();
+                            // TODO: Re-borrow
+                            let target_map = follow_set_map.entry(*target_id).or_default();
+                            found |= !target_map.is_superset(&cloned);
+                            target_map.extend(cloned);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !found {
+            break;
+        }
+    }
+    follow_set_map
+}
+
+// 入力の終端が後続しうる非終端記号
+pub fn create_end_follow_set(
+    set: &GrammerSet,
+    nullable: &HashSet<GrammerIdentifier>,
+    start: GrammerIdentifier,
+) -> HashSet<GrammerIdentifier> {
+    let mut end_follow = HashSet::from([start]);
+    let mut queue = vec![start];
+    while let Some(current) = queue.pop() {
+        for grammer in set.get(&current).into_iter().flatten() {
+            for item in grammer.iter().rev() {
+                if let Grammer::Grammer(id) = item {
+                    if end_follow.insert(*id) {
+                        queue.push(*id);
+                    }
+                }
+                if !is_nullable(nullable, item) {
+                    break;
+                }
+            }
+        }
+    }
+    end_follow
+}
+
+// 生成規則の先頭に Dot を置いた項
+pub fn create_initial_items(set: &GrammerSet, id: GrammerIdentifier) -> Vec<Vec<Grammer>> {
+    set[&id]
+        .iter()
+        .map(|grammer| {
+            let mut item = vec![Grammer::Dot];
+            item.extend(grammer.iter().filter(|g| **g != Grammer::Empty));
+            item
+        })
+        .collect()
+}
+
+// Dot の直後の非終端記号の項を加える
+pub fn create_closure_set(set: &GrammerSet, input: &GrammerSet) -> GrammerSet {
+    let mut output = input.clone();
+    let mut queue = input.keys().copied().collect::<Vec<_>>();
+    while let Some(id) = queue.pop() {
+        let mut next_ids = Vec::new();
+        for grammer in &output[&id] {
+            if let Some(pos) = grammer.iter().position(|g| *g == Grammer::Dot) {
+                if let Some(Grammer::Grammer(ref_id)) = grammer.get(pos + 1) {
+                    next_ids.push(*ref_id);
+                }
+            }
+        }
+        for ref_id in next_ids {
+            let items = output.entry(ref_id).or_default();
+            let mut added = false;
+            for item in create_initial_items(set, ref_id) {
+                if !items.contains(&item) {
+                    items.push(item);
+                    added = true;
+                }
+            }
+            if added {
+                queue.push(ref_id);
+            }
+        }
+    }
+    for items in output.values_mut() {
+        items.sort();
+    }
+    output
+}
+
+// Dot を `symbol` の後ろに進めた項の閉包
+pub fn create_goto_set(set: &GrammerSet, input: &GrammerSet, symbol: &Grammer) -> GrammerSet {
+    let mut moved = GrammerSet::new();
+    for (id, grammers) in input {
+        for grammer in grammers {
+            if let Some(pos) = grammer.iter().position(|g| *g == Grammer::Dot) {
+                if grammer.get(pos + 1) == Some(symbol) {
+                    let mut item = grammer.clone();
+                    item.swap(pos, pos + 1);
+                    moved.entry(*id).or_default().push(item);
+                }
+            }
+        }
+    }
+    create_closure_set(set, &moved)
+}
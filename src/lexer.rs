@@ -1,8 +1,11 @@
+// Token::TokenEnd は `;`
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Eq)]
 pub enum Token {
     Identifier(String),
     Space(usize),
     Equals,
+    IncrementalEquals,
     String(String),
     GroupBegin,
     GroupEnd,
@@ -15,6 +18,12 @@ pub enum Token {
     Separator,
     TokenEnd,
     LineEnd,
+    // `%left` などの宣言
+    Directive(String),
+    // `{{ rust code }}` の中身
+    Action(String),
+    // `@name`
+    ActionTag(String),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -43,22 +52,24 @@ impl<'a> Tokenizer<'a> {
         loop {
             if let Some(t) = self.read_string() {
                 tokens.push(t);
+            } else if let Some(t) = self.read_action(tokens.last()) {
+                tokens.push(t);
+            } else if let Some(t) = self.read_directive() {
+                tokens.push(t);
             } else if let Some(t) = self.read_identifier() {
                 tokens.push(t);
             } else if let Some(t) = self.read_equals() {
                 tokens.push(t);
             } else if let Some(t) = self.read_single_token() {
                 tokens.push(t);
-            } else if let Some(_) = self.read_space() {
-                // tokens.push(t);
-            } else if let Some(_) = self.read_lineend() {
+            } else if self.read_space().is_some() || self.read_lineend().is_some() {
                 // tokens.push(t);
             } else {
                 break;
             }
         }
 
-        if self.cursor.len() > 0 {
+        if !self.cursor.is_empty() {
             None
         } else {
             Some(tokens)
@@ -116,7 +127,10 @@ impl<'a> Tokenizer<'a> {
 
     fn read_equals(&mut self) -> Option<PositionedToken> {
         let pos = self.get_pos();
-        if let ['=', rest @ ..] = self.cursor {
+        if let ['=', '/', rest @ ..] = self.cursor {
+            self.cursor = rest;
+            Some(PositionedToken(Token::IncrementalEquals, pos))
+        } else if let ['=', rest @ ..] = self.cursor {
             self.cursor = rest;
             Some(PositionedToken(Token::Equals, pos))
         } else if let [':', ':', '=', rest @ ..] = self.cursor {
@@ -127,6 +141,79 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    // `{{` から `}}` までと `@name`.
+    // `{{` は因子の直後にあるときだけ動作とみなし, それ以外は繰り返しの入れ子になる
+    fn read_action(&mut self, last: Option<&PositionedToken>) -> Option<PositionedToken> {
+        let pos = self.get_pos();
+        match self.cursor {
+            ['{', '{', rest @ ..] => {
+                let after_factor = matches!(
+                    last,
+                    Some(PositionedToken(
+                        Token::String(_)
+                            | Token::Identifier(_)
+                            | Token::GroupEnd
+                            | Token::RepeatEnd
+                            | Token::OptionEnd,
+                        _
+                    ))
+                );
+                if !after_factor {
+                    return None;
+                }
+                // コード中の `{ }` の対応を取り, 深さ 0 の `}}` で閉じる
+                let mut depth = 0usize;
+                let mut end = None;
+                for i in 0..rest.len() {
+                    match rest[i..] {
+                        ['}', '}', ..] if depth == 0 => {
+                            end = Some(i);
+                            break;
+                        }
+                        ['{', ..] => depth += 1,
+                        ['}', ..] => depth = depth.checked_sub(1)?,
+                        _ => {}
+                    }
+                }
+                let end = end?;
+                let code = rest[..end].iter().collect::<String>().trim().to_string();
+                self.cursor = &rest[end + 2..];
+                Some(PositionedToken(Token::Action(code), pos))
+            }
+            ['@', rest @ ..] => {
+                let len = rest
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                if len == 0 {
+                    return None;
+                }
+                self.cursor = &rest[len..];
+                Some(PositionedToken(
+                    Token::ActionTag(rest[..len].iter().collect()),
+                    pos,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn read_directive(&mut self) -> Option<PositionedToken> {
+        let pos = self.get_pos();
+        let ['%', rest @ ..] = self.cursor else {
+            return None;
+        };
+        let len = rest.iter().take_while(|c| c.is_ascii_alphabetic()).count();
+        if len == 0 {
+            return None;
+        }
+        self.cursor = &rest[len..];
+        Some(PositionedToken(
+            Token::Directive(rest[..len].iter().collect()),
+            pos,
+        ))
+    }
+
     fn read_identifier(&mut self) -> Option<PositionedToken> {
         let pos = self.get_pos();
         let mut identifier = String::new();
@@ -135,12 +222,17 @@ impl<'a> Tokenizer<'a> {
             identifier.push(*c);
             cursor = rest;
         }
+        // 先頭の空白は識別子の位置に含めない
+        let leading = identifier.chars().count() - identifier.trim_start().chars().count();
         identifier = identifier.trim().to_string();
         if identifier.is_empty() {
             None
         } else {
             self.cursor = cursor;
-            Some(PositionedToken(Token::Identifier(identifier), pos))
+            Some(PositionedToken(
+                Token::Identifier(identifier),
+                pos + leading,
+            ))
         }
     }
 
@@ -203,6 +295,19 @@ fn read_equals_test() {
         Tokenizer::new(&[':', ':', '=', 'x'][..]).read_equals(),
         Some(PositionedToken(Token::Equals, 0))
     );
+    assert_eq!(
+        Tokenizer::new(&['=', '/', 'x'][..]).read_equals(),
+        Some(PositionedToken(Token::IncrementalEquals, 0))
+    );
+}
+
+#[test]
+fn read_directive_test() {
+    assert_eq!(Tokenizer::new(&['%', ' '][..]).read_directive(), None);
+    assert_eq!(
+        Tokenizer::new(&"%left \"+\"".chars().collect::<Vec<_>>()).read_directive(),
+        Some(PositionedToken(Token::Directive(String::from("left")), 0))
+    );
 }
 
 #[test]
@@ -238,3 +343,49 @@ fn read_string_test() {
     );
     assert_eq!(Tokenizer::new(&['"', 'x'][..]).read_string(), None);
 }
+
+#[test]
+fn read_action_test() {
+    let chars = |s: &str| s.chars().collect::<Vec<_>>();
+    let kinds = |s: &str| {
+        Tokenizer::new(&chars(s))
+            .read_definition()
+            .unwrap()
+            .into_iter()
+            .map(|PositionedToken(t, _)| t)
+            .collect::<Vec<_>>()
+    };
+
+    // 因子の前の `{{` は繰り返しの入れ子
+    assert_eq!(
+        kinds("a = {{ 'x' }} , 'y' ;"),
+        vec![
+            Token::Identifier(String::from("a")),
+            Token::Equals,
+            Token::RepeatBegin,
+            Token::RepeatBegin,
+            Token::String(String::from("x")),
+            Token::RepeatEnd,
+            Token::RepeatEnd,
+            Token::Separator,
+            Token::String(String::from("y")),
+            Token::TokenEnd,
+        ]
+    );
+    // 後ろの定義にある `}}` を飲み込まない
+    let tokens = kinds("a = {{ 'x' } , 'z' } ;\nb = 'y' {{ 1 }} ;");
+    assert_eq!(
+        tokens
+            .iter()
+            .filter(|t| matches!(t, Token::Action(_)))
+            .collect::<Vec<_>>(),
+        vec![&Token::Action(String::from("1"))]
+    );
+
+    // 因子の後なら動作. 中の `{ }` は対応を取る
+    assert_eq!(
+        kinds("e = ( 'x' ) {{ if a { 1 } else { 2 } }} ;")[5],
+        Token::Action(String::from("if a { 1 } else { 2 }"))
+    );
+    assert_eq!(Tokenizer::new(&chars("{{ 1 }}")).read_action(None), None);
+}
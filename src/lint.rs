@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{Definition, LexicalDeclaration, Rule, Span};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LintKind {
+    UndefinedReference(String),
+    Unreachable,
+    Duplicate { first: Span },
+    MissingBase,
+    NonProductive,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub kind: LintKind,
+    pub identifier: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            LintKind::UndefinedReference(_)
+            | LintKind::Duplicate { .. }
+            | LintKind::MissingBase => Severity::Error,
+            LintKind::Unreachable | LintKind::NonProductive => Severity::Warning,
+        }
+    }
+
+    // `3:5 error: ...` の形. source は位置を行と列にするための入力
+    pub fn display(&self, source: &[char]) -> String {
+        let level = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let message = match &self.kind {
+            LintKind::UndefinedReference(name) => {
+                format!("`{}` refers to undefined rule `{}`", self.identifier, name)
+            }
+            LintKind::Unreachable => {
+                format!("`{}` is not reachable from the start rule", self.identifier)
+            }
+            LintKind::Duplicate { first } => format!(
+                "`{}` is defined again (first defined at {})",
+                self.identifier,
+                first.location(source)
+            ),
+            LintKind::MissingBase => format!(
+                "`{}` is extended with `=/` before it is defined",
+                self.identifier
+            ),
+            LintKind::NonProductive => {
+                format!("`{}` does not derive any terminal string", self.identifier)
+            }
+        };
+        format!("{} {}: {}", self.span.location(source), level, message)
+    }
+}
+
+// 最初の定義を開始記号とみなす. `%token` と `%skip` の字句は入力から直接読むので, これも到達の起点にする
+pub fn lint(defs: &[Box<Definition>], declarations: &[LexicalDeclaration]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut first_spans: HashMap<&str, Span> = HashMap::new();
+    for def in defs {
+        let kind = match (first_spans.get(def.identifier.as_str()), def.incremental) {
+            (None, false) => {
+                first_spans.insert(&def.identifier, def.span);
+                continue;
+            }
+            // 参照の側でも未定義と報告しないよう, 最初の定義として扱う
+            (None, true) => {
+                first_spans.insert(&def.identifier, def.span);
+                LintKind::MissingBase
+            }
+            (Some(first), false) => LintKind::Duplicate { first: *first },
+            (Some(_), true) => continue,
+        };
+        diagnostics.push(Diagnostic {
+            kind,
+            identifier: def.identifier.clone(),
+            span: def.span,
+        });
+    }
+
+    for def in defs {
+        let mut reported = HashSet::new();
+        for (name, span) in &def.references {
+            if !first_spans.contains_key(name.as_str()) && reported.insert(name) {
+                diagnostics.push(Diagnostic {
+                    kind: LintKind::UndefinedReference(name.to_string()),
+                    identifier: def.identifier.clone(),
+                    span: *span,
+                });
+            }
+        }
+    }
+
+    if let Some(start) = defs.first() {
+        let mut reachable = declarations
+            .iter()
+            .flat_map(|d| d.names.iter().map(String::as_str))
+            .chain([start.identifier.as_str()])
+            .collect::<HashSet<_>>();
+        let mut queue = reachable.iter().copied().collect::<Vec<_>>();
+        while let Some(current) = queue.pop() {
+            for def in defs.iter().filter(|d| d.identifier == current) {
+                let mut refs = Vec::new();
+                collect_references(&def.rule, &mut refs);
+                for name in refs {
+                    if reachable.insert(name) {
+                        queue.push(name);
+                    }
+                }
+            }
+        }
+        for def in defs {
+            if !reachable.contains(def.identifier.as_str())
+                && first_spans.get(def.identifier.as_str()) == Some(&def.span)
+            {
+                diagnostics.push(Diagnostic {
+                    kind: LintKind::Unreachable,
+                    identifier: def.identifier.clone(),
+                    span: def.span,
+                });
+            }
+        }
+    }
+
+    let mut productive: HashSet<&str> = HashSet::new();
+    loop {
+        let mut found = false;
+        for def in defs {
+            if !productive.contains(def.identifier.as_str())
+                && is_productive(&def.rule, &productive)
+            {
+                productive.insert(&def.identifier);
+                found = true;
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+    for def in defs {
+        if !productive.contains(def.identifier.as_str())
+            && first_spans.get(def.identifier.as_str()) == Some(&def.span)
+        {
+            diagnostics.push(Diagnostic {
+                kind: LintKind::NonProductive,
+                identifier: def.identifier.clone(),
+                span: def.span,
+            });
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.span.start);
+    diagnostics
+}
+
+pub fn collect_references<'a>(rule: &'a Rule, refs: &mut Vec<&'a str>) {
+    match rule {
+        Rule::Character(_) | Rule::Token(_) => {}
+        Rule::IdentifierRef(name) => refs.push(name),
+        Rule::Exclude { from, target } => {
+            collect_references(from, refs);
+            collect_references(target, refs);
+        }
+        Rule::Sequence(rules) | Rule::Or(rules) => {
+            for rule in rules {
+                collect_references(rule, refs);
+            }
+        }
+        Rule::Repeat(rule) | Rule::Option(rule) | Rule::Group(rule) | Rule::Action { rule, .. } => {
+            collect_references(rule, refs)
+        }
+    }
+}
+
+fn is_productive(rule: &Rule, productive: &HashSet<&str>) -> bool {
+    match rule {
+        Rule::Character(_) | Rule::Token(_) => true,
+        Rule::IdentifierRef(name) => productive.contains(name.as_str()),
+        Rule::Exclude { from, target: _ } => is_productive(from, productive),
+        Rule::Sequence(rules) => rules.iter().all(|r| is_productive(r, productive)),
+        Rule::Or(rules) => rules.iter().any(|r| is_productive(r, productive)),
+        Rule::Repeat(_) | Rule::Option(_) => true,
+        Rule::Group(rule) | Rule::Action { rule, .. } => is_productive(rule, productive),
+    }
+}
+
+#[test]
+fn lint_test() {
+    use crate::testing::parse_str;
+
+    let rules = parse_str("a = b , c ; b = 'x' ; b = 'y' ; d = 'z' ; e = e , 'w' ;")
+        .unwrap()
+        .definitions;
+    let diagnostics = lint(&rules, &[]);
+
+    let kinds = diagnostics
+        .iter()
+        .map(|d| (d.identifier.as_str(), d.kind.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            ("a", LintKind::NonProductive),
+            ("a", LintKind::UndefinedReference("c".to_string())),
+            (
+                "b",
+                LintKind::Duplicate {
+                    first: Span { start: 12, end: 21 }
+                }
+            ),
+            ("d", LintKind::Unreachable),
+            ("e", LintKind::Unreachable),
+            ("e", LintKind::NonProductive),
+        ]
+    );
+    // 未定義の参照は定義全体ではなく参照の位置
+    assert_eq!(diagnostics[1].span, Span { start: 8, end: 9 });
+    assert_eq!(diagnostics[2].span, Span { start: 22, end: 31 });
+
+    // 位置は行と列で出す. 列は文字単位で数える
+    let input = "a = b ;\n  x = 'y' ;\nb = 'é' , c ;";
+    let rules = parse_str(input).unwrap().definitions;
+    let input = input.chars().collect::<Vec<_>>();
+    let messages = lint(&rules, &[])
+        .iter()
+        .map(|d| d.display(&input))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            "1:1 warning: `a` does not derive any terminal string",
+            "2:3 warning: `x` is not reachable from the start rule",
+            "3:1 warning: `b` does not derive any terminal string",
+            "3:11 error: `b` refers to undefined rule `c`",
+        ]
+    );
+
+    let rules = parse_str("a = 'x' , [ b ] ; a =/ 'y' ; b =/ a ;")
+        .unwrap()
+        .definitions;
+    let kinds = lint(&rules, &[])
+        .into_iter()
+        .map(|d| d.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, vec![LintKind::MissingBase]);
+
+    // 読み飛ばす字句とそこから参照される規則は到達可能
+    let source = parse_str(
+        "%token name ; %skip blank ;
+        a = name , { name } ; name = 'x' ; blank = space , { space } ; space = ' ' ; u = 'u' ;",
+    )
+    .unwrap();
+    let unreachable = lint(&source.definitions, &source.lexical_declarations)
+        .into_iter()
+        .map(|d| d.identifier)
+        .collect::<Vec<_>>();
+    assert_eq!(unreachable, ["u"]);
+}
@@ -0,0 +1,409 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+
+use super::LL1Table;
+use crate::grammer::{Ebnf2Gram, Grammer, GrammerAnnotation, GrammerIdentifier};
+use crate::parser::{Definition, Rule};
+
+const HELPERS: &str = r#"#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub expected: Vec<char>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.expected.is_empty() {
+            return write!(f, "{}: expected end of input", self.position);
+        }
+        let expected = self
+            .expected
+            .iter()
+            .map(|c| format!("{:?}", c))
+            .collect::<Vec<_>>();
+        write!(f, "{}: expected one of {}", self.position, expected.join(", "))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub struct Parser {
+    input: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Self {
+        Parser {
+            input: input.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn error(&self, expected: &[char]) -> ParseError {
+        ParseError {
+            position: self.pos,
+            expected: expected.to_vec(),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&[c]));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+"#;
+
+// 規則の名前を Rust の関数名にする
+fn function_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("parse_{}", name)
+}
+
+// 連続する三文字以上は範囲にまとめる
+fn char_pattern(chars: &BTreeSet<char>) -> String {
+    let mut runs: Vec<(char, char)> = Vec::new();
+    for c in chars {
+        match runs.last_mut() {
+            Some((_, end)) if (*end as u32) + 1 == *c as u32 => *end = *c,
+            _ => runs.push((*c, *c)),
+        }
+    }
+    let mut patterns = Vec::new();
+    for (start, end) in runs {
+        match end as u32 - start as u32 {
+            0 => patterns.push(format!("{:?}", start)),
+            1 => patterns.extend([format!("{:?}", start), format!("{:?}", end)]),
+            _ => patterns.push(format!("{:?}..={:?}", start, end)),
+        }
+    }
+    patterns.join(" | ")
+}
+
+struct Generator<'a> {
+    annotation: GrammerAnnotation,
+    identifier_map: &'a HashMap<String, GrammerIdentifier>,
+    output: String,
+}
+
+impl Generator<'_> {
+    // 部分規則の FIRST 集合 (文字) と空になりうるか. 非終端記号は GrammerAnnotation から引く
+    fn first(&self, rule: &Rule) -> Result<(BTreeSet<char>, bool)> {
+        match rule {
+            Rule::Character(c) => Ok((BTreeSet::from([*c]), false)),
+            Rule::IdentifierRef(name) => {
+                let id = self
+                    .identifier_map
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown identifier {}", name))?;
+                let first = self.annotation.first_set[&Grammer::Grammer(*id)]
+                    .iter()
+                    .filter_map(|g| match g {
+                        Grammer::Character(c) => Some(*c),
+                        _ => None,
+                    })
+                    .collect();
+                Ok((first, self.annotation.nullable.contains(id)))
+            }
+            Rule::Exclude { .. } => Err(anyhow!("Exclusion is not supported")),
+            // 生成する関数は文字列を読むので字句は扱わない
+            Rule::Token(_) => Err(anyhow!("Tokens are not supported")),
+            Rule::Sequence(rules) => {
+                let mut first = BTreeSet::new();
+                for rule in rules {
+                    let (inner, nullable) = self.first(rule)?;
+                    first.extend(inner);
+                    if !nullable {
+                        return Ok((first, false));
+                    }
+                }
+                Ok((first, true))
+            }
+            Rule::Or(rules) => {
+                let mut first = BTreeSet::new();
+                let mut any_nullable = false;
+                for rule in rules {
+                    let (inner, nullable) = self.first(rule)?;
+                    first.extend(inner);
+                    any_nullable |= nullable;
+                }
+                Ok((first, any_nullable))
+            }
+            Rule::Repeat(rule) | Rule::Option(rule) => Ok((self.first(rule)?.0, true)),
+            Rule::Group(rule) => self.first(rule),
+            Rule::Action { .. } => self.first(rule.strip_action()),
+        }
+    }
+
+    // 部分規則を選ぶ先読みの match のパターン. 選べる文字が無ければ `Some()` になるのでエラー
+    fn pattern(&self, rule: &Rule) -> Result<String> {
+        let (first, _) = self.first(rule)?;
+        if first.is_empty() {
+            return Err(anyhow!("No lookahead character selects `{}`", rule));
+        }
+        Ok(char_pattern(&first))
+    }
+
+    fn line(&mut self, depth: usize, code: &str) {
+        self.output += &"    ".repeat(depth);
+        self.output += code;
+        self.output += "\n";
+    }
+
+    fn emit(&mut self, rule: &Rule, depth: usize) -> Result<()> {
+        match rule {
+            Rule::Character(c) => self.line(depth, &format!("self.expect({:?})?;", c)),
+            Rule::IdentifierRef(name) => {
+                self.line(depth, &format!("self.{}()?;", function_name(name)))
+            }
+            Rule::Exclude { .. } => return Err(anyhow!("Exclusion is not supported")),
+            Rule::Token(_) => return Err(anyhow!("Tokens are not supported")),
+            Rule::Sequence(rules) => {
+                for rule in rules {
+                    self.emit(rule, depth)?;
+                }
+            }
+            Rule::Group(rule) => self.emit(rule, depth)?,
+            // 再帰下降の関数は値を返さないので動作を呼べない
+            Rule::Action { .. } => {
+                return Err(anyhow!(
+                    "Semantic actions are not supported by the recursive descent generator"
+                ))
+            }
+            Rule::Repeat(rule) => {
+                let pattern = self.pattern(rule)?;
+                self.line(
+                    depth,
+                    &format!("while let Some({}) = self.peek() {{", pattern),
+                );
+                self.emit(rule, depth + 1)?;
+                self.line(depth, "}");
+            }
+            Rule::Option(rule) => {
+                let pattern = self.pattern(rule)?;
+                self.line(depth, &format!("if let Some({}) = self.peek() {{", pattern));
+                self.emit(rule, depth + 1)?;
+                self.line(depth, "}");
+            }
+            Rule::Or(rules) => {
+                // 空になりうる選択肢は他の選択肢に当てはまらないときに選ぶ
+                let mut fallback = None;
+                let mut expected = BTreeSet::new();
+                self.line(depth, "match self.peek() {");
+                for rule in rules {
+                    let (first, nullable) = self.first(rule)?;
+                    expected.extend(first.iter().copied());
+                    if nullable {
+                        fallback = Some(rule);
+                        continue;
+                    }
+                    let pattern = self.pattern(rule)?;
+                    self.line(depth + 1, &format!("Some({}) => {{", pattern));
+                    self.emit(rule, depth + 2)?;
+                    self.line(depth + 1, "}");
+                }
+                match fallback {
+                    Some(rule) => {
+                        self.line(depth + 1, "_ => {");
+                        self.emit(rule, depth + 2)?;
+                        self.line(depth + 1, "}");
+                    }
+                    None => {
+                        let expected = expected
+                            .iter()
+                            .map(|c| format!("{:?}", c))
+                            .collect::<Vec<_>>();
+                        self.line(
+                            depth + 1,
+                            &format!("_ => return Err(self.error(&[{}])),", expected.join(", ")),
+                        );
+                    }
+                }
+                self.line(depth, "}");
+            }
+        }
+        Ok(())
+    }
+}
+
+// LL(1) の文法から EBNF の規則ごとに一つの関数を持つ再帰下降構文解析器を書き出す.
+// 文法が LL(1) でなければエラー
+pub fn generate_recursive_descent(definitions: &[Box<Definition>]) -> Result<String> {
+    let gramconv = Ebnf2Gram::process(definitions.to_vec(), &[])?;
+    let start = GrammerIdentifier(0);
+    let table = LL1Table::build(gramconv.get_grammer_set(), start)?;
+    if let Some((id, lookahead, productions)) = table.conflicts().first() {
+        return Err(anyhow!(
+            "Not LL(1): <{}> with lookahead {:?} has productions {:?}",
+            id.0,
+            lookahead,
+            productions
+        ));
+    }
+
+    // `=/` の定義は選択肢として併合する
+    let mut rules: Vec<(&str, Vec<Box<Rule>>)> = Vec::new();
+    for definition in definitions {
+        match rules
+            .iter_mut()
+            .find(|(name, _)| *name == definition.identifier)
+        {
+            Some((_, alternatives)) => alternatives.push(definition.rule.clone()),
+            None => rules.push((&definition.identifier, vec![definition.rule.clone()])),
+        }
+    }
+
+    // 大文字小文字や記号の違いしかない規則は同じ関数名になる
+    let mut functions: HashMap<String, &str> = HashMap::new();
+    for (name, _) in &rules {
+        if let Some(other) = functions.insert(function_name(name), name) {
+            return Err(anyhow!(
+                "`{}` and `{}` both generate the function {}",
+                other,
+                name,
+                function_name(name)
+            ));
+        }
+    }
+
+    let mut generator = Generator {
+        annotation: gramconv.create_annotations(),
+        identifier_map: gramconv.get_identifier_map(),
+        output: String::from(
+            "// Generated by rust-ebnf-cc. Do not edit.\n#![allow(dead_code)]\n\n",
+        ),
+    };
+    generator.output += HELPERS;
+    for (name, mut alternatives) in rules {
+        let rule = match alternatives.len() {
+            1 => *alternatives.pop().unwrap(),
+            _ => Rule::Or(alternatives),
+        };
+        generator.line(0, "");
+        generator.line(1, &format!("// {} = {} ;", name, rule));
+        generator.line(
+            1,
+            &format!(
+                "pub fn {}(&mut self) -> Result<(), ParseError> {{",
+                function_name(name)
+            ),
+        );
+        generator.emit(&rule, 2)?;
+        generator.line(2, "Ok(())");
+        generator.line(1, "}");
+    }
+    generator.line(0, "}");
+
+    let start_name = &definitions
+        .first()
+        .ok_or_else(|| anyhow!("No rule"))?
+        .identifier;
+    generator.output += &format!(
+        "
+pub fn parse(input: &str) -> Result<(), ParseError> {{
+    let mut parser = Parser::new(input);
+    parser.{}()?;
+    if parser.pos != parser.input.len() {{
+        return Err(parser.error(&[]));
+    }}
+    Ok(())
+}}
+",
+        function_name(start_name)
+    );
+    Ok(generator.output)
+}
+
+#[test]
+fn recursive_descent_test() {
+    use crate::testing::parse_str;
+
+    let definitions = parse_str(
+        "list = '[' , [ value , { ',' , value } ] , ']' ;
+        value = list | number | ( '-' , number ) ;
+        number = digit , { digit } ;
+        digit = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' ;",
+    )
+    .unwrap()
+    .definitions;
+    let code = generate_recursive_descent(&definitions).unwrap();
+    assert!(code.contains("pub fn parse_value(&mut self)"));
+    assert!(code.contains("while let Some(',') = self.peek() {"));
+    assert!(code.contains("Some('0'..='9') => {"));
+
+    let inputs = ["[]", "[1,-20,[3,[]]]", "[1,]", "[-]", "[12]3"];
+    let main = r#"mod parser;
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        match parser::parse(&input) {
+            Ok(()) => println!("ok"),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+"#;
+    let actual = crate::testing::run_generated_crate(
+        "rd",
+        &[("parser.rs", &code), ("main.rs", main)],
+        &inputs,
+    );
+    assert_eq!(
+        actual.lines().collect::<Vec<_>>(),
+        [
+            "ok",
+            "ok",
+            "3: expected one of '-', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '['",
+            "2: expected one of '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'",
+            "4: expected end of input",
+        ]
+    );
+
+    let definitions = parse_str("e = ( e , '+' , 'x' ) | 'x' ;")
+        .unwrap()
+        .definitions;
+    assert!(generate_recursive_descent(&definitions).is_err());
+
+    let definitions = parse_str("Expr = 'x' ; expr = Expr ;").unwrap().definitions;
+    assert!(generate_recursive_descent(&definitions)
+        .unwrap_err()
+        .to_string()
+        .contains("both generate the function parse_expr"));
+
+    let definitions = parse_str("list = '[' , { 'x' } , ']' @list ;")
+        .unwrap()
+        .definitions;
+    assert!(generate_recursive_descent(&definitions)
+        .unwrap_err()
+        .to_string()
+        .contains("Semantic actions"));
+
+    // 空の列は先読みで選べない
+    let gramconv = Ebnf2Gram::process(definitions, &[]).unwrap();
+    let generator = Generator {
+        annotation: gramconv.create_annotations(),
+        identifier_map: gramconv.get_identifier_map(),
+        output: String::new(),
+    };
+    assert!(generator.pattern(&Rule::Sequence(Vec::new())).is_err());
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use crate::export::{
+    lookahead_name, nonterminal_name, nonterminal_names, production_label, TableView,
+};
+use crate::grammer::{
+    create_end_follow_set, find_left_recursion, Grammer, GrammerAnnotation, GrammerIdentifier,
+    GrammerSet, ParseTree, Terminal,
+};
+
+mod codegen;
+pub use codegen::generate_recursive_descent;
+
+// 先読み None は入力の終端
+#[derive(Debug)]
+pub struct LL1Table {
+    pub start: GrammerIdentifier,
+    pub table: HashMap<(GrammerIdentifier, Option<Terminal>), Vec<usize>>,
+}
+
+impl LL1Table {
+    pub fn build(set: &GrammerSet, start: GrammerIdentifier) -> Result<Self> {
+        if let Some(id) = find_left_recursion(set) {
+            return Err(anyhow!(
+                "<{}> is left recursive; eliminate left recursion first",
+                id.0
+            ));
+        }
+
+        let annotation = GrammerAnnotation::new(set);
+        let end_follow = create_end_follow_set(set, &annotation.nullable, start);
+
+        let mut table: HashMap<_, Vec<usize>> = HashMap::new();
+        for (id, grammers) in set {
+            for (i, grammer) in grammers.iter().enumerate() {
+                let mut lookaheads = annotation
+                    .sequence_first_set(grammer)
+                    .iter()
+                    .filter_map(|g| g.terminal().map(Some))
+                    .collect::<HashSet<_>>();
+                if annotation.is_nullable(grammer) {
+                    for g in annotation.follow_set.get(id).into_iter().flatten() {
+                        if let Some(terminal) = g.terminal() {
+                            lookaheads.insert(Some(terminal));
+                        }
+                    }
+                    if end_follow.contains(id) {
+                        lookaheads.insert(None);
+                    }
+                }
+                for lookahead in lookaheads {
+                    table.entry((*id, lookahead)).or_default().push(i);
+                }
+            }
+        }
+        for productions in table.values_mut() {
+            productions.sort();
+        }
+
+        Ok(LL1Table { start, table })
+    }
+
+    pub fn conflicts(&self) -> Vec<(GrammerIdentifier, Option<Terminal>, &[usize])> {
+        let mut conflicts = self
+            .table
+            .iter()
+            .filter(|(_, productions)| productions.len() > 1)
+            .map(|((id, lookahead), productions)| (*id, *lookahead, productions.as_slice()))
+            .collect::<Vec<_>>();
+        conflicts.sort_by_key(|(id, lookahead, _)| (id.0, *lookahead));
+        conflicts
+    }
+
+    // 行は非終端記号, 列は終端記号と入力の終端 $. 欄には生成規則を書く
+    pub fn to_view(
+        &self,
+        set: &GrammerSet,
+        identifier_map: &HashMap<String, GrammerIdentifier>,
+        tokens: &[String],
+    ) -> TableView {
+        let names = nonterminal_names(identifier_map);
+        let mut terminals = self
+            .table
+            .keys()
+            .map(|(_, lookahead)| *lookahead)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        // 入力の終端 $ は最後の列
+        terminals.sort_by_key(|lookahead| (lookahead.is_none(), *lookahead));
+        let mut ids = set.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+
+        let rows = ids
+            .into_iter()
+            .map(|id| {
+                let cells = terminals
+                    .iter()
+                    .map(|lookahead| {
+                        self.table
+                            .get(&(id, *lookahead))
+                            .into_iter()
+                            .flatten()
+                            .map(|production| {
+                                production_label(set, &names, tokens, id, *production)
+                            })
+                            .collect()
+                    })
+                    .collect();
+                (nonterminal_name(&names, id), cells)
+            })
+            .collect();
+        TableView {
+            corner: String::new(),
+            columns: terminals
+                .into_iter()
+                .map(|lookahead| lookahead_name(tokens, lookahead))
+                .collect(),
+            rows,
+        }
+    }
+
+    // 文字の列も字句の種類の列も受け取る
+    pub fn parse<T: Copy + Into<Terminal>>(
+        &self,
+        set: &GrammerSet,
+        input: &[T],
+    ) -> Result<ParseTree> {
+        let input = input.iter().map(|t| (*t).into()).collect::<Vec<Terminal>>();
+        let mut pos = 0;
+        let tree = self.parse_nonterminal(set, self.start, &input, &mut pos)?;
+        if pos != input.len() {
+            return Err(anyhow!("{} Unexpected {}", pos, input[pos]));
+        }
+        Ok(tree)
+    }
+
+    fn parse_nonterminal(
+        &self,
+        set: &GrammerSet,
+        id: GrammerIdentifier,
+        input: &[Terminal],
+        pos: &mut usize,
+    ) -> Result<ParseTree> {
+        let lookahead = input.get(*pos).copied();
+        let production = match self.table.get(&(id, lookahead)).map(|p| p.as_slice()) {
+            Some([production]) => *production,
+            Some(_) => {
+                return Err(anyhow!(
+                    "{} Conflict on <{}> with lookahead {:?}",
+                    pos,
+                    id.0,
+                    lookahead
+                ))
+            }
+            None => {
+                return Err(anyhow!(
+                    "{} Unexpected {:?} while parsing <{}>",
+                    pos,
+                    lookahead,
+                    id.0
+                ))
+            }
+        };
+
+        let mut children = Vec::new();
+        for item in &set[&id][production] {
+            match item {
+                Grammer::Character(_) | Grammer::Token(_) => {
+                    let terminal = item.terminal().unwrap();
+                    if input.get(*pos) != Some(&terminal) {
+                        return Err(anyhow!("{} Expected {}", pos, terminal));
+                    }
+                    *pos += 1;
+                    children.push(ParseTree::Leaf(terminal));
+                }
+                Grammer::Grammer(next) => {
+                    children.push(self.parse_nonterminal(set, *next, input, pos)?);
+                }
+                Grammer::Empty => {}
+                Grammer::Dot => return Err(anyhow!("Dot not allowed")),
+            }
+        }
+
+        Ok(ParseTree::Node {
+            id,
+            production,
+            children,
+        })
+    }
+}
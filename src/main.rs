@@ -5,25 +5,510 @@ use lexer::Tokenizer;
 mod parser;
 use parser::Parser;
 
+mod ast;
+mod cache;
+mod export;
 mod grammer;
+mod lint;
 mod ll;
+mod scanner;
 mod slr;
+#[cfg(test)]
+mod testing;
 fn main() {
     let input = std::env::args().nth(1).expect("no input file");
     let mut file = File::open(input).expect("input file open");
     let mut buf = String::new();
     file.read_to_string(&mut buf).unwrap();
 
+    // 内容のハッシュが一致すれば文法を処理せずにキャッシュを読む
+    if std::env::args().nth(2).as_deref() == Some("--cache") {
+        let path = std::env::args().nth(3).expect("no cache file");
+        match cache::CompiledGrammer::load_or_compile(&buf, std::path::Path::new(&path)) {
+            Ok((compiled, hit)) => {
+                print!(
+                    "{} {}: {} states",
+                    if hit { "loaded" } else { "rebuilt" },
+                    path,
+                    compiled.table.automaton.states.len()
+                );
+                match &compiled.lexicon {
+                    Some(lexicon) => println!(", {} token kinds", lexicon.get_kinds().len()),
+                    None => println!(),
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // let input_vec: Vec<char> = &buf.as_str().chars().collect::<Vec<_>>();
     let input_buf: &[char] = &buf.as_str().chars().collect::<Vec<_>>();
     let tokenizer = Tokenizer::new(input_buf);
     if let Some(tokens) = tokenizer.read_definition() {
         // dbg!(&tokens);
         let mut parser = Parser::new(&tokens);
-        let rules = parser.eat();
+        let rules = parser.eat().unwrap();
+
+        let diagnostics = lint::lint(&rules, parser.get_lexical_declarations());
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic.display(input_buf));
+        }
+        if diagnostics
+            .iter()
+            .any(|d| d.severity() == lint::Severity::Error)
+        {
+            std::process::exit(1);
+        }
+
+        let definitions = rules.clone();
+        let mode = std::env::args().nth(2);
+        // 字句規則があれば先に分け, 字句の種類を終端記号にした構文規則だけを変換する.
+        // 字句解析器を作るモードでは宣言が無くても文字列を字句にする
+        let (lexicon, syntactic, precedences) = if !parser.get_lexical_declarations().is_empty()
+            || matches!(mode.as_deref(), Some("--tokens" | "--lex" | "--scanner"))
+        {
+            let (lexicon, syntactic) =
+                match scanner::separate(&definitions, parser.get_lexical_declarations()) {
+                    Ok(separated) => separated,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+            let precedences = lexicon.rewrite_precedences(parser.get_precedences());
+            (Some(lexicon), syntactic, precedences)
+        } else {
+            (None, rules, parser.get_precedences().to_vec())
+        };
+        let mut gramconv = match grammer::Ebnf2Gram::process(syntactic.clone(), &precedences) {
+            Ok(gramconv) => gramconv,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let report = gramconv.simplify();
+        eprintln!(
+            "simplified: {} inlined, {} unit productions, {} unreachable rules",
+            report.inlined, report.unit_removed, report.unreachable_removed
+        );
+        let start = grammer::GrammerIdentifier(0);
+        let precedence = gramconv.get_precedence_table();
+        let names = export::nonterminal_names(gramconv.get_identifier_map());
+        // 報告では字句の種類を番号でなく名前で書く
+        let tokens = lexicon
+            .as_ref()
+            .map_or_else(Vec::new, scanner::Lexicon::token_names);
+        match mode.as_deref() {
+            Some("--cnf") => {
+                let cnf = grammer::CnfGrammer::convert(gramconv.get_grammer_set(), start);
+                grammer::display_grammer_set(&cnf.set);
+                let mut origins = cnf.origins.iter().collect::<Vec<_>>();
+                origins.sort_by_key(|(id, _)| id.0);
+                for (id, origin) in origins {
+                    match origin {
+                        grammer::CnfOrigin::Rule(rule) => {
+                            println!(
+                                "<{}> from {}",
+                                id.0,
+                                export::nonterminal_name(&names, *rule)
+                            )
+                        }
+                        grammer::CnfOrigin::Terminal(c) => {
+                            println!("<{}> from {}", id.0, export::terminal_name(&tokens, *c))
+                        }
+                    }
+                }
+            }
+            Some("--cyk") => {
+                let input = read_source(lexicon.as_ref());
+                let cnf = grammer::CnfGrammer::convert(gramconv.get_grammer_set(), start);
+                if cnf.cyk(&input) {
+                    println!("accepted");
+                } else {
+                    println!("rejected");
+                }
+            }
+            // 変換の前後で言語が同じかはテストでだけ比べる. ここでは結果を確かめずに出力する
+            Some("--gnf") => {
+                match grammer::GnfGrammer::convert(gramconv.get_grammer_set(), start) {
+                    Ok(gnf) => grammer::display_grammer_set(&gnf.set),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some("--llk") => {
+                let set = gramconv.get_grammer_set();
+                let minimum = grammer::find_minimum_lookahead(set, start, 8);
+                let mut ids = minimum.keys().copied().collect::<Vec<_>>();
+                ids.sort();
+                for id in ids {
+                    let name = export::nonterminal_name(&names, id);
+                    match minimum[&id] {
+                        grammer::Lookahead::Within(k) => println!("{}: LL({})", name, k),
+                        grammer::Lookahead::NotWithin(k) => {
+                            println!("{}: not LL(k) for k <= {}", name, k)
+                        }
+                    }
+                }
+            }
+            Some("--left-factor") => {
+                let (set, report) = match grammer::left_factor(gramconv.get_grammer_set(), 8) {
+                    Ok(factored) => factored,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                grammer::display_grammer_set(&set);
+                let mut factored = report.factored.iter().collect::<Vec<_>>();
+                factored.sort_by_key(|(id, _)| id.0);
+                for (id, owner) in factored {
+                    println!(
+                        "<{}> factored from {}",
+                        id.0,
+                        export::nonterminal_name(&names, *owner)
+                    );
+                }
+                println!("{} substitutions", report.substituted);
+                for id in report.remaining_conflicts {
+                    println!("conflict: {}", export::nonterminal_name(&names, id));
+                }
+            }
+            // 左再帰を除いた文法の LL(1) 表で構文解析し, 元の文法の構文木に戻す
+            Some("--ll-parse") => {
+                let input = read_source(lexicon.as_ref());
+                let (set, map) = match grammer::eliminate_left_recursion(gramconv.get_grammer_set())
+                {
+                    Ok(eliminated) => eliminated,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let table = match ll::LL1Table::build(&set, start) {
+                    Ok(table) => table,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                match table
+                    .parse(&set, &input)
+                    .and_then(|tree| map.restore(&tree))
+                {
+                    Ok(tree) => println!("{}", tree.display(&names, &tokens)),
+                    Err(e) => println!("rejected: {}", e),
+                }
+            }
+            Some("--ll") => {
+                let set = gramconv.get_grammer_set();
+                let table = match ll::LL1Table::build(set, start) {
+                    Ok(table) => table,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let annotation = grammer::GrammerAnnotation::new(set);
+                for (id, lookahead, productions) in table.conflicts() {
+                    println!(
+                        "conflict: {} on {}",
+                        export::nonterminal_name(&names, id),
+                        export::lookahead_name(&tokens, lookahead)
+                    );
+                    for production in productions {
+                        // 先読みが入力の終端なら根拠は示さず生成規則だけを書く
+                        match lookahead
+                            .and_then(|c| annotation.explain_lookahead(set, id, *production, c))
+                        {
+                            Some(trace) => print!(
+                                "{}",
+                                grammer::display_trace(
+                                    set,
+                                    &trace,
+                                    gramconv.get_identifier_map(),
+                                    &tokens
+                                )
+                            ),
+                            None => println!(
+                                "{}",
+                                export::production_label(set, &names, &tokens, id, *production)
+                            ),
+                        }
+                        println!();
+                    }
+                }
+            }
+            Some("--slr") => {
+                let table = slr::build_slr_table(gramconv.get_grammer_set(), start, precedence);
+                for resolution in &table.resolutions {
+                    eprintln!(
+                        "precedence: {}",
+                        resolution.display(gramconv.get_grammer_set(), &names, &tokens)
+                    );
+                }
+                for counterexample in table.find_counterexamples() {
+                    print!(
+                        "{}",
+                        slr::display_counterexample(
+                            &counterexample,
+                            gramconv.get_identifier_map(),
+                            &tokens
+                        )
+                    );
+                }
+            }
+            Some(mode @ ("--dot" | "--dot-lr0")) => {
+                let set = gramconv.get_grammer_set();
+                let table = if mode == "--dot" {
+                    slr::build_lalr_table(set, start, precedence)
+                } else {
+                    slr::build_lr0_table(set, start, precedence)
+                };
+                print!("{}", table.to_dot(gramconv.get_identifier_map(), &tokens));
+            }
+            Some("--lalr") => {
+                let set = gramconv.get_grammer_set();
+                let slr = slr::build_slr_table(set, start, precedence);
+                let lalr = slr::build_lalr_table(set, start, precedence);
+                for resolution in &lalr.resolutions {
+                    eprintln!("precedence: {}", resolution.display(set, &names, &tokens));
+                }
+                // 表の欄と同じく衝突する動作は / で区切る
+                let actions = |actions: &[slr::Action]| {
+                    actions
+                        .iter()
+                        .map(|action| slr::action_label(set, &names, &tokens, action))
+                        .collect::<Vec<_>>()
+                        .join(" / ")
+                };
+                println!("{} states", lalr.automaton.states.len());
+                for (state, lookahead, conflict) in slr::resolved_conflicts(&slr, &lalr) {
+                    println!(
+                        "resolved: state {} on {}: {}",
+                        state,
+                        export::lookahead_name(&tokens, lookahead),
+                        actions(conflict)
+                    );
+                }
+                for (state, lookahead, conflict) in lalr.conflicts() {
+                    println!(
+                        "conflict: state {} on {}: {}",
+                        state,
+                        export::lookahead_name(&tokens, lookahead),
+                        actions(conflict)
+                    );
+                }
+            }
+            Some(mode @ ("--ll-table" | "--slr-table")) => {
+                let format = match std::env::args()
+                    .nth(3)
+                    .unwrap_or_else(|| "md".to_string())
+                    .parse::<export::TableFormat>()
+                {
+                    Ok(format) => format,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let set = gramconv.get_grammer_set();
+                let view = if mode == "--ll-table" {
+                    match ll::LL1Table::build(set, start) {
+                        Ok(table) => table.to_view(set, gramconv.get_identifier_map(), &tokens),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                } else {
+                    let table = slr::build_slr_table(set, start, precedence);
+                    table.to_view(gramconv.get_identifier_map(), &tokens)
+                };
+                print!("{}", view.render(format));
+            }
+            Some(mode @ ("--rust" | "--rust-slr")) => {
+                let set = gramconv.get_grammer_set();
+                let table = if mode == "--rust" {
+                    slr::build_lalr_table(set, start, precedence)
+                } else {
+                    slr::build_slr_table(set, start, precedence)
+                };
+                match table.to_rust(gramconv.get_identifier_map()) {
+                    Ok(code) => print!("{}", code),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some("--rd") => match ll::generate_recursive_descent(&definitions) {
+                Ok(code) => print!("{}", code),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+            Some("--ast") => {
+                // 変換は --rust-raw の構文木を前提にするので, その表に衝突があれば知らせる
+                let raw = lower_raw(&syntactic, &precedences);
+                let table =
+                    slr::build_lalr_table(raw.get_grammer_set(), start, raw.get_precedence_table());
+                let conflicts = table.conflicts().len();
+                if conflicts > 0 {
+                    eprintln!(
+                        "warning: the unsimplified grammar has {} conflicts, so --rust-raw cannot generate the parser this AST converts from",
+                        conflicts
+                    );
+                }
+                let kinds = lexicon
+                    .as_ref()
+                    .map_or(&[][..], scanner::Lexicon::get_kinds);
+                match ast::generate_ast(&syntactic, kinds, "super::parser::Tree") {
+                    Ok(code) => print!("{}", code),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            // --ast の変換が受け取れる, 単純化していない文法の構文解析器
+            Some("--rust-raw") => {
+                let raw = lower_raw(&syntactic, &precedences);
+                let table =
+                    slr::build_lalr_table(raw.get_grammer_set(), start, raw.get_precedence_table());
+                match table.to_rust(raw.get_identifier_map()) {
+                    Ok(code) => print!("{}", code),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some("--rust-actions") => {
+                // simplify は動作の付いた生成規則を書き換えないので番号はそのまま
+                let value_type = std::env::args().nth(3).unwrap_or_else(|| "i64".to_string());
+                let table = slr::build_lalr_table(gramconv.get_grammer_set(), start, precedence);
+                match table.to_rust_with_actions(
+                    gramconv.get_identifier_map(),
+                    &tokens,
+                    gramconv.get_actions(),
+                    &value_type,
+                ) {
+                    Ok(code) => print!("{}", code),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some(mode @ ("--tokens" | "--lex" | "--scanner")) => {
+                let lexicon = lexicon.expect("lexicon is built for the scanner modes");
+                let table = slr::build_lalr_table(gramconv.get_grammer_set(), start, precedence);
+                if mode == "--scanner" {
+                    print!("{}", lexicon.to_rust());
+                } else if mode == "--tokens" {
+                    for (kind, token) in lexicon.get_kinds().iter().enumerate() {
+                        let skip = if token.skip { " (skip)" } else { "" };
+                        println!("{:4} {}{}", kind, token.name(), skip);
+                    }
+                    println!("DFA states: {}", lexicon.get_dfa().transitions.len());
+                    // 字句に分けない文法と比べる. 状態数は優先順位によらない
+                    match grammer::Ebnf2Gram::process(definitions.clone(), &[]) {
+                        Ok(mut scannerless) => {
+                            scannerless.simplify();
+                            let scannerless = slr::build_lalr_table(
+                                scannerless.get_grammer_set(),
+                                start,
+                                scannerless.get_precedence_table(),
+                            );
+                            println!(
+                                "LALR states: {} scannerless, {} tokenized",
+                                scannerless.automaton.states.len(),
+                                table.automaton.states.len()
+                            );
+                        }
+                        Err(e) => println!(
+                            "LALR states: {} tokenized (no scannerless grammar: {})",
+                            table.automaton.states.len(),
+                            e
+                        ),
+                    }
+                } else {
+                    let path = std::env::args().nth(3).expect("no source file");
+                    let source = std::fs::read_to_string(path).expect("source file open");
+                    let lexemes = match lexicon.scan(&source) {
+                        Ok(lexemes) => lexemes,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    };
+                    for lexeme in &lexemes {
+                        println!(
+                            "{:6} {:12} {:?}",
+                            lexeme.position,
+                            lexicon.get_kinds()[lexeme.kind].name(),
+                            lexeme.text
+                        );
+                    }
+                    let terminals = lexemes
+                        .iter()
+                        .map(scanner::Lexeme::terminal)
+                        .collect::<Vec<_>>();
+                    match table.parse(&terminals) {
+                        Ok(_) => println!("accepted"),
+                        Err(e) => println!("rejected: {}", e),
+                    }
+                }
+            }
+            Some("--tables") => {
+                for report in slr::compare_tables(gramconv.get_grammer_set(), start, precedence) {
+                    println!(
+                        "{:8} {:5} states {:4} conflicts",
+                        report.name, report.states, report.conflicts
+                    );
+                }
+            }
+            _ => grammer::display_grammer_set(gramconv.get_grammer_set()),
+        }
+    }
+}
+
+// 三番目の引数のファイルを終端記号の列にする. 字句規則があれば字句に分ける
+fn read_source(lexicon: Option<&scanner::Lexicon>) -> Vec<grammer::Terminal> {
+    let path = std::env::args().nth(3).expect("no source file");
+    let source = std::fs::read_to_string(path).expect("source file open");
+    match lexicon {
+        Some(lexicon) => match lexicon.scan(&source) {
+            Ok(lexemes) => lexemes.iter().map(scanner::Lexeme::terminal).collect(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => source.chars().map(grammer::Terminal::Character).collect(),
+    }
+}
 
-        let gramconv = grammer::Ebnf2Gram::process(rules.unwrap()).unwrap();
-        grammer::display_grammer_set(&gramconv.grammer_set);
-        dbg!(gramconv.identifier_map);
+// 単純化していない文法. --rust-raw の構文解析器と --ast の変換はこの生成規則の並びを使う.
+// 字句の文法では字句規則を分けた後の構文規則を渡す
+fn lower_raw(
+    definitions: &[Box<parser::Definition>],
+    precedences: &[parser::PrecedenceDeclaration],
+) -> grammer::Ebnf2Gram {
+    match grammer::Ebnf2Gram::process(definitions.to_vec(), precedences) {
+        Ok(raw) => raw,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
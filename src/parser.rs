@@ -6,29 +6,211 @@ use anyhow::{anyhow, Result};
 #[derive(Debug)]
 pub struct Parser<'a> {
     tokens: &'a [PositionedToken],
+    precedences: Vec<PrecedenceDeclaration>,
+    lexicals: Vec<LexicalDeclaration>,
+    // 読んでいる定義の中の識別子の参照
+    references: Vec<(String, Span)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+// 1 から数える行と列
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Span {
+    // source は Tokenizer に渡した入力
+    pub fn location(&self, source: &[char]) -> Location {
+        let before = &source[..self.start.min(source.len())];
+        Location {
+            line: before.iter().filter(|c| **c == '\n').count() + 1,
+            column: before.iter().rev().take_while(|c| **c != '\n').count() + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Definition {
     pub identifier: String,
     pub rule: Box<Rule>,
+    pub span: Span,
+    // `=/` で既存の定義に選択肢を追加する
+    pub incremental: bool,
+    // 右辺の識別子の参照と位置
+    pub references: Vec<(String, Span)>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Associativity {
+    Left,
+    Right,
+    Nonassoc,
+}
+
+// `%left "+" "-";` 後に書いたものほど優先順位が高い
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrecedenceDeclaration {
+    pub associativity: Associativity,
+    // 文字列リテラル. 字句規則を分けると字句に置き換わる
+    pub operators: Vec<Rule>,
+    pub span: Span,
+}
+
+// `%token name , number ;` で字句規則にする. `%skip blank ;` の字句は構文解析に渡さない
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LexicalDeclaration {
+    pub names: Vec<String>,
+    pub skip: bool,
+    pub span: Span,
+}
+
+// 選択肢の後に書く構文主導翻訳の動作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemanticAction {
+    // `{{ rust code }}`. `$1` などで右辺の値を参照する
+    Code(String),
+    // `@name`
+    Named(String),
+}
+
+impl std::fmt::Display for SemanticAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SemanticAction::Code(code) => write!(f, "{{{{ {} }}}}", code),
+            SemanticAction::Named(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+#[allow(clippy::vec_box)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Rule {
     Character(char),
+    // 字句の種類. 字句規則を分けた後の構文規則にだけ現れる
+    Token(usize),
     IdentifierRef(String),
-    Exclude { from: Box<Rule>, target: Box<Rule> },
+    Exclude {
+        from: Box<Rule>,
+        target: Box<Rule>,
+    },
     Sequence(Vec<Box<Rule>>),
     Or(Vec<Box<Rule>>),
     Repeat(Box<Rule>),
     Option(Box<Rule>),
     Group(Box<Rule>),
+    Action {
+        rule: Box<Rule>,
+        action: SemanticAction,
+    },
+}
+
+impl Rule {
+    // 動作を外した規則. 動作の付いた括弧は Ebnf2Gram で生成規則にそのまま展開される
+    pub fn strip_action(&self) -> &Rule {
+        match self {
+            Rule::Action { rule, .. } => match rule.as_ref() {
+                Rule::Group(inner) => inner,
+                rule => rule,
+            },
+            rule => rule,
+        }
+    }
+
+    // 文字だけの列 (文字列リテラル) ならその文字列
+    pub fn literal(&self) -> Option<String> {
+        match self {
+            Rule::Character(c) => Some(c.to_string()),
+            Rule::Sequence(rules) => rules.iter().map(|r| r.literal()).collect(),
+            _ => None,
+        }
+    }
+
+    // 文字列リテラル一つ. literal と違い, 並べた文字列はつなげない
+    pub fn string_literal(&self) -> Option<String> {
+        match self {
+            Rule::Character(c) => Some(c.to_string()),
+            Rule::Sequence(rules) => rules
+                .iter()
+                .map(|r| match **r {
+                    Rule::Character(c) => Some(c),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |rules: &[Box<Rule>], separator: &str| {
+            rules
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        match self {
+            Rule::Character(c) => write!(f, "{:?}", c),
+            Rule::Token(kind) => write!(f, "#{}", kind),
+            Rule::IdentifierRef(name) => write!(f, "{}", name),
+            Rule::Exclude { from, target } => write!(f, "{} - {}", from, target),
+            // 文字列リテラルは文字の並びになっているので戻す
+            Rule::Sequence(rules) if rules.iter().all(|r| matches!(**r, Rule::Character(_))) => {
+                let s = rules
+                    .iter()
+                    .map(|r| match **r {
+                        Rule::Character(c) => c,
+                        _ => unreachable!(),
+                    })
+                    .collect::<String>();
+                write!(f, "{:?}", s)
+            }
+            Rule::Sequence(rules) => write!(f, "{}", join(rules, " , ")),
+            Rule::Or(rules) => write!(f, "{}", join(rules, " | ")),
+            Rule::Repeat(rule) => write!(f, "{{ {} }}", rule),
+            Rule::Option(rule) => write!(f, "[ {} ]", rule),
+            Rule::Group(rule) => write!(f, "( {} )", rule),
+            Rule::Action { rule, action } => write!(f, "{} {}", rule, action),
+        }
+    }
 }
 
 impl<'a> Parser<'a> {
     pub fn new<'b>(tokens: &'b [PositionedToken]) -> Parser<'b> {
-        Parser { tokens }
+        Parser {
+            tokens,
+            precedences: Vec::new(),
+            lexicals: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    pub fn get_precedences(&self) -> &[PrecedenceDeclaration] {
+        &self.precedences
+    }
+
+    pub fn get_lexical_declarations(&self) -> &[LexicalDeclaration] {
+        &self.lexicals
     }
 
     fn bump(&mut self, size: usize) {
@@ -42,16 +224,27 @@ impl<'a> Parser<'a> {
 
     fn make_error(&self, msg: &str) -> anyhow::Error {
         if let [PositionedToken(_, pos), ..] = self.tokens {
-            anyhow::Error::msg(format!("{} {}", pos, msg.to_string()))
+            anyhow::Error::msg(format!("{} {}", pos, msg))
         } else {
-            anyhow::Error::msg(format!("Unknown position {}", msg.to_string()))
+            anyhow::Error::msg(format!("Unknown position {}", msg))
         }
     }
 
+    #[allow(clippy::vec_box)]
     pub fn eat(&mut self) -> Result<Vec<Box<Definition>>> {
         let mut defs = Vec::new();
         while !self.is_empty() {
-            defs.push(self.eat_single_def()?);
+            if let [PositionedToken(Token::Directive(name), _), ..] = self.tokens {
+                if name == "token" || name == "skip" {
+                    let declaration = self.eat_lexical()?;
+                    self.lexicals.push(declaration);
+                } else {
+                    let declaration = self.eat_precedence()?;
+                    self.precedences.push(declaration);
+                }
+            } else {
+                defs.push(self.eat_single_def()?);
+            }
         }
         if self.is_empty() {
             Ok(defs)
@@ -61,22 +254,91 @@ impl<'a> Parser<'a> {
     }
 
     fn eat_single_def(&mut self) -> Result<Box<Definition>> {
-        if let [PositionedToken(Token::Identifier(identifier), _), PositionedToken(Token::Equals, _), ..] =
+        if let [PositionedToken(Token::Identifier(identifier), start), PositionedToken(equals @ (Token::Equals | Token::IncrementalEquals), _), ..] =
             self.tokens
         {
+            let incremental = *equals == Token::IncrementalEquals;
             self.bump(2);
+            self.references.clear();
             let rule = self.eat_rule()?;
-            if let [PositionedToken(Token::TokenEnd, _), ..] = self.tokens {
+            if let [PositionedToken(Token::TokenEnd, end), ..] = self.tokens {
                 self.bump(1);
                 return Ok(Box::new(Definition {
                     identifier: identifier.clone(),
                     rule,
+                    span: Span {
+                        start: *start,
+                        end: end + 1,
+                    },
+                    incremental,
+                    references: std::mem::take(&mut self.references),
                 }));
             }
         }
         Err(self.make_error("Definition is not valid"))
     }
 
+    fn eat_precedence(&mut self) -> Result<PrecedenceDeclaration> {
+        let [PositionedToken(Token::Directive(name), start), ..] = self.tokens else {
+            return Err(self.make_error("Directive expected"));
+        };
+        let associativity = match name.as_str() {
+            "left" => Associativity::Left,
+            "right" => Associativity::Right,
+            "nonassoc" => Associativity::Nonassoc,
+            _ => return Err(self.make_error(&format!("Unknown directive %{}", name))),
+        };
+        let start = *start;
+        self.bump(1);
+        let mut operators = Vec::new();
+        while let [PositionedToken(Token::String(s), _), ..] = self.tokens {
+            operators.push(self.convert_string_rule(s));
+            self.bump(1);
+        }
+        if let [PositionedToken(Token::TokenEnd, end), ..] = self.tokens {
+            if !operators.is_empty() {
+                let end = end + 1;
+                self.bump(1);
+                return Ok(PrecedenceDeclaration {
+                    associativity,
+                    operators,
+                    span: Span { start, end },
+                });
+            }
+        }
+        Err(self.make_error("Precedence declaration is not valid"))
+    }
+
+    fn eat_lexical(&mut self) -> Result<LexicalDeclaration> {
+        let [PositionedToken(Token::Directive(name), start), ..] = self.tokens else {
+            return Err(self.make_error("Directive expected"));
+        };
+        let skip = name == "skip";
+        let start = *start;
+        self.bump(1);
+        let mut names = Vec::new();
+        while let [PositionedToken(Token::Identifier(name), _), ..] = self.tokens {
+            names.push(name.clone());
+            self.bump(1);
+            match self.tokens {
+                [PositionedToken(Token::Separator, _), ..] => self.bump(1),
+                _ => break,
+            }
+        }
+        if let [PositionedToken(Token::TokenEnd, end), ..] = self.tokens {
+            if !names.is_empty() {
+                let end = end + 1;
+                self.bump(1);
+                return Ok(LexicalDeclaration {
+                    names,
+                    skip,
+                    span: Span { start, end },
+                });
+            }
+        }
+        Err(self.make_error("Lexical declaration is not valid"))
+    }
+
     fn eat_rule(&mut self) -> Result<Box<Rule>> {
         let mut seq = Vec::new();
         loop {
@@ -98,14 +360,14 @@ impl<'a> Parser<'a> {
             0 => Err(self.make_error("No rule found between separator")),
             1 => Ok(Box::new(seq.pop().unwrap())),
             _ => Ok(Box::new(Rule::Sequence(
-                seq.into_iter().map(|r| Box::new(r)).collect(),
+                seq.into_iter().map(Box::new).collect(),
             ))),
         }
     }
 
     fn eat_element(&mut self) -> Result<Rule> {
         let mut left: Option<Rule> = None;
-        while let [PositionedToken(token, _), ..] = self.tokens {
+        while let [PositionedToken(token, pos), ..] = self.tokens {
             if let Some(l) = &left {
                 match token {
                     Token::Or => {
@@ -120,6 +382,20 @@ impl<'a> Parser<'a> {
                         }
                         left = Some(Rule::Or(rules));
                     }
+                    Token::Action(code) => {
+                        self.bump(1);
+                        left = Some(Rule::Action {
+                            rule: Box::new(l.clone()),
+                            action: SemanticAction::Code(code.clone()),
+                        });
+                    }
+                    Token::ActionTag(name) => {
+                        self.bump(1);
+                        left = Some(Rule::Action {
+                            rule: Box::new(l.clone()),
+                            action: SemanticAction::Named(name.clone()),
+                        });
+                    }
                     Token::Exclude => {
                         self.bump(1); // Eat Exclude
                         let right = self.eat_element()?;
@@ -160,11 +436,16 @@ impl<'a> Parser<'a> {
                     }
                     Token::Identifier(s) => {
                         self.bump(1);
+                        let span = Span {
+                            start: *pos,
+                            end: pos + s.chars().count(),
+                        };
+                        self.references.push((s.clone(), span));
                         left = Some(Rule::IdentifierRef(s.clone()));
                     }
                     Token::String(s) => {
                         self.bump(1);
-                        left = Some(self.convert_string_rule(&s));
+                        left = Some(self.convert_string_rule(s));
                     }
                     _ => {
                         break;
@@ -197,7 +478,7 @@ fn convert_string_rule_test() {
                 Rule::Character('t'),
             ]
             .into_iter()
-            .map(|r| Box::new(r))
+            .map(Box::new)
             .collect()
         )
     )
@@ -217,7 +498,7 @@ fn string_rule_test() {
                 Rule::Character('t'),
             ]
             .into_iter()
-            .map(|r| Box::new(r))
+            .map(Box::new)
             .collect()
         )
     );
@@ -234,3 +515,38 @@ fn identifier_rule_test() {
     );
     assert!(parser.is_empty());
 }
+
+#[test]
+fn precedence_declaration_test() {
+    use crate::lexer::Tokenizer;
+
+    let input = "%left '+' '-' ; %right '^' ; expr = 'x' ;"
+        .chars()
+        .collect::<Vec<_>>();
+    let tokens = Tokenizer::new(&input).read_definition().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let defs = parser.eat().unwrap();
+    assert_eq!(defs.len(), 1);
+    assert_eq!(
+        parser.get_precedences(),
+        &[
+            PrecedenceDeclaration {
+                associativity: Associativity::Left,
+                operators: vec![
+                    Rule::Sequence(vec![Box::new(Rule::Character('+'))]),
+                    Rule::Sequence(vec![Box::new(Rule::Character('-'))]),
+                ],
+                span: Span { start: 0, end: 15 },
+            },
+            PrecedenceDeclaration {
+                associativity: Associativity::Right,
+                operators: vec![Rule::Sequence(vec![Box::new(Rule::Character('^'))])],
+                span: Span { start: 16, end: 28 },
+            },
+        ]
+    );
+
+    let input = "%left ;".chars().collect::<Vec<_>>();
+    let tokens = Tokenizer::new(&input).read_definition().unwrap();
+    assert!(Parser::new(&tokens).eat().is_err());
+}
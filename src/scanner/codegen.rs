@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+
+use super::{Lexicon, TokenPattern};
+use crate::export::camel_case;
+
+// 生成した字句解析器の型と駆動部. TokenKind と表は前に置く
+const DRIVER: &str = r#"
+impl TokenKind {
+    // 構文解析器の終端記号 (種類の番号)
+    pub fn terminal(self) -> usize {
+        self as usize
+    }
+}
+
+// position は入力の文字単位の位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub position: usize,
+    pub found: char,
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: no token matches {:?}", self.position, self.found)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+// 最長一致. 読み飛ばす字句は返さない
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ScanError> {
+    let input = input.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        let mut state = 0;
+        let mut last = None;
+        for (end, c) in input.iter().enumerate().skip(pos) {
+            let transitions = TRANSITIONS[state];
+            match transitions.binary_search_by_key(c, |(c, _)| *c) {
+                Ok(i) => state = transitions[i].1,
+                Err(_) => break,
+            }
+            if let Some(kind) = ACCEPT[state] {
+                last = Some((end + 1, kind));
+            }
+        }
+        let Some((end, kind)) = last else {
+            return Err(ScanError {
+                position: pos,
+                found: input[pos],
+            });
+        };
+        if !SKIP.contains(&kind) {
+            tokens.push(Token {
+                kind,
+                text: input[pos..end].iter().collect(),
+                position: pos,
+            });
+        }
+        pos = end;
+    }
+    Ok(tokens)
+}
+
+// 構文解析器に渡す (終端記号, 字句の文字列) の列
+pub fn terminals(tokens: &[Token]) -> Vec<(usize, String)> {
+    tokens
+        .iter()
+        .map(|t| (t.kind.terminal(), t.text.clone()))
+        .collect()
+}
+"#;
+
+fn symbol_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '+' => "Plus",
+        '-' => "Minus",
+        '*' => "Star",
+        '/' => "Slash",
+        '%' => "Percent",
+        '=' => "Equals",
+        '<' => "Less",
+        '>' => "Greater",
+        '!' => "Bang",
+        '&' => "Amp",
+        '|' => "Pipe",
+        '^' => "Caret",
+        '~' => "Tilde",
+        '.' => "Dot",
+        ',' => "Comma",
+        ':' => "Colon",
+        ';' => "Semicolon",
+        '(' => "LParen",
+        ')' => "RParen",
+        '[' => "LBracket",
+        ']' => "RBracket",
+        '{' => "LBrace",
+        '}' => "RBrace",
+        '?' => "Question",
+        '#' => "Hash",
+        '@' => "At",
+        '$' => "Dollar",
+        '\'' => "Quote",
+        '"' => "DoubleQuote",
+        '\\' => "Backslash",
+        _ => return None,
+    })
+}
+
+// 予約語と規則は名前を, 記号は文字の名前を並べる. 作れなければ番号を使う
+fn variant_name(pattern: &TokenPattern, kind: usize) -> String {
+    let name = match pattern {
+        TokenPattern::Rule(name) => camel_case(name),
+        TokenPattern::Literal(s) if s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            camel_case(s)
+        }
+        TokenPattern::Literal(s) => s
+            .chars()
+            .map(symbol_name)
+            .collect::<Option<String>>()
+            .unwrap_or_default(),
+    };
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => name,
+        _ => format!("Token{}", kind),
+    }
+}
+
+impl Lexicon {
+    // 実行時にこのツールに依存しない字句解析器を Rust のモジュールとして書き出す
+    pub fn to_rust(&self) -> String {
+        let mut names = Vec::new();
+        let mut used = HashSet::new();
+        for (kind, token) in self.kinds.iter().enumerate() {
+            let mut name = variant_name(&token.pattern, kind);
+            if !used.insert(name.clone()) {
+                name = format!("{}{}", name, kind);
+                used.insert(name.clone());
+            }
+            names.push(name);
+        }
+
+        let mut output =
+            String::from("// Generated by rust-ebnf-cc. Do not edit.\n#![allow(dead_code)]\n\n");
+        output += "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\npub enum TokenKind {\n";
+        for (kind, token) in self.kinds.iter().enumerate() {
+            output += &format!("    // {}\n    {} = {},\n", token.name(), names[kind], kind);
+        }
+        output += "}\n\n";
+
+        let skip = self
+            .kinds
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token.skip)
+            .map(|(kind, _)| format!("TokenKind::{}", names[kind]))
+            .collect::<Vec<_>>();
+        output += &format!("const SKIP: &[TokenKind] = &[{}];\n\n", skip.join(", "));
+
+        output += "const TRANSITIONS: &[&[(char, usize)]] = &[\n";
+        for transitions in &self.dfa.transitions {
+            let transitions = transitions
+                .iter()
+                .map(|(c, next)| format!("({:?}, {})", c, next))
+                .collect::<Vec<_>>();
+            output += &format!("    &[{}],\n", transitions.join(", "));
+        }
+        output += "];\n\n";
+
+        output += "const ACCEPT: &[Option<TokenKind>] = &[\n";
+        for accept in &self.dfa.accept {
+            match accept {
+                Some(kind) => output += &format!("    Some(TokenKind::{}),\n", names[*kind]),
+                None => output += "    None,\n",
+            }
+        }
+        output += "];\n";
+        output += DRIVER;
+        output
+    }
+}
+
+#[test]
+fn generated_scanner_test() {
+    use crate::grammer::GrammerIdentifier;
+    use crate::slr::build_lalr_table;
+    use crate::testing::process_tokenized;
+
+    assert_eq!(
+        variant_name(&TokenPattern::Literal(":=".into()), 3),
+        "ColonEquals"
+    );
+    assert_eq!(
+        variant_name(&TokenPattern::Literal("program".into()), 3),
+        "Program"
+    );
+    assert_eq!(
+        variant_name(&TokenPattern::Literal("€".into()), 3),
+        "Token3"
+    );
+    assert_eq!(
+        variant_name(&TokenPattern::Rule("unsigned integer".into()), 3),
+        "UnsignedInteger"
+    );
+
+    let (lexicon, mut tokenized) = process_tokenized(
+        "%token name , number ; %skip blank , comment ;
+         %left '+' '-' ;
+         program = 'program' , name , ';' , { statement } , 'end' , '.' ;
+         statement = ( name , ':=' , expression , ';' ) | ( 'write' , expression , ';' ) ;
+         expression = name | number | ( expression , '+' , expression ) | ( expression , '-' , expression ) ;
+         name = alphabet , { alphabet | digit } ;
+         number = digit , { digit } ;
+         comment = '{' , { alphabet | digit | ' ' } , '}' ;
+         alphabet = 'a' | 'b' | 'c' | 'd' | 'e' | 'f' | 'g' | 'h' | 'i' | 'j' | 'k' | 'l' | 'm'
+                  | 'n' | 'o' | 'p' | 'q' | 'r' | 's' | 't' | 'u' | 'v' | 'w' | 'x' | 'y' | 'z' ;
+         digit = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' ;
+         blank = ' ' , { ' ' } ;",
+    );
+    tokenized.simplify();
+    let table = build_lalr_table(
+        tokenized.get_grammer_set(),
+        GrammerIdentifier(0),
+        tokenized.get_precedence_table(),
+    );
+    let parser_code = table.to_rust(tokenized.get_identifier_map()).unwrap();
+    let scanner_code = lexicon.to_rust();
+    assert!(scanner_code.contains("    ColonEquals = "));
+
+    let main = r#"mod parser;
+mod scanner;
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        let tokens = match scanner::tokenize(&input) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        let kinds = tokens
+            .iter()
+            .map(|t| format!("{:?}", t.kind))
+            .collect::<Vec<_>>();
+        match parser::parse(&scanner::terminals(&tokens)) {
+            Ok(_) => println!("{}", kinds.join(" ")),
+            Err(e) => println!("{}", e.position),
+        }
+    }
+}
+"#;
+    let inputs = [
+        "program p; x := x + 1; end.",
+        "program endx; {note} write endx - 20; end.",
+        "program p; x := ; end.",
+        "program p?",
+    ];
+    let actual = crate::testing::run_generated_crate(
+        "scanner",
+        &[
+            ("parser.rs", &parser_code),
+            ("scanner.rs", &scanner_code),
+            ("main.rs", main),
+        ],
+        &inputs,
+    );
+
+    // 実行時の字句解析器と同じ字句に分ける
+    let mut expected = Vec::new();
+    for input in inputs {
+        let line = match lexicon.scan(input) {
+            Ok(lexemes) => {
+                let terminals = lexemes
+                    .iter()
+                    .map(super::Lexeme::terminal)
+                    .collect::<Vec<_>>();
+                match table.parse(&terminals) {
+                    Ok(_) => lexemes
+                        .iter()
+                        .map(|l| variant_name(&lexicon.kinds[l.kind].pattern, l.kind))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    Err(e) => e.to_string().split(' ').next().unwrap().to_string(),
+                }
+            }
+            Err(_) => "9: no token matches '?'".to_string(),
+        };
+        expected.push(line);
+    }
+    assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    assert_eq!(
+        expected[1],
+        "Program Name Semicolon Write Name Minus Number Semicolon End Dot"
+    );
+    assert_eq!(expected[2], "5");
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::Nfa;
+
+// 遷移のない文字は行き止まり. 状態 0 が開始状態
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dfa {
+    pub transitions: Vec<BTreeMap<char, usize>>,
+    // 受理する字句の種類
+    pub accept: Vec<Option<usize>>,
+}
+
+fn epsilon_closure(nfa: &Nfa, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+    let mut closure = BTreeSet::new();
+    let mut queue = states.into_iter().collect::<Vec<_>>();
+    while let Some(state) = queue.pop() {
+        if closure.insert(state) {
+            queue.extend(nfa.states[state].epsilon.iter().copied());
+        }
+    }
+    closure
+}
+
+impl Dfa {
+    // 部分集合構成. 複数の種類を受理する状態は rank の最も小さい種類を受理する
+    pub fn from_nfa(nfa: &Nfa, rank: impl Fn(usize) -> usize) -> Self {
+        let mut dfa = Dfa::default();
+        let start = epsilon_closure(nfa, [0]);
+        let mut index = HashMap::from([(start.clone(), 0)]);
+        let mut queue = vec![start];
+        dfa.transitions.push(BTreeMap::new());
+        dfa.accept.push(None);
+        while let Some(subset) = queue.pop() {
+            let state = index[&subset];
+            dfa.accept[state] = subset
+                .iter()
+                .filter_map(|s| nfa.states[*s].accept)
+                .min_by_key(|kind| rank(*kind));
+
+            let mut moves: BTreeMap<char, BTreeSet<usize>> = BTreeMap::new();
+            for s in &subset {
+                for (c, next) in &nfa.states[*s].transitions {
+                    moves.entry(*c).or_default().insert(*next);
+                }
+            }
+            for (c, targets) in moves {
+                let target = epsilon_closure(nfa, targets);
+                let next = match index.get(&target) {
+                    Some(next) => *next,
+                    None => {
+                        let next = dfa.transitions.len();
+                        dfa.transitions.push(BTreeMap::new());
+                        dfa.accept.push(None);
+                        index.insert(target.clone(), next);
+                        queue.push(target);
+                        next
+                    }
+                };
+                dfa.transitions[state].insert(c, next);
+            }
+        }
+        dfa
+    }
+
+    // 受理状態に辿り着けない状態への遷移を除き, 受理する種類と遷移先の組が同じ状態を
+    // 分けられなくなるまで分割する (Moore の方法)
+    pub fn minimize(&self) -> Self {
+        let mut live = self
+            .accept
+            .iter()
+            .enumerate()
+            .filter(|(_, accept)| accept.is_some())
+            .map(|(state, _)| state)
+            .collect::<HashSet<_>>();
+        loop {
+            let before = live.len();
+            for (state, transitions) in self.transitions.iter().enumerate() {
+                if transitions.values().any(|next| live.contains(next)) {
+                    live.insert(state);
+                }
+            }
+            if live.len() == before {
+                break;
+            }
+        }
+
+        let mut class = self
+            .accept
+            .iter()
+            .map(|accept| accept.map_or(0, |kind| kind + 1))
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        loop {
+            let mut signatures = HashMap::new();
+            let refined = (0..self.transitions.len())
+                .map(|state| {
+                    let moves = self.transitions[state]
+                        .iter()
+                        .filter(|(_, next)| live.contains(*next))
+                        .map(|(c, next)| (*c, class[*next]))
+                        .collect::<Vec<_>>();
+                    let next_class = signatures.len();
+                    *signatures
+                        .entry((class[state], moves))
+                        .or_insert(next_class)
+                })
+                .collect::<Vec<_>>();
+            class = refined;
+            if signatures.len() == count {
+                break;
+            }
+            count = signatures.len();
+        }
+
+        // 状態 0 から番号を振り直しているので開始状態の分類は 0
+        let mut merged = Dfa {
+            transitions: vec![BTreeMap::new(); count],
+            accept: vec![None; count],
+        };
+        for (state, transitions) in self.transitions.iter().enumerate() {
+            merged.accept[class[state]] = self.accept[state];
+            for (c, next) in transitions {
+                if live.contains(next) {
+                    merged.transitions[class[state]].insert(*c, class[*next]);
+                }
+            }
+        }
+
+        // 行き止まりの状態は開始状態から辿れなくなっているので除き, 幅優先の順に番号を振る
+        let mut order = vec![0];
+        let mut number = HashMap::from([(0, 0)]);
+        let mut i = 0;
+        while i < order.len() {
+            for next in merged.transitions[order[i]].values() {
+                if !number.contains_key(next) {
+                    number.insert(*next, order.len());
+                    order.push(*next);
+                }
+            }
+            i += 1;
+        }
+        Dfa {
+            transitions: order
+                .iter()
+                .map(|state| {
+                    merged.transitions[*state]
+                        .iter()
+                        .map(|(c, next)| (*c, number[next]))
+                        .collect()
+                })
+                .collect(),
+            accept: order.iter().map(|state| merged.accept[*state]).collect(),
+        }
+    }
+
+    // self が受理して other が受理しない文字列を受理する DFA. 受理する種類は 0
+    pub fn difference(&self, other: &Dfa) -> Dfa {
+        let mut dfa = Dfa::default();
+        let mut index = HashMap::from([((0, Some(0)), 0)]);
+        let mut queue = vec![(0, Some(0))];
+        dfa.transitions.push(BTreeMap::new());
+        dfa.accept.push(None);
+        while let Some(pair @ (state, other_state)) = queue.pop() {
+            let current = index[&pair];
+            let excluded = other_state.is_some_and(|s| other.accept[s].is_some());
+            if self.accept[state].is_some() && !excluded {
+                dfa.accept[current] = Some(0);
+            }
+            for (c, next) in &self.transitions[state] {
+                let target = (
+                    *next,
+                    other_state.and_then(|s| other.transitions[s].get(c).copied()),
+                );
+                let next = match index.get(&target) {
+                    Some(next) => *next,
+                    None => {
+                        let next = dfa.transitions.len();
+                        dfa.transitions.push(BTreeMap::new());
+                        dfa.accept.push(None);
+                        index.insert(target, next);
+                        queue.push(target);
+                        next
+                    }
+                };
+                dfa.transitions[current].insert(*c, next);
+            }
+        }
+        dfa.minimize()
+    }
+
+    // input の start から最も長く一致する終わりの位置と種類. 空の一致は含めない
+    pub fn longest_match(&self, input: &[char], start: usize) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut last = None;
+        for (end, c) in input.iter().enumerate().skip(start) {
+            let Some(next) = self.transitions[state].get(c) else {
+                break;
+            };
+            state = *next;
+            if let Some(kind) = self.accept[state] {
+                last = Some((end + 1, kind));
+            }
+        }
+        last
+    }
+}
+
+#[test]
+fn dfa_test() {
+    use crate::testing::parse_str;
+
+    let dfa = |source: &str| {
+        let definitions = parse_str(source).unwrap().definitions;
+        let rules = definitions
+            .iter()
+            .map(|d| (d.identifier.clone(), (*d.rule).clone()))
+            .collect::<HashMap<_, _>>();
+        let nfa = Nfa::build(&[(*definitions[0].rule).clone()], &rules).unwrap();
+        Dfa::from_nfa(&nfa, |k| k)
+    };
+
+    // a* を二つ並べても最小化すれば一状態
+    let repeated = dfa("x = { 'a' } , { 'a' } ;");
+    assert!(repeated.transitions.len() > 1);
+    assert_eq!(repeated.minimize().transitions.len(), 1);
+
+    // 'ab' 以外の a と b の並び
+    let word =
+        dfa("word = ( letter , { letter } ) - ( 'a' , 'b' ) ; letter = 'a' | 'b' ;").minimize();
+    assert_eq!(word.transitions.len(), 4);
+    let input = "abba".chars().collect::<Vec<_>>();
+    assert_eq!(word.longest_match(&input, 0), Some((4, 0)));
+    assert_eq!(word.longest_match(&input[..2], 0), Some((1, 0)));
+    assert_eq!(word.longest_match(&input, 1), Some((4, 0)));
+    assert_eq!(word.longest_match(&['c'], 0), None);
+}
@@ -0,0 +1,439 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+mod codegen;
+mod dfa;
+mod nfa;
+pub use dfa::Dfa;
+pub use nfa::Nfa;
+
+use crate::grammer::Terminal;
+use crate::lint::collect_references;
+use crate::parser::{Definition, LexicalDeclaration, PrecedenceDeclaration, Rule};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum TokenPattern {
+    // 構文規則に書かれた文字列 (予約語や記号)
+    Literal(String),
+    // `%token` で宣言した字句規則
+    Rule(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TokenKind {
+    pub pattern: TokenPattern,
+    pub skip: bool,
+}
+
+impl TokenKind {
+    pub fn name(&self) -> String {
+        match &self.pattern {
+            TokenPattern::Literal(s) => format!("{:?}", s),
+            TokenPattern::Rule(name) => name.clone(),
+        }
+    }
+}
+
+// position は入力の文字単位の位置
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Lexeme {
+    pub kind: usize,
+    pub text: String,
+    pub position: usize,
+}
+
+impl Lexeme {
+    // 構文解析器に渡す終端記号
+    pub fn terminal(&self) -> Terminal {
+        Terminal::Token(self.kind)
+    }
+}
+
+// 字句の種類とそれを読む DFA. 字句規則そのものは分けるときにだけ使う
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lexicon {
+    kinds: Vec<TokenKind>,
+    dfa: Dfa,
+}
+
+// 字句規則と構文規則を分ける. 宣言された規則とそこから参照される規則が字句規則になり,
+// 構文規則の中の字句規則の参照と文字列は字句の種類を表す終端記号に置き換える
+#[allow(clippy::vec_box)]
+pub fn separate(
+    definitions: &[Box<Definition>],
+    declarations: &[LexicalDeclaration],
+) -> Result<(Lexicon, Vec<Box<Definition>>)> {
+    let mut rules: HashMap<String, Rule> = HashMap::new();
+    for definition in definitions {
+        let rule = match rules.remove(&definition.identifier) {
+            Some(Rule::Or(mut alternatives)) if definition.incremental => {
+                alternatives.push(definition.rule.clone());
+                Rule::Or(alternatives)
+            }
+            Some(rule) if definition.incremental => {
+                Rule::Or(vec![Box::new(rule), definition.rule.clone()])
+            }
+            _ => (*definition.rule).clone(),
+        };
+        rules.insert(definition.identifier.clone(), rule);
+    }
+
+    let mut kinds = Vec::new();
+    for declaration in declarations {
+        for name in &declaration.names {
+            if !rules.contains_key(name) {
+                return Err(anyhow!(
+                    "{} `{}` is declared as a token but not defined",
+                    declaration.span,
+                    name
+                ));
+            }
+            kinds.push(TokenKind {
+                pattern: TokenPattern::Rule(name.clone()),
+                skip: declaration.skip,
+            });
+        }
+    }
+
+    // 字句規則から参照される規則も字句規則
+    let mut lexical = declarations
+        .iter()
+        .flat_map(|d| d.names.iter().map(String::as_str))
+        .collect::<HashSet<_>>();
+    let mut queue = lexical.iter().copied().collect::<Vec<_>>();
+    while let Some(name) = queue.pop() {
+        let mut refs = Vec::new();
+        collect_references(&rules[name], &mut refs);
+        for name in refs {
+            if !rules.contains_key(name) {
+                return Err(anyhow!("Unknown identifier {}", name));
+            }
+            if lexical.insert(name) {
+                queue.push(name);
+            }
+        }
+    }
+    // 正規言語に限るので再帰する字句規則は扱わない
+    for name in &lexical {
+        let mut visited = HashSet::new();
+        let mut queue = vec![*name];
+        while let Some(current) = queue.pop() {
+            let mut refs = Vec::new();
+            collect_references(&rules[current], &mut refs);
+            for next in refs {
+                if next == *name {
+                    return Err(anyhow!("Lexical rule `{}` is recursive", name));
+                }
+                if visited.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+    }
+    if let Some(start) = definitions.first() {
+        if lexical.contains(start.identifier.as_str()) {
+            return Err(anyhow!(
+                "The start rule `{}` must not be lexical",
+                start.identifier
+            ));
+        }
+    }
+
+    // 字句規則の定義. `=/` は選択肢として併合してある
+    let rules = rules
+        .iter()
+        .filter(|(name, _)| lexical.contains(name.as_str()))
+        .map(|(name, rule)| (name.clone(), rule.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut lexicon = Lexicon {
+        kinds,
+        dfa: Dfa::default(),
+    };
+    let mut syntactic = Vec::new();
+    for definition in definitions {
+        if lexical.contains(definition.identifier.as_str()) {
+            continue;
+        }
+        let rule = lexicon.rewrite(&definition.rule, &rules)?;
+        syntactic.push(Box::new(Definition {
+            rule: Box::new(rule),
+            ..(**definition).clone()
+        }));
+    }
+    // 構文規則の文字列も字句になるので DFA は最後に作る
+    lexicon.dfa = lexicon.build_dfa(&rules)?;
+    Ok((lexicon, syntactic))
+}
+
+impl Lexicon {
+    pub fn get_kinds(&self) -> &[TokenKind] {
+        &self.kinds
+    }
+
+    // 報告で字句の種類を書くための名前. 番号は字句の種類の番号
+    pub fn token_names(&self) -> Vec<String> {
+        self.kinds.iter().map(TokenKind::name).collect()
+    }
+
+    fn literal_kind(&mut self, s: String) -> usize {
+        let pattern = TokenPattern::Literal(s);
+        match self.kinds.iter().position(|k| k.pattern == pattern) {
+            Some(kind) => kind,
+            None => {
+                self.kinds.push(TokenKind {
+                    pattern,
+                    skip: false,
+                });
+                self.kinds.len() - 1
+            }
+        }
+    }
+
+    // 文字列一つが一つの字句になる. 並べた文字列は要素ごとに別の字句にする
+    fn rewrite(&mut self, rule: &Rule, rules: &HashMap<String, Rule>) -> Result<Rule> {
+        if let Some(s) = rule.string_literal() {
+            return Ok(Rule::Token(self.literal_kind(s)));
+        }
+        Ok(match rule {
+            Rule::IdentifierRef(name) if rules.contains_key(name) => {
+                let pattern = TokenPattern::Rule(name.clone());
+                let Some(kind) = self.kinds.iter().position(|k| k.pattern == pattern) else {
+                    return Err(anyhow!(
+                        "`{}` is a lexical fragment; declare it with %token to use it in syntactic rules",
+                        name
+                    ));
+                };
+                if self.kinds[kind].skip {
+                    return Err(anyhow!(
+                        "Skipped token `{}` is used in a syntactic rule",
+                        name
+                    ));
+                }
+                Rule::Token(kind)
+            }
+            Rule::Sequence(sequence) => Rule::Sequence(self.rewrite_all(sequence, rules)?),
+            Rule::Or(alternatives) => Rule::Or(self.rewrite_all(alternatives, rules)?),
+            Rule::Exclude { from, target } => Rule::Exclude {
+                from: Box::new(self.rewrite(from, rules)?),
+                target: Box::new(self.rewrite(target, rules)?),
+            },
+            Rule::Repeat(rule) => Rule::Repeat(Box::new(self.rewrite(rule, rules)?)),
+            Rule::Option(rule) => Rule::Option(Box::new(self.rewrite(rule, rules)?)),
+            Rule::Group(rule) => Rule::Group(Box::new(self.rewrite(rule, rules)?)),
+            Rule::Action { rule, action } => Rule::Action {
+                rule: Box::new(self.rewrite(rule, rules)?),
+                action: action.clone(),
+            },
+            rule => rule.clone(),
+        })
+    }
+
+    #[allow(clippy::vec_box)]
+    fn rewrite_all(
+        &mut self,
+        sequence: &[Box<Rule>],
+        rules: &HashMap<String, Rule>,
+    ) -> Result<Vec<Box<Rule>>> {
+        sequence
+            .iter()
+            .map(|r| Ok(Box::new(self.rewrite(r, rules)?)))
+            .collect()
+    }
+
+    // 演算子の文字列を字句に置き換える. 字句になれば複数文字の演算子も使える
+    pub fn rewrite_precedences(
+        &self,
+        declarations: &[PrecedenceDeclaration],
+    ) -> Vec<PrecedenceDeclaration> {
+        let mut declarations = declarations.to_vec();
+        for declaration in &mut declarations {
+            for operator in &mut declaration.operators {
+                let Some(s) = operator.string_literal() else {
+                    continue;
+                };
+                let pattern = TokenPattern::Literal(s);
+                if let Some(kind) = self.kinds.iter().position(|k| k.pattern == pattern) {
+                    *operator = Rule::Token(kind);
+                }
+            }
+        }
+        declarations
+    }
+
+    // 長さが同じなら文字列の字句 (予約語) を, その次に先に宣言した字句を選ぶ
+    fn rank(&self, kind: usize) -> usize {
+        match self.kinds[kind].pattern {
+            TokenPattern::Literal(_) => kind,
+            TokenPattern::Rule(_) => self.kinds.len() + kind,
+        }
+    }
+
+    // すべての字句の種類を受理する最小の DFA
+    fn build_dfa(&self, rules: &HashMap<String, Rule>) -> Result<Dfa> {
+        let patterns = self
+            .kinds
+            .iter()
+            .map(|kind| match &kind.pattern {
+                TokenPattern::Literal(s) => {
+                    Rule::Sequence(s.chars().map(|c| Box::new(Rule::Character(c))).collect())
+                }
+                TokenPattern::Rule(name) => Rule::IdentifierRef(name.clone()),
+            })
+            .collect::<Vec<_>>();
+        let nfa = Nfa::build(&patterns, rules)?;
+        Ok(Dfa::from_nfa(&nfa, |kind| self.rank(kind)).minimize())
+    }
+
+    pub fn get_dfa(&self) -> &Dfa {
+        &self.dfa
+    }
+
+    // DFA による最長一致
+    pub fn scan(&self, input: &str) -> Result<Vec<Lexeme>> {
+        let input = input.chars().collect::<Vec<_>>();
+        let mut lexemes = Vec::new();
+        let mut pos = 0;
+        while pos < input.len() {
+            let Some((end, kind)) = self.dfa.longest_match(&input, pos) else {
+                return Err(anyhow!("{} No token matches {:?}", pos, input[pos]));
+            };
+            if !self.kinds[kind].skip {
+                lexemes.push(Lexeme {
+                    kind,
+                    text: input[pos..end].iter().collect(),
+                    position: pos,
+                });
+            }
+            pos = end;
+        }
+        Ok(lexemes)
+    }
+}
+
+#[test]
+fn lexical_separation_test() {
+    use crate::grammer::{Ebnf2Gram, GrammerIdentifier};
+    use crate::slr::build_lalr_table;
+    use crate::testing::parse_str;
+
+    let source = parse_str(
+        "%token name , number ; %skip blank ;
+        program = 'program' , name , ';' , { statement } , 'end' , '.' ;
+        statement = ( name , ':=' , expression , ';' ) | ( 'write' , expression , ';' ) ;
+        expression = term , { ( '+' | '-' ) , term } ;
+        term = name | number ;
+        name = alphabet , { alphabet | digit } ;
+        number = digit , { digit } ;
+        alphabet = 'a' | 'b' | 'c' | 'd' | 'e' | 'f' | 'g' | 'h' | 'i' | 'j' | 'k' | 'l' | 'm'
+                 | 'n' | 'o' | 'p' | 'q' | 'r' | 's' | 't' | 'u' | 'v' | 'w' | 'x' | 'y' | 'z' ;
+        digit = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' ;
+        blank = ' ' , { ' ' } ;",
+    )
+    .unwrap();
+    let definitions = source.definitions;
+    let (lexicon, syntactic) = separate(&definitions, &source.lexical_declarations).unwrap();
+    assert_eq!(syntactic.len(), 4);
+    let names = lexicon
+        .get_kinds()
+        .iter()
+        .map(|k| k.name())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "name",
+            "number",
+            "blank",
+            "\"program\"",
+            "\";\"",
+            "\"end\"",
+            "\".\"",
+            "\":=\"",
+            "\"write\"",
+            "\"+\"",
+            "\"-\""
+        ]
+    );
+
+    // 予約語と同じ長さなら予約語, 長ければ名前
+    let lexemes = lexicon
+        .scan("program programs; x1 := x1 + 12; write x1; end.")
+        .unwrap();
+    let scanned = lexemes
+        .iter()
+        .map(|l| (lexicon.get_kinds()[l.kind].name(), l.text.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        scanned[..5],
+        [
+            ("\"program\"".to_string(), "program"),
+            ("name".to_string(), "programs"),
+            ("\";\"".to_string(), ";"),
+            ("name".to_string(), "x1"),
+            ("\":=\"".to_string(), ":="),
+        ]
+    );
+    assert_eq!(lexemes[1].position, 8);
+    assert!(lexicon.scan("program ?").is_err());
+
+    // 字句の列を構文解析すると表は文字単位より小さい
+    let start = GrammerIdentifier(0);
+    let mut scannerless = Ebnf2Gram::process(definitions.clone(), &[]).unwrap();
+    scannerless.simplify();
+    let mut tokenized = Ebnf2Gram::process(syntactic, &[]).unwrap();
+    tokenized.simplify();
+    let table = build_lalr_table(
+        tokenized.get_grammer_set(),
+        start,
+        tokenized.get_precedence_table(),
+    );
+    let terminals = lexemes.iter().map(Lexeme::terminal).collect::<Vec<_>>();
+    assert!(table.parse(&terminals).is_ok());
+    let error = table.parse(&terminals[1..]).unwrap_err().to_string();
+    assert!(error.contains('#'));
+    assert!(
+        table.automaton.states.len()
+            < build_lalr_table(
+                scannerless.get_grammer_set(),
+                start,
+                scannerless.get_precedence_table()
+            )
+            .automaton
+            .states
+            .len()
+    );
+
+    let read = |source: &str| {
+        let source = parse_str(source).unwrap();
+        separate(&source.definitions, &source.lexical_declarations).map(|_| ())
+    };
+    assert!(read("%token a ; s = a ; a = 'x' , [ a ] ;").is_err());
+    assert!(read("%token a ; s = a , b ; a = b , b ; b = 'x' ;").is_err());
+    assert!(read("%skip a ; s = a ; a = 'x' ;").is_err());
+    assert!(read("%token s ; s = 'x' ;").is_err());
+    assert!(read("%token a ; s = a , 'y' ; a = 'x' ;").is_ok());
+}
+
+#[test]
+fn adjacent_literal_test() {
+    use crate::testing::parse_str;
+
+    let source = parse_str(
+        "%token name ; %skip blank ;
+        call = 'go' , '(' , name ;
+        name = 'x' , { 'x' } ;
+        blank = ' ' , { ' ' } ;",
+    )
+    .unwrap();
+    let definitions = source.definitions;
+    let (lexicon, _) = separate(&definitions, &source.lexical_declarations).unwrap();
+    let kinds = lexicon
+        .scan("go ( xx")
+        .unwrap()
+        .iter()
+        .map(|l| lexicon.get_kinds()[l.kind].name())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["\"go\"", "\"(\"", "name"]);
+}
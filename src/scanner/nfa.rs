@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use super::Dfa;
+use crate::parser::Rule;
+
+#[derive(Debug, Default, Clone)]
+pub struct NfaState {
+    pub epsilon: Vec<usize>,
+    pub transitions: Vec<(char, usize)>,
+    // 受理する字句の種類
+    pub accept: Option<usize>,
+}
+
+// Thompson 構成の NFA. 状態 0 が開始状態
+#[derive(Debug, Default)]
+pub struct Nfa {
+    pub states: Vec<NfaState>,
+}
+
+impl Nfa {
+    // 字句の種類ごとの規則を開始状態からの ε 遷移でまとめる. rules は参照される字句規則
+    pub fn build(patterns: &[Rule], rules: &HashMap<String, Rule>) -> Result<Self> {
+        let mut nfa = Nfa::default();
+        let start = nfa.add_state();
+        for (kind, pattern) in patterns.iter().enumerate() {
+            let (entry, exit) = nfa.add_rule(pattern, rules)?;
+            nfa.states[start].epsilon.push(entry);
+            nfa.states[exit].accept = Some(kind);
+        }
+        Ok(nfa)
+    }
+
+    fn add_state(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    fn add_epsilon(&mut self, from: usize, to: usize) {
+        self.states[from].epsilon.push(to);
+    }
+
+    // rule を受理する断片の (入口, 出口)
+    fn add_rule(&mut self, rule: &Rule, rules: &HashMap<String, Rule>) -> Result<(usize, usize)> {
+        let entry = self.add_state();
+        let exit = match rule {
+            Rule::Character(c) => {
+                let exit = self.add_state();
+                self.states[entry].transitions.push((*c, exit));
+                exit
+            }
+            Rule::IdentifierRef(name) => {
+                let rule = rules
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown identifier {}", name))?;
+                let (inner_entry, inner_exit) = self.add_rule(rule, rules)?;
+                self.add_epsilon(entry, inner_entry);
+                inner_exit
+            }
+            Rule::Sequence(sequence) => {
+                let mut current = entry;
+                for rule in sequence {
+                    let (inner_entry, inner_exit) = self.add_rule(rule, rules)?;
+                    self.add_epsilon(current, inner_entry);
+                    current = inner_exit;
+                }
+                current
+            }
+            Rule::Or(alternatives) => {
+                let exit = self.add_state();
+                for rule in alternatives {
+                    let (inner_entry, inner_exit) = self.add_rule(rule, rules)?;
+                    self.add_epsilon(entry, inner_entry);
+                    self.add_epsilon(inner_exit, exit);
+                }
+                exit
+            }
+            Rule::Repeat(inner) | Rule::Option(inner) => {
+                let exit = self.add_state();
+                let (inner_entry, inner_exit) = self.add_rule(inner, rules)?;
+                self.add_epsilon(entry, inner_entry);
+                self.add_epsilon(entry, exit);
+                self.add_epsilon(inner_exit, exit);
+                if matches!(rule, Rule::Repeat(_)) {
+                    self.add_epsilon(inner_exit, inner_entry);
+                }
+                exit
+            }
+            Rule::Group(rule) => {
+                let (inner_entry, inner_exit) = self.add_rule(rule, rules)?;
+                self.add_epsilon(entry, inner_entry);
+                inner_exit
+            }
+            Rule::Action { .. } => {
+                let (inner_entry, inner_exit) = self.add_rule(rule.strip_action(), rules)?;
+                self.add_epsilon(entry, inner_entry);
+                inner_exit
+            }
+            // 字句は字句規則を分けた後の構文規則にしか現れない
+            Rule::Token(_) => return Err(anyhow!("Tokens cannot appear in lexical rules")),
+            // 差は NFA では作れないので, 両辺の DFA の差を状態ごと埋め込む
+            Rule::Exclude { from, target } => {
+                let from = Dfa::from_nfa(&Nfa::build(&[(**from).clone()], rules)?, |k| k);
+                let target = Dfa::from_nfa(&Nfa::build(&[(**target).clone()], rules)?, |k| k);
+                let difference = from.difference(&target);
+                let exit = self.add_state();
+                let offset = self.states.len();
+                for _ in 0..difference.transitions.len() {
+                    self.add_state();
+                }
+                for (state, transitions) in difference.transitions.iter().enumerate() {
+                    for (c, next) in transitions {
+                        self.states[offset + state]
+                            .transitions
+                            .push((*c, offset + next));
+                    }
+                    if difference.accept[state].is_some() {
+                        self.add_epsilon(offset + state, exit);
+                    }
+                }
+                self.add_epsilon(entry, offset);
+                exit
+            }
+        };
+        Ok((entry, exit))
+    }
+}
@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use super::{Action, LRTable};
+use crate::export::{nonterminal_name, nonterminal_names, production_label};
+use crate::grammer::{Grammer, GrammerIdentifier, Terminal};
+use crate::parser::SemanticAction;
+
+// 生成したモジュールの型と駆動部. 表は static 配列として前に置く
+const DRIVER: &str = r#"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+// rule は RULES の番号, production はその規則の中の生成規則の番号
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tree {
+    Node {
+        rule: usize,
+        production: usize,
+        children: Vec<Tree>,
+    },
+    Leaf(Lexeme),
+}
+
+impl Tree {
+    pub fn text(&self) -> String {
+        match self {
+            Tree::Node { children, .. } => children.iter().map(|c| c.text()).collect(),
+            Tree::Leaf(lexeme) => lexeme_text(lexeme),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub found: Option<Terminal>,
+    pub expected: Vec<Option<Terminal>>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |c: &Option<Terminal>| match c {
+            Some(c) => format!("{:?}", c),
+            None => "end of input".to_string(),
+        };
+        let expected = self.expected.iter().map(show).collect::<Vec<_>>();
+        write!(
+            f,
+            "{}: unexpected {}, expected one of {}",
+            self.position,
+            show(&self.found),
+            expected.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(input: &Input) -> Result<Tree, ParseError> {
+    let input = read(input);
+    let mut states = vec![0];
+    let mut trees = Vec::new();
+    let mut pos = 0;
+    loop {
+        let state = *states.last().unwrap();
+        let lookahead = input.get(pos).map(terminal);
+        let actions = ACTION[state];
+        let action = match actions.binary_search_by_key(&lookahead, |(c, _)| *c) {
+            Ok(i) => actions[i].1,
+            Err(_) => {
+                return Err(ParseError {
+                    position: pos,
+                    found: lookahead,
+                    expected: actions.iter().map(|(c, _)| *c).collect(),
+                })
+            }
+        };
+        match action {
+            Action::Shift(next) => {
+                states.push(next);
+                trees.push(Tree::Leaf(input[pos].clone()));
+                pos += 1;
+            }
+            Action::Reduce(index) => {
+                let (rule, production, len) = PRODUCTIONS[index];
+                states.truncate(states.len() - len);
+                let children = trees.split_off(trees.len() - len);
+                let top = *states.last().unwrap();
+                let gotos = GOTO[top];
+                let next = gotos[gotos.binary_search_by_key(&rule, |(r, _)| *r).unwrap()].1;
+                states.push(next);
+                trees.push(Tree::Node {
+                    rule,
+                    production,
+                    children,
+                });
+            }
+            Action::Accept => return Ok(trees.pop().unwrap()),
+        }
+    }
+}
+"#;
+
+// 動作の付いた生成規則の還元で値を計算する駆動部. Value は生成時に指定した型
+const SEMANTIC_DRIVER: &str = r#"
+pub trait Semantics {
+    // 読んだ字句 (文字の文法では文字) の値
+    fn token(&mut self, lexeme: Lexeme) -> Value;
+    // `@name` の動作
+    fn action(&mut self, name: &str, values: Vec<Value>) -> Value;
+    // 動作のない生成規則 (rule は RULES の番号)
+    fn default(&mut self, rule: usize, values: Vec<Value>) -> Value;
+}
+
+pub fn parse_with<S: Semantics>(input: &Input, semantics: &mut S) -> Result<Value, ParseError> {
+    let input = read(input);
+    let mut states = vec![0];
+    let mut values = Vec::new();
+    let mut pos = 0;
+    loop {
+        let state = *states.last().unwrap();
+        let lookahead = input.get(pos).map(terminal);
+        let actions = ACTION[state];
+        let action = match actions.binary_search_by_key(&lookahead, |(c, _)| *c) {
+            Ok(i) => actions[i].1,
+            Err(_) => {
+                return Err(ParseError {
+                    position: pos,
+                    found: lookahead,
+                    expected: actions.iter().map(|(c, _)| *c).collect(),
+                })
+            }
+        };
+        match action {
+            Action::Shift(next) => {
+                states.push(next);
+                values.push(semantics.token(input[pos].clone()));
+                pos += 1;
+            }
+            Action::Reduce(index) => {
+                let (rule, _, len) = PRODUCTIONS[index];
+                states.truncate(states.len() - len);
+                let children = values.split_off(values.len() - len);
+                let top = *states.last().unwrap();
+                let gotos = GOTO[top];
+                let next = gotos[gotos.binary_search_by_key(&rule, |(r, _)| *r).unwrap()].1;
+                states.push(next);
+                values.push(reduce(semantics, index, children));
+            }
+            Action::Accept => return Ok(values.pop().unwrap()),
+        }
+    }
+}
+"#;
+
+// 文字の文法は文字列を, 字句の文法は (字句の種類の番号, 字句の文字列) の列を読む.
+// Lexeme は構文木の葉と Semantics::token に渡す読んだ記号
+const CHARACTER_INPUT: &str = "pub type Terminal = char;
+pub type Input = str;
+pub type Lexeme = char;
+
+fn read(input: &str) -> Vec<char> {
+    input.chars().collect()
+}
+
+fn terminal(lexeme: &char) -> char {
+    *lexeme
+}
+
+fn lexeme_text(lexeme: &char) -> String {
+    lexeme.to_string()
+}
+";
+
+const TOKEN_INPUT: &str = "pub type Terminal = usize;
+pub type Input = [(usize, String)];
+pub type Lexeme = (usize, String);
+
+fn read(input: &[(usize, String)]) -> Vec<(usize, String)> {
+    input.to_vec()
+}
+
+fn terminal(lexeme: &(usize, String)) -> usize {
+    lexeme.0
+}
+
+fn lexeme_text(lexeme: &(usize, String)) -> String {
+    lexeme.1.clone()
+}
+";
+
+fn terminal_literal(lookahead: Option<Terminal>) -> String {
+    match lookahead {
+        Some(Terminal::Character(c)) => format!("Some({:?})", c),
+        Some(Terminal::Token(kind)) => format!("Some({})", kind),
+        None => "None".to_string(),
+    }
+}
+
+// `$1` などを還元する右辺の値を束縛した変数 `v1` に置き換える. 右辺の長さ len を超える番号と
+// `$0` はその番号を誤りとして返す
+fn substitute_values(code: &str, len: usize) -> std::result::Result<String, usize> {
+    let mut output = String::new();
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' || !chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            output.push(c);
+            continue;
+        }
+        let mut n = 0;
+        while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+            n = n * 10 + d as usize;
+            chars.next();
+        }
+        if n == 0 || n > len {
+            return Err(n);
+        }
+        output += &format!("v{}", n);
+    }
+    Ok(output)
+}
+
+impl LRTable {
+    // S' を除く非終端記号と, (非終端記号, 生成規則の番号) ごとの 0 からの通し番号
+    fn production_numbers(
+        &self,
+    ) -> (
+        Vec<GrammerIdentifier>,
+        HashMap<(GrammerIdentifier, usize), usize>,
+    ) {
+        let mut ids = self
+            .automaton
+            .set
+            .keys()
+            .copied()
+            .filter(|id| *id != self.automaton.start)
+            .collect::<Vec<_>>();
+        ids.sort();
+        let mut numbers = HashMap::new();
+        for id in &ids {
+            for i in 0..self.automaton.set[id].len() {
+                numbers.insert((*id, i), numbers.len());
+            }
+        }
+        (ids, numbers)
+    }
+
+    // to_rust に加え, 還元ごとに動作を呼んで値を計算する parse_with を書き出す.
+    // `{{ }}` の中では `$1` などで右辺の値 (所有権ごと) を, `semantics` で Semantics を参照できる.
+    // 右辺に無い `$n` はエラーで, tokens はその生成規則を書くのに使う
+    pub fn to_rust_with_actions(
+        &self,
+        identifier_map: &HashMap<String, GrammerIdentifier>,
+        tokens: &[String],
+        actions: &HashMap<(GrammerIdentifier, usize), SemanticAction>,
+        value_type: &str,
+    ) -> Result<String> {
+        let mut output = self.to_rust(identifier_map)?;
+        let names = nonterminal_names(identifier_map);
+        let (_, numbers) = self.production_numbers();
+        let mut arms = actions
+            .iter()
+            .map(|(production @ (id, i), action)| {
+                let len = self.automaton.set[id][*i]
+                    .iter()
+                    .filter(|g| **g != Grammer::Empty)
+                    .count();
+                (numbers[production], *production, len, action)
+            })
+            .collect::<Vec<_>>();
+        arms.sort_by_key(|(index, _, _, _)| *index);
+
+        output += &format!(
+            "
+pub type Value = {};
+",
+            value_type
+        );
+        output += SEMANTIC_DRIVER;
+        output += "
+#[allow(unused_mut, unused_variables, clippy::all)]
+";
+        output +=
+            "fn reduce<S: Semantics>(semantics: &mut S, index: usize, mut v: Vec<Value>) -> Value {
+";
+        output += "    match index {
+";
+        for (index, (id, production), len, action) in arms {
+            match action {
+                // 右辺の値は Copy とは限らないので, 添字で取り出さずに変数へ分解する
+                SemanticAction::Code(code) => {
+                    let code = substitute_values(code, len).map_err(|n| {
+                        anyhow!(
+                            "`${}` is out of range in `{}`: the production has {} values",
+                            n,
+                            production_label(&self.automaton.set, &names, tokens, id, production),
+                            len
+                        )
+                    })?;
+                    let values = (1..=len).map(|n| format!("v{}", n)).collect::<Vec<_>>();
+                    output += &format!(
+                        "        {} => {{
+            let Ok([{}]) = <[Value; {}]>::try_from(v) else {{ unreachable!() }};
+            {{ {} }}
+        }}
+",
+                        index,
+                        values.join(", "),
+                        len,
+                        code
+                    )
+                }
+                SemanticAction::Named(name) => {
+                    output += &format!(
+                        "        {} => semantics.action({:?}, v),
+",
+                        index, name
+                    )
+                }
+            }
+        }
+        output += "        _ => semantics.default(PRODUCTIONS[index].0, v),
+";
+        output += "    }
+}
+";
+        Ok(output)
+    }
+
+    // 実行時にこのツールに依存しない Rust のモジュールを書き出す. 衝突が残っていればエラー
+    pub fn to_rust(&self, identifier_map: &HashMap<String, GrammerIdentifier>) -> Result<String> {
+        if let Some((state, lookahead, actions)) = self.conflicts().first() {
+            return Err(anyhow!(
+                "Conflict in state {} with lookahead {:?}: {:?}",
+                state,
+                lookahead,
+                actions
+            ));
+        }
+        let terminals = self
+            .action
+            .keys()
+            .filter_map(|(_, lookahead)| *lookahead)
+            .collect::<Vec<_>>();
+        let input = if terminals
+            .iter()
+            .all(|t| matches!(t, Terminal::Character(_)))
+        {
+            CHARACTER_INPUT
+        } else if terminals.iter().all(|t| matches!(t, Terminal::Token(_))) {
+            TOKEN_INPUT
+        } else {
+            return Err(anyhow!(
+                "Characters and tokens cannot be mixed in a generated parser"
+            ));
+        };
+        let names = nonterminal_names(identifier_map);
+
+        // 非終端記号と生成規則に 0 からの番号を振る (S' は除く)
+        let (ids, production_index) = self.production_numbers();
+        let rule_index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+        let mut productions = Vec::new();
+        for id in &ids {
+            for (i, production) in self.automaton.set[id].iter().enumerate() {
+                let len = production.iter().filter(|g| **g != Grammer::Empty).count();
+                productions.push((rule_index[id], i, len));
+            }
+        }
+
+        let mut output = String::from("// Generated by rust-ebnf-cc. Do not edit.\n");
+        output += "#![allow(dead_code)]\n\n";
+        output += input;
+        output += "\n";
+        output += "pub static RULES: &[&str] = &[\n";
+        for id in &ids {
+            output += &format!("    {:?},\n", nonterminal_name(&names, *id));
+        }
+        output += "];\n\n";
+        output += "// (規則, 規則の中の番号, 右辺の長さ)\n";
+        output += "pub static PRODUCTIONS: &[(usize, usize, usize)] = &[\n";
+        for (rule, production, len) in &productions {
+            output += &format!("    ({}, {}, {}),\n", rule, production, len);
+        }
+        output += "];\n\n";
+
+        // 状態ごとに先読みで整列し, 駆動部で二分探索する
+        output += "pub static ACTION: &[&[(Option<Terminal>, Action)]] = &[\n";
+        for state in 0..self.automaton.states.len() {
+            let mut cells = self
+                .action
+                .iter()
+                .filter(|((s, _), _)| *s == state)
+                .map(|((_, lookahead), actions)| (*lookahead, actions[0]))
+                .collect::<Vec<_>>();
+            cells.sort();
+            let cells = cells
+                .into_iter()
+                .map(|(lookahead, action)| {
+                    let action = match action {
+                        Action::Shift(next) => format!("Action::Shift({})", next),
+                        Action::Reduce(id, production) => {
+                            format!("Action::Reduce({})", production_index[&(id, production)])
+                        }
+                        Action::Accept => "Action::Accept".to_string(),
+                    };
+                    format!("({}, {})", terminal_literal(lookahead), action)
+                })
+                .collect::<Vec<_>>();
+            output += &format!("    &[{}],\n", cells.join(", "));
+        }
+        output += "];\n\n";
+
+        output += "pub static GOTO: &[&[(usize, usize)]] = &[\n";
+        for state in 0..self.automaton.states.len() {
+            let mut cells = self
+                .goto
+                .iter()
+                .filter(|((s, id), _)| *s == state && *id != self.automaton.start)
+                .map(|((_, id), next)| (rule_index[id], *next))
+                .collect::<Vec<_>>();
+            cells.sort();
+            let cells = cells
+                .into_iter()
+                .map(|(rule, next)| format!("({}, {})", rule, next))
+                .collect::<Vec<_>>();
+            output += &format!("    &[{}],\n", cells.join(", "));
+        }
+        output += "];\n";
+        output += DRIVER;
+        Ok(output)
+    }
+}
+
+// 生成したモジュールを一時的なクレートでビルドし, 同じ入力の結果をこの中の構文解析と比べる
+#[test]
+fn generated_parser_test() {
+    use crate::scanner::Lexeme;
+    use crate::testing::process_tokenized;
+
+    // MPPL の文法. 字句解析器も生成して字句の列を構文解析器に渡す
+    let (lexicon, mut gramconv) = process_tokenized(include_str!("../../tests/mppl.ebnf"));
+    gramconv.simplify();
+    let table = super::build_lalr_table(
+        gramconv.get_grammer_set(),
+        GrammerIdentifier(0),
+        gramconv.get_precedence_table(),
+    );
+    assert!(table.conflicts().is_empty());
+    // ぶら下がり else は移動で解消する
+    assert!(table
+        .resolutions
+        .iter()
+        .all(|r| r.chosen.is_some_and(|a| matches!(a, Action::Shift(_)))));
+    let code = table.to_rust(gramconv.get_identifier_map()).unwrap();
+    let scanner_code = lexicon.to_rust();
+
+    let inputs = [
+        "program sample;
+var n, i : integer; a : array [10] of char;
+procedure show(x : integer);
+begin
+  if x > 0 then writeln('positive', x : 4) else writeln('it''s not')
+end;
+begin
+  { comment } n := 3; i := 0;
+  /* another * comment */
+  while i < n do begin call show(i - 1); i := i + 1 end;
+  if not (n = 3) then if true then break else return;
+  read(a[1])
+end.",
+        "program p; begin end.",
+        "program p; var b : boolean; begin b := integer(b) <= -1 or false end.",
+        "program p; begin x := 1 + end.",
+        "program p; var x : integer begin end.",
+    ];
+    let names = crate::export::nonterminal_names(gramconv.get_identifier_map());
+    let expected = inputs
+        .iter()
+        .map(|input| {
+            let lexemes = lexicon.scan(input).unwrap();
+            let terminals = lexemes.iter().map(Lexeme::terminal).collect::<Vec<_>>();
+            // 葉には字句の文字列も残る
+            let texts = lexemes.iter().map(|l| l.text.as_str()).collect::<Vec<_>>();
+            match table.parse(&terminals) {
+                Ok(tree) => format!("{}\n{}", tree.display(&names, &[]), texts.join(" ")),
+                Err(_) => "error".to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(expected.iter().filter(|s| *s == "error").count(), 2);
+    assert!(expected[0].contains("\nprogram sample ; var n , i : integer ;"));
+
+    let main = r##"mod parser;
+mod scanner;
+
+fn shape(tree: &parser::Tree) -> String {
+    match tree {
+        parser::Tree::Leaf((kind, _)) => format!("#{}", kind),
+        parser::Tree::Node { rule, children, .. } => {
+            let children = children.iter().map(shape).collect::<String>();
+            format!("{}({})", parser::RULES[*rule], children)
+        }
+    }
+}
+
+fn texts(tree: &parser::Tree, output: &mut Vec<String>) {
+    match tree {
+        parser::Tree::Leaf((_, text)) => output.push(text.clone()),
+        parser::Tree::Node { children, .. } => {
+            children.iter().for_each(|child| texts(child, output))
+        }
+    }
+}
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        let tokens = scanner::tokenize(&input).unwrap();
+        match parser::parse(&scanner::terminals(&tokens)) {
+            Ok(tree) => {
+                let mut output = Vec::new();
+                texts(&tree, &mut output);
+                println!("{}\n{}", shape(&tree), output.join(" "));
+            }
+            Err(_) => println!("error"),
+        }
+    }
+}
+"##;
+    let actual = crate::testing::run_generated_crate(
+        "lr",
+        &[
+            ("parser.rs", &code),
+            ("scanner.rs", &scanner_code),
+            ("main.rs", main),
+        ],
+        &inputs,
+    );
+    assert_eq!(
+        actual.lines().collect::<Vec<_>>(),
+        expected.join("\n").lines().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn semantic_action_codegen_test() {
+    use crate::testing::process_str;
+
+    assert_eq!(
+        substitute_values("$1 + $12 * $", 12),
+        Ok("v1 + v12 * $".to_string())
+    );
+    assert_eq!(substitute_values("$1 + $3", 2), Err(3));
+    assert_eq!(substitute_values("$0", 2), Err(0));
+
+    let mut gramconv = process_str(
+        "%left '+' '-' ; %left '*' ;
+         e = ( e , '+' , e ) {{ $1 + $3 }} | ( e , '-' , e ) {{ $1 - $3 }}
+           | ( e , '*' , e ) {{ $1 * $3 }} | ( '(' , e , ')' ) {{ $2 }} | d @digit ;
+         d = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' ;",
+    )
+    .unwrap();
+    gramconv.simplify();
+    let table = super::build_lalr_table(
+        gramconv.get_grammer_set(),
+        GrammerIdentifier(0),
+        gramconv.get_precedence_table(),
+    );
+    let code = table
+        .to_rust_with_actions(
+            gramconv.get_identifier_map(),
+            &[],
+            gramconv.get_actions(),
+            "i64",
+        )
+        .unwrap();
+
+    let main = r#"mod parser;
+
+struct Calculator {
+    digits: usize,
+}
+
+impl parser::Semantics for Calculator {
+    fn token(&mut self, c: char) -> i64 {
+        c.to_digit(10).map_or(0, i64::from)
+    }
+
+    fn action(&mut self, name: &str, values: Vec<i64>) -> i64 {
+        assert_eq!(name, "digit");
+        self.digits += 1;
+        values[0]
+    }
+
+    fn default(&mut self, _rule: usize, values: Vec<i64>) -> i64 {
+        values.first().copied().unwrap_or(0)
+    }
+}
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        let mut calculator = Calculator { digits: 0 };
+        match parser::parse_with(&input, &mut calculator) {
+            Ok(value) => println!("{} {}", value, calculator.digits),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+"#;
+    let actual = crate::testing::run_generated_crate(
+        "actions",
+        &[("parser.rs", &code), ("main.rs", main)],
+        &["1+2*3", "(1+2)*3", "9-4-3", "2*"],
+    );
+    assert_eq!(
+        actual.lines().collect::<Vec<_>>(),
+        [
+            "7 3",
+            "9 3",
+            "2 3",
+            "2: unexpected end of input, expected one of '(', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'",
+        ]
+    );
+}
+
+// 値が Copy でない型でも `$n` を使え, 繰り返しの中の動作は左から順に呼ばれる
+#[test]
+fn owned_value_action_test() {
+    use crate::testing::process_str;
+
+    let mut gramconv = process_str(
+        "s = p , { ',' , p @item } ;
+         p = ( '(' , s , ')' ) {{ $2 }} | ( d , d ) {{ $2 + &$1 }} | d ;
+         d = 'a' | 'b' | 'c' ;",
+    )
+    .unwrap();
+    gramconv.simplify();
+    let table = super::build_lalr_table(
+        gramconv.get_grammer_set(),
+        GrammerIdentifier(0),
+        gramconv.get_precedence_table(),
+    );
+    let code = table
+        .to_rust_with_actions(
+            gramconv.get_identifier_map(),
+            &[],
+            gramconv.get_actions(),
+            "String",
+        )
+        .unwrap();
+
+    let main = r#"mod parser;
+
+struct Items {
+    items: Vec<String>,
+}
+
+impl parser::Semantics for Items {
+    fn token(&mut self, c: char) -> String {
+        c.to_string()
+    }
+
+    fn action(&mut self, name: &str, mut values: Vec<String>) -> String {
+        assert_eq!(name, "item");
+        self.items.push(values.remove(1));
+        String::new()
+    }
+
+    fn default(&mut self, _rule: usize, values: Vec<String>) -> String {
+        values.concat()
+    }
+}
+
+fn main() {
+    for input in std::env::args().skip(1) {
+        let mut items = Items { items: Vec::new() };
+        let value = parser::parse_with(&input, &mut items).unwrap();
+        println!("{} {}", value, items.items.join(","));
+    }
+}
+"#;
+    let actual = crate::testing::run_generated_crate(
+        "owned_actions",
+        &[("parser.rs", &code), ("main.rs", main)],
+        &["ab,c,ba", "(ab,c),b"],
+    );
+    assert_eq!(actual.lines().collect::<Vec<_>>(), ["ba c,ab", "ba c,b"]);
+}
+
+// 右辺に無い `$n` は生成したクレートのコンパイルを待たずにエラーにする
+#[test]
+fn action_value_range_test() {
+    use crate::testing::process_str;
+
+    for (value, n) in [("$5", 5), ("$0", 0)] {
+        let mut gramconv = process_str(&format!(
+            "e = ( e , '+' , 'x' ) {{{{ {} }}}} | 'x' ;",
+            value
+        ))
+        .unwrap();
+        gramconv.simplify();
+        let table = super::build_lalr_table(
+            gramconv.get_grammer_set(),
+            GrammerIdentifier(0),
+            gramconv.get_precedence_table(),
+        );
+        let error = table
+            .to_rust_with_actions(
+                gramconv.get_identifier_map(),
+                &[],
+                gramconv.get_actions(),
+                "i64",
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with(&format!("`${}` is out of range in `", n)));
+        assert!(error.ends_with(" → e '+' 'x'`: the production has 3 values"));
+    }
+}
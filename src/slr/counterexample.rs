@@ -0,0 +1,617 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use super::{Action, LRTable};
+use crate::export::{nonterminal_name, nonterminal_names, terminal_name};
+use crate::grammer::{
+    sentence, Grammer, GrammerAnnotation, GrammerIdentifier, ParseTree, Terminal,
+};
+
+#[derive(Debug)]
+pub struct Derivation {
+    pub action: Action,
+    pub input: Vec<Terminal>,
+    // 衝突した時点の入力位置 (終端記号の数)
+    pub position: usize,
+    pub tree: ParseTree,
+}
+
+// unifying なら同じ入力の二つの導出, そうでなければ動作ごとの別々の入力の導出
+#[derive(Debug)]
+pub struct Counterexample {
+    pub state: usize,
+    pub lookahead: Option<Terminal>,
+    pub unifying: bool,
+    pub derivations: Vec<Derivation>,
+}
+
+// 状態の中の項 (状態, 非終端記号, 生成規則の番号, Dot の位置)
+type StateItem = (usize, GrammerIdentifier, usize, usize);
+
+// 項の後ろに続く必要のある先読み. None なら制約なし, Some(None) は入力の終端
+type Need = Option<Option<Terminal>>;
+
+// 部分木が導出する文字列への制約
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum Yield {
+    Any,
+    Empty,
+    Starts(Terminal),
+}
+
+// 制約ごとに (文字数, 節の数) が最小になる生成規則. Starts では先頭を導く記号の位置も持つ
+type Choices = HashMap<GrammerIdentifier, ((usize, usize), usize, usize)>;
+
+struct Search<'a> {
+    table: &'a LRTable,
+    annotation: GrammerAnnotation,
+    items: Vec<BTreeSet<(GrammerIdentifier, usize, usize)>>,
+    reverse: HashMap<usize, Vec<(usize, Grammer)>>,
+    choices: HashMap<Yield, Choices>,
+}
+
+impl<'a> Search<'a> {
+    fn new(table: &'a LRTable) -> Self {
+        let automaton = &table.automaton;
+        let items = automaton
+            .states
+            .iter()
+            .map(|state| {
+                let mut items = BTreeSet::new();
+                for (id, grammers) in state {
+                    for item in grammers {
+                        let dot = item.iter().position(|g| *g == Grammer::Dot).unwrap();
+                        items.insert((*id, automaton.production_index(*id, item), dot));
+                    }
+                }
+                items
+            })
+            .collect();
+        let mut reverse: HashMap<usize, Vec<(usize, Grammer)>> = HashMap::new();
+        for ((from, symbol), to) in &automaton.transitions {
+            reverse.entry(*to).or_default().push((*from, *symbol));
+        }
+        // 同じ長さの経路からは常に同じものを選ぶ
+        for predecessors in reverse.values_mut() {
+            predecessors.sort();
+        }
+        let mut search = Search {
+            table,
+            annotation: GrammerAnnotation::new(&automaton.set),
+            items,
+            reverse,
+            choices: HashMap::new(),
+        };
+        search.prepare(Yield::Any);
+        search
+    }
+
+    // 終端記号の列を導出できる記号だけなら true
+    fn is_productive(&self, symbols: &[Grammer]) -> bool {
+        symbols.iter().all(|symbol| match symbol {
+            Grammer::Grammer(id) => self.choices[&Yield::Any].contains_key(id),
+            _ => true,
+        })
+    }
+
+    fn symbols(&self, id: GrammerIdentifier, production: usize) -> Vec<Grammer> {
+        self.table.automaton.set[&id][production]
+            .iter()
+            .filter(|g| **g != Grammer::Empty)
+            .copied()
+            .collect()
+    }
+
+    // 衝突した項から開始状態の S' -> •S まで状態と項のグラフを逆にたどる.
+    // 還元では先読みを導く文脈を通る経路だけを選ぶ (Bison と同じ).
+    // along があれば, 開始状態から along の状態の列をたどる経路に限る
+    fn path(
+        &self,
+        target: StateItem,
+        need: Need,
+        along: Option<&[usize]>,
+    ) -> Option<Vec<StateItem>> {
+        type Node = (StateItem, Need, usize);
+        let automaton = &self.table.automaton;
+        let last = along.map_or(0, |states| states.len() - 1);
+        if along.is_some_and(|states| states[last] != target.0) {
+            return None;
+        }
+        let mut next: HashMap<Node, Node> = HashMap::new();
+        let mut queue = VecDeque::from([(target, need, last)]);
+        let mut visited = HashSet::from([(target, need, last)]);
+
+        while let Some(node @ ((state, id, production, dot), need, k)) = queue.pop_front() {
+            if state == 0
+                && id == automaton.start
+                && dot == 0
+                && k == 0
+                && need.is_none_or(|c| c.is_none())
+            {
+                let mut path = vec![node.0];
+                let mut current = node;
+                while let Some(following) = next.get(&current) {
+                    path.push(following.0);
+                    current = *following;
+                }
+                return Some(path);
+            }
+
+            let mut predecessors = Vec::new();
+            if dot > 0 {
+                let symbol = self.symbols(id, production)[dot - 1];
+                if !self.is_productive(&[symbol]) {
+                    continue;
+                }
+                let (k, expected) = match along {
+                    Some(_) if k == 0 => continue,
+                    Some(states) => (k - 1, Some(states[k - 1])),
+                    None => (0, None),
+                };
+                for (from, s) in self.reverse.get(&state).into_iter().flatten() {
+                    if *s == symbol
+                        && expected.is_none_or(|expected| expected == *from)
+                        && self.items[*from].contains(&(id, production, dot - 1))
+                    {
+                        predecessors.push(((*from, id, production, dot - 1), need, k));
+                    }
+                }
+            } else {
+                for (owner, owner_production, owner_dot) in &self.items[state] {
+                    let symbols = self.symbols(*owner, *owner_production);
+                    if symbols.get(*owner_dot) != Some(&Grammer::Grammer(id)) {
+                        continue;
+                    }
+                    let rest = &symbols[owner_dot + 1..];
+                    if !self.is_productive(rest) {
+                        continue;
+                    }
+                    let need = match need {
+                        Some(Some(c))
+                            if self
+                                .annotation
+                                .sequence_first_set(rest)
+                                .contains(&Grammer::from(c)) =>
+                        {
+                            None
+                        }
+                        Some(_) if !self.annotation.is_nullable(rest) => continue,
+                        need => need,
+                    };
+                    predecessors.push(((state, *owner, *owner_production, *owner_dot), need, k));
+                }
+            }
+            for predecessor in predecessors {
+                if visited.insert(predecessor) {
+                    next.insert(predecessor, node);
+                    queue.push_back(predecessor);
+                }
+            }
+        }
+        None
+    }
+
+    // 記号列が制約を満たす最小の費用と Starts の先頭の位置.
+    // choices は組み立て中の constraint の表
+    fn sequence_cost(
+        &self,
+        choices: &Choices,
+        symbols: &[Grammer],
+        constraint: Yield,
+    ) -> Option<((usize, usize), usize)> {
+        let cost = |symbol: &Grammer, inner: Yield| match (symbol, inner) {
+            (Grammer::Character(_) | Grammer::Token(_), Yield::Empty) => None,
+            (Grammer::Character(_) | Grammer::Token(_), Yield::Starts(c))
+                if symbol.terminal() != Some(c) =>
+            {
+                None
+            }
+            (Grammer::Character(_) | Grammer::Token(_), _) => Some((1, 1)),
+            (Grammer::Grammer(id), _) => {
+                let table = if inner == constraint {
+                    choices
+                } else {
+                    &self.choices[&inner]
+                };
+                table.get(id).map(|(cost, _, _)| *cost)
+            }
+            _ => None,
+        };
+        let add = |(a, b): (usize, usize), (c, d): (usize, usize)| (a + c, b + d);
+        match constraint {
+            Yield::Starts(c) => (0..symbols.len())
+                .filter_map(|i| {
+                    let mut total = cost(&symbols[i], Yield::Starts(c))?;
+                    for symbol in &symbols[..i] {
+                        total = add(total, cost(symbol, Yield::Empty)?);
+                    }
+                    for symbol in &symbols[i + 1..] {
+                        total = add(total, cost(symbol, Yield::Any)?);
+                    }
+                    Some((total, i))
+                })
+                .min(),
+            _ => symbols
+                .iter()
+                .try_fold((0, 0), |total, symbol| {
+                    Some(add(total, cost(symbol, constraint)?))
+                })
+                .map(|total| (total, 0)),
+        }
+    }
+
+    // 費用が真に減るときだけ更新するので, 選んだ生成規則をたどっても循環しない
+    fn prepare(&mut self, constraint: Yield) {
+        if self.choices.contains_key(&constraint) {
+            return;
+        }
+        if let Yield::Starts(_) = constraint {
+            self.prepare(Yield::Any);
+            self.prepare(Yield::Empty);
+        }
+        let mut choices = Choices::new();
+        loop {
+            let mut found = false;
+            let mut ids = self.table.automaton.set.keys().copied().collect::<Vec<_>>();
+            ids.sort();
+            for id in &ids {
+                for production in 0..self.table.automaton.set[id].len() {
+                    let symbols = self.symbols(*id, production);
+                    let Some(((length, size), split)) =
+                        self.sequence_cost(&choices, &symbols, constraint)
+                    else {
+                        continue;
+                    };
+                    let cost = (length, size + 1);
+                    if choices.get(id).is_none_or(|(best, _, _)| cost < *best) {
+                        choices.insert(*id, (cost, production, split));
+                        found = true;
+                    }
+                }
+            }
+            if !found {
+                break;
+            }
+        }
+        self.choices.insert(constraint, choices);
+    }
+
+    fn expand(&mut self, symbol: Grammer, constraint: Yield) -> ParseTree {
+        let id = match symbol {
+            Grammer::Character(_) | Grammer::Token(_) => {
+                return ParseTree::Leaf(symbol.terminal().unwrap())
+            }
+            Grammer::Grammer(id) => id,
+            _ => unreachable!(),
+        };
+        self.prepare(constraint);
+        let (_, production, split) = self.choices[&constraint][&id];
+        let children = self
+            .symbols(id, production)
+            .into_iter()
+            .enumerate()
+            .map(|(i, symbol)| {
+                let constraint = match constraint {
+                    Yield::Starts(_) if i < split => Yield::Empty,
+                    Yield::Starts(_) if i > split => Yield::Any,
+                    constraint => constraint,
+                };
+                self.expand(symbol, constraint)
+            })
+            .collect();
+        ParseTree::Node {
+            id,
+            production,
+            children,
+        }
+    }
+
+    // 経路に沿って導出木を組み立てる. 衝突の後は need を満たすように残りを埋める
+    fn derive(&mut self, path: &[StateItem], mut need: Need) -> (ParseTree, usize) {
+        let mut frames: Vec<(GrammerIdentifier, usize, Vec<ParseTree>)> = Vec::new();
+        for (_, id, production, dot) in path {
+            match frames.last_mut() {
+                Some((_, _, children)) if *dot > 0 => {
+                    let symbol = self.symbols(*id, *production)[dot - 1];
+                    children.push(self.expand(symbol, Yield::Any));
+                }
+                _ => frames.push((*id, *production, Vec::new())),
+            }
+        }
+        let position = frames
+            .iter()
+            .flat_map(|(_, _, children)| children)
+            .map(|tree| tree.leaves().len())
+            .sum();
+
+        let mut completed: Option<ParseTree> = None;
+        while let Some((id, production, mut children)) = frames.pop() {
+            children.extend(completed.take());
+            for symbol in self.symbols(id, production).split_off(children.len()) {
+                let constraint = match need {
+                    Some(Some(c))
+                        if self
+                            .annotation
+                            .sequence_first_set(&[symbol])
+                            .contains(&Grammer::from(c)) =>
+                    {
+                        need = None;
+                        Yield::Starts(c)
+                    }
+                    Some(_) => Yield::Empty,
+                    None => Yield::Any,
+                };
+                children.push(self.expand(symbol, constraint));
+            }
+            completed = Some(ParseTree::Node {
+                id,
+                production,
+                children,
+            });
+        }
+        // S' -> S の節は除く
+        let ParseTree::Node { mut children, .. } = completed.unwrap() else {
+            unreachable!()
+        };
+        (children.remove(0), position)
+    }
+
+    // 動作を行う項と, その後に続く必要のある先読み
+    fn targets(
+        &self,
+        state: usize,
+        lookahead: Option<Terminal>,
+        action: Action,
+    ) -> Vec<(StateItem, Need)> {
+        match action {
+            Action::Shift(_) => self.items[state]
+                .iter()
+                .filter(|(id, production, dot)| {
+                    self.symbols(*id, *production).get(*dot)
+                        == lookahead.map(Grammer::from).as_ref()
+                })
+                .map(|(id, production, dot)| ((state, *id, *production, *dot), None))
+                .collect(),
+            Action::Reduce(id, production) => {
+                let dot = self.symbols(id, production).len();
+                vec![((state, id, production, dot), Some(lookahead))]
+            }
+            Action::Accept => vec![((state, self.table.automaton.start, 0, 1), Some(None))],
+        }
+    }
+
+    // 最も短い経路. 先読みを導く文脈が無ければ (SLR の余分な先読みなど) 制約なしで探す
+    fn shortest(
+        &self,
+        targets: &[(StateItem, Need)],
+        along: Option<&[usize]>,
+    ) -> Option<(Vec<StateItem>, Need)> {
+        [true, false].into_iter().find_map(|constrained| {
+            targets
+                .iter()
+                .map(|(target, need)| (*target, if constrained { *need } else { None }))
+                .filter_map(|(target, need)| Some((self.path(target, need, along)?, need)))
+                .min_by_key(|(path, _)| path.len())
+        })
+    }
+
+    fn derivation(&mut self, action: Action, path: &[StateItem], need: Need) -> Derivation {
+        let (tree, position) = self.derive(path, need);
+        Derivation {
+            action,
+            input: tree.leaves(),
+            position,
+            tree,
+        }
+    }
+
+    // 動作ごとの導出. 一つの動作の最短の経路と同じ状態の列で他の動作にも至り,
+    // 入力と位置が一致すれば曖昧な文 (unifying). そうでなければ動作ごとの最短の経路
+    fn counterexample(
+        &mut self,
+        state: usize,
+        lookahead: Option<Terminal>,
+        actions: &[Action],
+    ) -> Counterexample {
+        let targets = actions
+            .iter()
+            .map(|action| self.targets(state, lookahead, *action))
+            .collect::<Vec<_>>();
+
+        for reference in &targets {
+            let Some((path, _)) = self.shortest(reference, None) else {
+                continue;
+            };
+            let along = std::iter::once(path[0].0)
+                .chain(
+                    path[1..]
+                        .iter()
+                        .filter(|item| item.3 > 0)
+                        .map(|item| item.0),
+                )
+                .collect::<Vec<_>>();
+            let Some(paths) = targets
+                .iter()
+                .map(|targets| self.shortest(targets, Some(&along)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let derivations = actions
+                .iter()
+                .zip(paths)
+                .map(|(action, (path, need))| self.derivation(*action, &path, need))
+                .collect::<Vec<_>>();
+            if derivations.windows(2).all(|pair| {
+                pair[0].input == pair[1].input
+                    && pair[0].position == pair[1].position
+                    && pair[0].tree != pair[1].tree
+            }) {
+                return Counterexample {
+                    state,
+                    lookahead,
+                    unifying: true,
+                    derivations,
+                };
+            }
+        }
+
+        let mut derivations = Vec::new();
+        for (action, targets) in actions.iter().zip(&targets) {
+            if let Some((path, need)) = self.shortest(targets, None) {
+                derivations.push(self.derivation(*action, &path, need));
+            }
+        }
+        Counterexample {
+            state,
+            lookahead,
+            unifying: false,
+            derivations,
+        }
+    }
+}
+
+impl LRTable {
+    // 衝突ごとに LR オートマトンの経路から反例を作る
+    pub fn find_counterexamples(&self) -> Vec<Counterexample> {
+        let mut search = Search::new(self);
+        self.conflicts()
+            .into_iter()
+            .map(|(state, lookahead, actions)| search.counterexample(state, lookahead, actions))
+            .collect()
+    }
+}
+
+fn display_tree(
+    tree: &ParseTree,
+    names: &HashMap<GrammerIdentifier, &str>,
+    tokens: &[String],
+) -> String {
+    match tree {
+        ParseTree::Leaf(terminal) => terminal_name(tokens, *terminal),
+        ParseTree::Node { id, children, .. } => {
+            let name = nonterminal_name(names, *id);
+            let children = children
+                .iter()
+                .map(|child| display_tree(child, names, tokens))
+                .collect::<Vec<_>>();
+            format!("{} → [ {} ]", name, children.join(" "))
+        }
+    }
+}
+
+// 入力の衝突位置に • を置き, 導出を EBNF の名前で表示する
+pub fn display_counterexample(
+    counterexample: &Counterexample,
+    identifier_map: &HashMap<String, GrammerIdentifier>,
+    tokens: &[String],
+) -> String {
+    let names = nonterminal_names(identifier_map);
+    let lookahead = match counterexample.lookahead {
+        Some(terminal) => terminal_name(tokens, terminal),
+        None => "end of input".to_string(),
+    };
+    let kind = if counterexample.unifying {
+        "unifying"
+    } else {
+        "non-unifying"
+    };
+
+    let mut output = format!(
+        "conflict in state {} on {} ({})\n",
+        counterexample.state, lookahead, kind
+    );
+    if counterexample.derivations.is_empty() {
+        output += "  no example found\n";
+    }
+    for derivation in &counterexample.derivations {
+        let (before, after) = derivation.input.split_at(derivation.position);
+        let (before, after) = (sentence(tokens, before), sentence(tokens, after));
+        let action = match derivation.action {
+            Action::Shift(_) => "shift",
+            Action::Reduce(..) => "reduce",
+            Action::Accept => "accept",
+        };
+        output += &format!("  {} example: {} • {}\n", action, before, after);
+        output += &format!("    {}\n", display_tree(&derivation.tree, &names, tokens));
+    }
+    output
+}
+
+#[test]
+fn unifying_counterexample_test() {
+    let gramconv = crate::testing::process_str("e = ( e , '+' , e ) | 'x' ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let e = GrammerIdentifier(0);
+    let table = super::build_slr_table(set, e, &super::PrecedenceTable::new());
+    let counterexamples = table.find_counterexamples();
+    assert_eq!(counterexamples.len(), table.conflicts().len());
+    let example = &counterexamples[0];
+    assert!(example.unifying);
+    assert_eq!(example.derivations.len(), 2);
+    assert_eq!(sentence(&[], &example.derivations[0].input), "x+x+x");
+    assert_eq!(example.derivations[0].position, 3);
+    assert_ne!(example.derivations[0].tree, example.derivations[1].tree);
+    assert!(
+        display_counterexample(example, &HashMap::from([("e".to_string(), e)]), &[])
+            .contains("example: x+x • +x")
+    );
+}
+
+#[test]
+fn non_unifying_counterexample_test() {
+    let gramconv = crate::testing::process_str(
+        "s = ( a , 'x' , 'y' ) | ( b , 'x' , 'z' ) ;
+         a = 'c' ;
+         b = 'c' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = GrammerIdentifier(0);
+    let table = super::build_slr_table(set, s, &super::PrecedenceTable::new());
+    let counterexamples = table.find_counterexamples();
+    assert_eq!(counterexamples.len(), 1);
+    let example = &counterexamples[0];
+    assert!(!example.unifying);
+    let inputs = example
+        .derivations
+        .iter()
+        .map(|d| (sentence(&[], &d.input), d.position))
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, vec![("cxy".to_string(), 1), ("cxz".to_string(), 1)]);
+}
+
+#[test]
+fn many_terminals_counterexample_test() {
+    // 文を列挙すると長さ 15 で 39^15 通りになる. 経路から作れば文の長さに依らない
+    let letters = ('a'..='z').map(|c| format!("'{}'", c)).collect::<Vec<_>>();
+    let digits = ('0'..='9').map(|c| format!("'{}'", c)).collect::<Vec<_>>();
+    let source = format!(
+        "s = ( 'I' , name , 'T' , s ) | ( 'I' , name , 'T' , s , 'E' , s ) | ( name , '=' , value ) ;
+         value = name | ( digit , {{ digit }} ) ;
+         name = letter , {{ letter | digit }} ;
+         letter = {} ;
+         digit = {} ;",
+        letters.join(" | "),
+        digits.join(" | ")
+    );
+    let gramconv = crate::testing::process_str(&source).unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = GrammerIdentifier(0);
+    let table = super::build_lalr_table(set, s, &super::PrecedenceTable::new());
+    let counterexamples = table.find_counterexamples();
+    assert_eq!(counterexamples.len(), table.conflicts().len());
+
+    let example = counterexamples
+        .iter()
+        .find(|c| c.lookahead == Some(Terminal::Character('E')))
+        .unwrap();
+    assert!(example.unifying);
+    for derivation in &example.derivations {
+        assert_eq!(sentence(&[], &derivation.input), "IaTIaTa=aEa=a");
+        assert_eq!(derivation.tree.leaves(), derivation.input);
+        assert_eq!(
+            derivation.input[derivation.position],
+            Terminal::Character('E')
+        );
+    }
+}
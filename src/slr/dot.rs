@@ -0,0 +1,112 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{Action, LRTable};
+use crate::export::{nonterminal_names, symbol_name, terminal_name};
+use crate::grammer::{Grammer, GrammerIdentifier, Terminal};
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl LRTable {
+    // Graphviz の DOT 形式. 状態には Dot 入りの項 (完了項には先読み) を書き, 衝突のある状態は赤くする
+    pub fn to_dot(
+        &self,
+        identifier_map: &HashMap<String, GrammerIdentifier>,
+        tokens: &[String],
+    ) -> String {
+        let mut names = nonterminal_names(identifier_map);
+        names.insert(self.automaton.start, "S'");
+        let symbol = |g: &Grammer| symbol_name(&names, tokens, g);
+
+        let conflicts = self
+            .conflicts()
+            .into_iter()
+            .map(|(state, _, _)| state)
+            .collect::<BTreeSet<_>>();
+        let mut lookaheads: HashMap<(usize, GrammerIdentifier, usize), BTreeSet<Option<Terminal>>> =
+            HashMap::new();
+        for ((state, lookahead), actions) in &self.action {
+            for action in actions {
+                if let Action::Reduce(id, production) = action {
+                    lookaheads
+                        .entry((*state, *id, *production))
+                        .or_default()
+                        .insert(*lookahead);
+                }
+            }
+        }
+
+        let mut output = String::from("digraph automaton {\n    rankdir=LR;\n");
+        output += "    node [shape=box, fontname=monospace];\n";
+        for (state, items) in self.automaton.states.iter().enumerate() {
+            let mut ids = items.keys().copied().collect::<Vec<_>>();
+            ids.sort();
+            let mut label = format!("{}\\l", state);
+            for id in ids {
+                for item in &items[&id] {
+                    let mut line = format!("{} →", symbol(&Grammer::Grammer(id)));
+                    for g in item {
+                        line += &format!(" {}", symbol(g));
+                    }
+                    if item.last() == Some(&Grammer::Dot) && id != self.automaton.start {
+                        let production = self.automaton.production_index(id, item);
+                        if let Some(set) = lookaheads.get(&(state, id, production)) {
+                            // 入力の終端 $ は最後に書く
+                            let mut lookaheads = set
+                                .iter()
+                                .flatten()
+                                .map(|terminal| terminal_name(tokens, *terminal))
+                                .collect::<Vec<_>>();
+                            if set.contains(&None) {
+                                lookaheads.push("$".to_string());
+                            }
+                            line += &format!(", [{}]", lookaheads.join(" "));
+                        }
+                    }
+                    label += &escape(&line);
+                    label += "\\l";
+                }
+            }
+            let style = if conflicts.contains(&state) {
+                ", color=red, style=filled, fillcolor=\"#ffdddd\""
+            } else {
+                ""
+            };
+            output += &format!("    s{} [label=\"{}\"{}];\n", state, label, style);
+        }
+
+        let mut edges = self.automaton.transitions.iter().collect::<Vec<_>>();
+        edges.sort();
+        for ((from, g), to) in edges {
+            output += &format!(
+                "    s{} -> s{} [label=\"{}\"];\n",
+                from,
+                to,
+                escape(&symbol(g))
+            );
+        }
+        output += "}\n";
+        output
+    }
+}
+
+#[test]
+fn dot_export_test() {
+    let gramconv = crate::testing::process_str("e = ( e , '+' , e ) | '\"' ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let e = GrammerIdentifier(0);
+    let table = super::build_lalr_table(set, e, &super::PrecedenceTable::new());
+    let dot = table.to_dot(&HashMap::from([("e".to_string(), e)]), &[]);
+
+    assert!(dot.starts_with("digraph automaton {"));
+    assert!(dot.trim_end().ends_with('}'));
+    assert_eq!(
+        dot.matches("[label=").count(),
+        table.automaton.states.len() + table.automaton.transitions.len()
+    );
+    assert_eq!(dot.matches("color=red").count(), 1);
+    assert!(dot.contains("S' → • e\\l"));
+    assert!(dot.contains("[label=\"'\\\"'\"]"));
+    assert!(dot.contains(", ['+' $]\\l"));
+}
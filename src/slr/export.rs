@@ -0,0 +1,109 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{Action, LRTable};
+use crate::export::{
+    lookahead_name, nonterminal_name, nonterminal_names, production_label, TableView,
+};
+use crate::grammer::{GrammerIdentifier, GrammerSet};
+
+// 表の欄に書く動作. 還元は生成規則を書く
+pub fn action_label(
+    set: &GrammerSet,
+    names: &HashMap<GrammerIdentifier, &str>,
+    tokens: &[String],
+    action: &Action,
+) -> String {
+    match action {
+        Action::Shift(state) => format!("s{}", state),
+        Action::Reduce(id, production) => {
+            format!(
+                "r {}",
+                production_label(set, names, tokens, *id, *production)
+            )
+        }
+        Action::Accept => "acc".to_string(),
+    }
+}
+
+impl LRTable {
+    // 行は状態, 列は ACTION の終端記号と $ の後に GOTO の非終端記号
+    pub fn to_view(
+        &self,
+        identifier_map: &HashMap<String, GrammerIdentifier>,
+        tokens: &[String],
+    ) -> TableView {
+        let mut names = nonterminal_names(identifier_map);
+        names.insert(self.automaton.start, "S'");
+        let mut terminals = self
+            .action
+            .keys()
+            .map(|(_, lookahead)| *lookahead)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        // 入力の終端 $ は ACTION の最後の列
+        terminals.sort_by_key(|lookahead| (lookahead.is_none(), *lookahead));
+        let nonterminals = self.goto.keys().map(|(_, id)| *id).collect::<BTreeSet<_>>();
+
+        let action = |action: &Action| action_label(&self.automaton.set, &names, tokens, action);
+        let rows = (0..self.automaton.states.len())
+            .map(|state| {
+                let mut cells = terminals
+                    .iter()
+                    .map(|lookahead| {
+                        self.action
+                            .get(&(state, *lookahead))
+                            .into_iter()
+                            .flatten()
+                            .map(action)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                for id in &nonterminals {
+                    cells.push(
+                        self.goto
+                            .get(&(state, *id))
+                            .map(|next| next.to_string())
+                            .into_iter()
+                            .collect(),
+                    );
+                }
+                (state.to_string(), cells)
+            })
+            .collect();
+
+        let columns = terminals
+            .into_iter()
+            .map(|lookahead| lookahead_name(tokens, lookahead))
+            .chain(nonterminals.iter().map(|id| nonterminal_name(&names, *id)))
+            .collect();
+        TableView {
+            corner: "state".to_string(),
+            columns,
+            rows,
+        }
+    }
+}
+
+#[test]
+fn lr_table_view_test() {
+    let gramconv = crate::testing::process_str("e = ( e , '+' , e ) | 'x' ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let e = GrammerIdentifier(0);
+    let table = super::build_slr_table(set, e, &super::PrecedenceTable::new());
+    let view = table.to_view(&HashMap::from([("e".to_string(), e)]), &[]);
+
+    assert_eq!(view.rows.len(), table.automaton.states.len());
+    assert_eq!(&view.columns[..3], ["'+'", "'x'", "$"]);
+    assert!(view.columns.contains(&"e".to_string()));
+    let conflicts = view
+        .rows
+        .iter()
+        .flat_map(|(_, cells)| cells)
+        .filter(|cell| cell.len() > 1)
+        .collect::<Vec<_>>();
+    assert_eq!(conflicts.len(), table.conflicts().len());
+    assert!(conflicts[0][0].starts_with('s'));
+    assert!(conflicts[0][1].starts_with("r "));
+    assert!(view.to_csv().contains("conflict: s"));
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::lr1::{closure, dot_position, Item, Mark};
+use super::{Action, LR0Automaton, LRTable};
+use crate::grammer::{
+    Grammer, GrammerAnnotation, GrammerIdentifier, GrammerSet, PrecedenceTable, Terminal,
+};
+
+fn kernel_items(automaton: &LR0Automaton, state: usize) -> Vec<Item> {
+    let mut kernel = Vec::new();
+    for (id, items) in &automaton.states[state] {
+        for item in items {
+            if dot_position(item) > 0 || *id == automaton.start {
+                kernel.push((*id, item.clone()));
+            }
+        }
+    }
+    kernel.sort();
+    kernel
+}
+
+// 核項の先読みを自発的な生成と伝播で求める
+pub fn build_lalr_table(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    precedence: &PrecedenceTable,
+) -> LRTable {
+    let automaton = LR0Automaton::build(set, start);
+    let annotation = GrammerAnnotation::new(&automaton.set);
+    let kernels = (0..automaton.states.len())
+        .map(|state| kernel_items(&automaton, state))
+        .collect::<Vec<_>>();
+
+    let mut lookaheads: HashMap<(usize, Item), BTreeSet<Option<Terminal>>> = HashMap::new();
+    let mut propagation: HashMap<(usize, Item), Vec<(usize, Item)>> = HashMap::new();
+    lookaheads.insert(
+        (
+            0,
+            (automaton.start, vec![Grammer::Dot, Grammer::Grammer(start)]),
+        ),
+        BTreeSet::from([None]),
+    );
+
+    for (state, kernel) in kernels.iter().enumerate() {
+        for item in kernel {
+            let closed = closure(
+                &automaton.set,
+                &annotation,
+                vec![(item.clone(), Mark::Propagate)],
+            );
+            for ((id, closed_item), mark) in closed {
+                let pos = dot_position(&closed_item);
+                let Some(symbol) = closed_item.get(pos + 1) else {
+                    continue;
+                };
+                let target = automaton.transitions[&(state, *symbol)];
+                let mut moved = closed_item.clone();
+                moved.swap(pos, pos + 1);
+                match mark {
+                    Mark::Token(lookahead) => {
+                        lookaheads
+                            .entry((target, (id, moved)))
+                            .or_default()
+                            .insert(lookahead);
+                    }
+                    Mark::Propagate => propagation
+                        .entry((state, item.clone()))
+                        .or_default()
+                        .push((target, (id, moved))),
+                }
+            }
+        }
+    }
+
+    loop {
+        let mut found = false;
+        for (from, targets) in &propagation {
+            let Some(source) = lookaheads.get(from).cloned() else {
+                continue;
+            };
+            for target in targets {
+                let target = lookaheads.entry(target.clone()).or_default();
+                let before = target.len();
+                target.extend(source.iter().copied());
+                found |= before != target.len();
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+
+    // 核項の先読みから閉包を取り, 完了項の先読みを集める
+    let mut reductions: HashMap<(usize, GrammerIdentifier, usize), BTreeSet<Option<Terminal>>> =
+        HashMap::new();
+    for (state, kernel) in kernels.iter().enumerate() {
+        let mut input = Vec::new();
+        for item in kernel {
+            for lookahead in lookaheads.get(&(state, item.clone())).into_iter().flatten() {
+                input.push((item.clone(), Mark::Token(*lookahead)));
+            }
+        }
+        for ((id, item), mark) in closure(&automaton.set, &annotation, input) {
+            if item.last() != Some(&Grammer::Dot) {
+                continue;
+            }
+            if let Mark::Token(lookahead) = mark {
+                let production = automaton.production_index(id, &item);
+                reductions
+                    .entry((state, id, production))
+                    .or_default()
+                    .insert(lookahead);
+            }
+        }
+    }
+
+    LRTable::from_automaton(automaton, precedence, |_, state, id, production| {
+        reductions
+            .get(&(state, id, production))
+            .cloned()
+            .unwrap_or_default()
+    })
+}
+
+// SLR では衝突していて LALR では解消された (状態, 先読み, SLR の動作)
+// 同じ LR(0) オートマトンから作るので状態番号は共通
+pub fn resolved_conflicts<'a>(
+    slr: &'a LRTable,
+    lalr: &LRTable,
+) -> Vec<(usize, Option<Terminal>, &'a [Action])> {
+    slr.conflicts()
+        .into_iter()
+        .filter(|(state, lookahead, _)| {
+            lalr.action
+                .get(&(*state, *lookahead))
+                .is_none_or(|actions| actions.len() <= 1)
+        })
+        .collect()
+}
+
+#[test]
+fn lalr_assignment_test() {
+    use super::build_slr_table;
+
+    // S -> L = R | R, L -> * R | i, R -> L は SLR では衝突する
+    let gramconv = crate::testing::process_str(
+        "s = ( l , '=' , r ) | r ;
+         l = ( '*' , r ) | 'i' ;
+         r = l ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = GrammerIdentifier(0);
+    let slr = build_slr_table(set, s, &PrecedenceTable::new());
+    let lalr = build_lalr_table(set, s, &PrecedenceTable::new());
+    assert!(!slr.conflicts().is_empty());
+    assert!(lalr.conflicts().is_empty());
+    assert_eq!(resolved_conflicts(&slr, &lalr).len(), slr.conflicts().len());
+    assert!(resolved_conflicts(&slr, &lalr)
+        .iter()
+        .all(|(_, lookahead, _)| *lookahead == Some('='.into())));
+
+    for input in ["i", "*i", "i=*i", "**i=i"] {
+        let tree = lalr.parse(&input.chars().collect::<Vec<_>>()).unwrap();
+        assert_eq!(tree.text(), input);
+    }
+    assert!(lalr.parse(&['i', '=']).is_err());
+}
+
+#[test]
+fn lalr_nullable_test() {
+    let gramconv = crate::testing::process_str("a = [ 'x' ] , { 'y' } , 'z' ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let start = GrammerIdentifier(0);
+    let lalr = build_lalr_table(set, start, &PrecedenceTable::new());
+    assert!(lalr.conflicts().is_empty());
+    for input in ["z", "xz", "yyz", "xyz"] {
+        let tree = lalr.parse(&input.chars().collect::<Vec<_>>()).unwrap();
+        assert_eq!(tree.text(), input);
+    }
+}
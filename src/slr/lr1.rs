@@ -0,0 +1,337 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use super::{augment, build_lalr_table, build_slr_table, LR0Automaton, LRTable};
+use crate::grammer::{
+    create_closure_set, create_initial_items, Grammer, GrammerAnnotation, GrammerIdentifier,
+    GrammerSet, PrecedenceTable, Terminal,
+};
+
+// 先読み. Propagate は LALR で伝播を調べるための仮の記号 (#)
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub(super) enum Mark {
+    Token(Option<Terminal>),
+    Propagate,
+}
+
+pub(super) type Item = (GrammerIdentifier, Vec<Grammer>);
+
+pub(super) fn dot_position(item: &[Grammer]) -> usize {
+    item.iter().position(|g| *g == Grammer::Dot).unwrap()
+}
+
+// LR(1) 項の閉包
+pub(super) fn closure(
+    set: &GrammerSet,
+    annotation: &GrammerAnnotation,
+    input: Vec<(Item, Mark)>,
+) -> BTreeSet<(Item, Mark)> {
+    let mut output = BTreeSet::new();
+    let mut queue = input;
+    while let Some(entry) = queue.pop() {
+        if !output.insert(entry.clone()) {
+            continue;
+        }
+        let ((_, item), mark) = entry;
+        let pos = dot_position(&item);
+        let Some(Grammer::Grammer(next)) = item.get(pos + 1) else {
+            continue;
+        };
+        let rest = &item[pos + 2..];
+        let mut marks = annotation
+            .sequence_first_set(rest)
+            .into_iter()
+            .filter_map(|g| g.terminal().map(|t| Mark::Token(Some(t))))
+            .collect::<Vec<_>>();
+        if annotation.is_nullable(rest) {
+            marks.push(mark);
+        }
+        for initial in create_initial_items(set, *next) {
+            for mark in &marks {
+                queue.push(((*next, initial.clone()), *mark));
+            }
+        }
+    }
+    output
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LR1Mode {
+    // 正準 LR(1). 核と先読みが一致する状態のみ同一視する
+    Canonical,
+    // Pager の弱両立性を満たす同じ核の状態を併合する (最小 LR(1))
+    Pager,
+}
+
+// 核項ごとの先読み
+type Kernel = BTreeMap<Item, BTreeSet<Option<Terminal>>>;
+
+fn same_core(left: &Kernel, right: &Kernel) -> bool {
+    left.keys().eq(right.keys())
+}
+
+fn kernel_input(kernel: &Kernel) -> Vec<(Item, Mark)> {
+    kernel
+        .iter()
+        .flat_map(|(item, lookaheads)| {
+            lookaheads
+                .iter()
+                .map(move |lookahead| (item.clone(), Mark::Token(*lookahead)))
+        })
+        .collect()
+}
+
+// 併合しても新しい還元/還元衝突が生じないための条件
+fn weakly_compatible(left: &Kernel, right: &Kernel) -> bool {
+    let left = left.values().collect::<Vec<_>>();
+    let right = right.values().collect::<Vec<_>>();
+    for i in 0..left.len() {
+        for j in i + 1..left.len() {
+            let crossed = !left[i].is_disjoint(right[j]) || !right[i].is_disjoint(left[j]);
+            let existing = !left[i].is_disjoint(left[j]) || !right[i].is_disjoint(right[j]);
+            if crossed && !existing {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub fn build_lr1_table(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    mode: LR1Mode,
+    precedence: &PrecedenceTable,
+) -> LRTable {
+    let (set, augmented) = augment(set, start);
+    let annotation = GrammerAnnotation::new(&set);
+
+    let initial = Kernel::from([(
+        (augmented, vec![Grammer::Dot, Grammer::Grammer(start)]),
+        BTreeSet::from([None]),
+    )]);
+    let mut kernels = vec![initial];
+    let mut transitions: HashMap<(usize, Grammer), usize> = HashMap::new();
+    let mut queue = VecDeque::from([0]);
+
+    while let Some(current) = queue.pop_front() {
+        let input = kernel_input(&kernels[current]);
+        let mut successors: BTreeMap<Grammer, Kernel> = BTreeMap::new();
+        for ((id, item), mark) in closure(&set, &annotation, input) {
+            let pos = dot_position(&item);
+            let (Some(symbol), Mark::Token(lookahead)) = (item.get(pos + 1), mark) else {
+                continue;
+            };
+            let mut moved = item.clone();
+            moved.swap(pos, pos + 1);
+            successors
+                .entry(*symbol)
+                .or_default()
+                .entry((id, moved))
+                .or_default()
+                .insert(lookahead);
+        }
+
+        for (symbol, kernel) in successors {
+            let found = kernels.iter().position(|k| match mode {
+                LR1Mode::Canonical => *k == kernel,
+                LR1Mode::Pager => same_core(k, &kernel) && weakly_compatible(k, &kernel),
+            });
+            let target = match found {
+                Some(target) => {
+                    let mut grown = false;
+                    for (item, lookaheads) in kernel {
+                        let merged = kernels[target].get_mut(&item).unwrap();
+                        let before = merged.len();
+                        merged.extend(lookaheads);
+                        grown |= before != merged.len();
+                    }
+                    // 先読みが増えたら後続の状態も作り直す
+                    if grown && !queue.contains(&target) {
+                        queue.push_back(target);
+                    }
+                    target
+                }
+                None => {
+                    kernels.push(kernel);
+                    queue.push_back(kernels.len() - 1);
+                    kernels.len() - 1
+                }
+            };
+            transitions.insert((current, symbol), target);
+        }
+    }
+
+    // 併合で辿れなくなった状態を除いて番号を振り直す
+    let mut order = vec![0];
+    let mut renumber = HashMap::from([(0, 0)]);
+    let mut k = 0;
+    while k < order.len() {
+        let mut targets = transitions
+            .iter()
+            .filter(|((from, _), _)| *from == order[k])
+            .map(|((_, symbol), target)| (*symbol, *target))
+            .collect::<Vec<_>>();
+        targets.sort();
+        for (_, target) in targets {
+            if let Entry::Vacant(entry) = renumber.entry(target) {
+                entry.insert(order.len());
+                order.push(target);
+            }
+        }
+        k += 1;
+    }
+    let transitions = transitions
+        .into_iter()
+        .filter(|((from, _), _)| renumber.contains_key(from))
+        .map(|((from, symbol), target)| ((renumber[&from], symbol), renumber[&target]))
+        .collect();
+
+    let mut states = Vec::new();
+    let mut reductions: HashMap<
+        (usize, GrammerIdentifier, Vec<Grammer>),
+        BTreeSet<Option<Terminal>>,
+    > = HashMap::new();
+    for (state, old) in order.iter().enumerate() {
+        let kernel = &kernels[*old];
+        let mut core = GrammerSet::new();
+        for (id, item) in kernel.keys() {
+            core.entry(*id).or_default().push(item.clone());
+        }
+        states.push(create_closure_set(&set, &core));
+
+        for ((id, item), mark) in closure(&set, &annotation, kernel_input(kernel)) {
+            if let (Some(Grammer::Dot), Mark::Token(lookahead)) = (item.last(), mark) {
+                reductions
+                    .entry((state, id, item))
+                    .or_default()
+                    .insert(lookahead);
+            }
+        }
+    }
+
+    let automaton = LR0Automaton {
+        set,
+        start: augmented,
+        states,
+        transitions,
+    };
+    let reductions = reductions
+        .into_iter()
+        .map(|((state, id, item), lookaheads)| {
+            (
+                (state, id, automaton.production_index(id, &item)),
+                lookaheads,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    LRTable::from_automaton(automaton, precedence, |_, state, id, production| {
+        reductions
+            .get(&(state, id, production))
+            .cloned()
+            .unwrap_or_default()
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TableReport {
+    pub name: &'static str,
+    pub states: usize,
+    pub conflicts: usize,
+}
+
+// LR(0) は先読みを見ずに還元する
+pub fn build_lr0_table(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    precedence: &PrecedenceTable,
+) -> LRTable {
+    let automaton = LR0Automaton::build(set, start);
+    let annotation = GrammerAnnotation::new(&automaton.set);
+    let mut lookaheads = annotation
+        .endchars
+        .iter()
+        .map(|c| Some(*c))
+        .collect::<BTreeSet<_>>();
+    lookaheads.insert(None);
+    LRTable::from_automaton(automaton, precedence, |_, _, _, _| lookaheads.clone())
+}
+
+pub fn compare_tables(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    precedence: &PrecedenceTable,
+) -> Vec<TableReport> {
+    let tables = [
+        ("LR(0)", build_lr0_table(set, start, precedence)),
+        ("SLR(1)", build_slr_table(set, start, precedence)),
+        ("LALR(1)", build_lalr_table(set, start, precedence)),
+        (
+            "LR(1)",
+            build_lr1_table(set, start, LR1Mode::Canonical, precedence),
+        ),
+        (
+            "Pager",
+            build_lr1_table(set, start, LR1Mode::Pager, precedence),
+        ),
+    ];
+    tables
+        .into_iter()
+        .map(|(name, table)| TableReport {
+            name,
+            states: table.automaton.states.len(),
+            conflicts: table.conflicts().len(),
+        })
+        .collect()
+}
+
+#[test]
+fn lr1_reduce_reduce_test() {
+    // LALR で併合すると x と y の還元が衝突する
+    let gramconv = crate::testing::process_str(
+        "s = ( 'a' , x , 'd' ) | ( 'b' , y , 'd' ) | ( 'a' , y , 'e' ) | ( 'b' , x , 'e' ) ;
+         x = 'c' ;
+         y = 'c' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let s = GrammerIdentifier(0);
+    let report = compare_tables(set, s, &PrecedenceTable::new());
+    let get = |name| report.iter().find(|r| r.name == name).unwrap();
+    assert!(get("LALR(1)").conflicts > 0);
+    assert_eq!(get("LR(1)").conflicts, 0);
+    assert_eq!(get("Pager").conflicts, 0);
+    assert_eq!(get("LALR(1)").states, get("LR(0)").states);
+    assert!(get("Pager").states > get("LALR(1)").states);
+    assert!(get("Pager").states <= get("LR(1)").states);
+
+    let table = build_lr1_table(set, s, LR1Mode::Pager, &PrecedenceTable::new());
+    for input in ["acd", "bcd", "ace", "bce"] {
+        let tree = table.parse(&input.chars().collect::<Vec<_>>()).unwrap();
+        assert_eq!(tree.text(), input);
+    }
+    assert!(table.parse(&['a', 'c']).is_err());
+}
+
+#[test]
+fn lr1_expression_test() {
+    let gramconv = crate::testing::process_str(
+        "expr = ( expr , '+' , term ) | term ;
+         term = ( term , '*' , factor ) | factor ;
+         factor = ( '(' , expr , ')' ) | 'x' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let expr = GrammerIdentifier(0);
+    let report = compare_tables(set, expr, &PrecedenceTable::new());
+    let get = |name| report.iter().find(|r| r.name == name).unwrap();
+    assert!(get("LR(0)").conflicts > 0);
+    assert_eq!(get("SLR(1)").conflicts, 0);
+    assert!(get("LR(1)").states > get("LALR(1)").states);
+    assert_eq!(get("Pager").states, get("LALR(1)").states);
+    assert_eq!(get("Pager").conflicts, 0);
+
+    let table = build_lr1_table(set, expr, LR1Mode::Canonical, &PrecedenceTable::new());
+    let tree = table.parse(&"(x+x)*x".chars().collect::<Vec<_>>()).unwrap();
+    assert_eq!(tree.text(), "(x+x)*x");
+}
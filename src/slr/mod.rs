@@ -1 +1,339 @@
-
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+mod codegen;
+mod counterexample;
+mod dot;
+mod export;
+mod lalr;
+mod lr1;
+mod precedence;
+pub use counterexample::display_counterexample;
+pub use export::action_label;
+pub use lalr::{build_lalr_table, resolved_conflicts};
+pub use lr1::{build_lr0_table, compare_tables};
+pub use precedence::Resolution;
+
+use crate::cache::{sorted_map, sorted_sets};
+use crate::grammer::{
+    create_closure_set, create_end_follow_set, create_goto_set, create_initial_items, Grammer,
+    GrammerAnnotation, GrammerIdentifier, GrammerSet, ParseTree, PrecedenceTable, Terminal,
+};
+
+// 状態は Dot 入りの項の集合 (LR(1) では同じ項の集合の状態が複数ありうる)
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LR0Automaton {
+    // S' -> S を加えた文法
+    #[serde(with = "sorted_map")]
+    pub set: GrammerSet,
+    pub start: GrammerIdentifier,
+    #[serde(with = "sorted_sets")]
+    pub states: Vec<GrammerSet>,
+    #[serde(with = "sorted_map")]
+    pub transitions: HashMap<(usize, Grammer), usize>,
+}
+
+// S' -> S を加えた文法と S'
+fn augment(set: &GrammerSet, start: GrammerIdentifier) -> (GrammerSet, GrammerIdentifier) {
+    let mut set = set.clone();
+    let augmented = GrammerIdentifier(set.keys().map(|id| id.0 + 1).max().unwrap_or(0));
+    set.insert(augmented, vec![vec![Grammer::Grammer(start)]]);
+    (set, augmented)
+}
+
+type StateKey = Vec<(GrammerIdentifier, Vec<Vec<Grammer>>)>;
+
+fn state_key(state: &GrammerSet) -> StateKey {
+    let mut key = state
+        .iter()
+        .map(|(id, items)| (*id, items.clone()))
+        .collect::<Vec<_>>();
+    key.sort();
+    key
+}
+
+impl LR0Automaton {
+    pub fn build(set: &GrammerSet, start: GrammerIdentifier) -> Self {
+        let (set, augmented) = augment(set, start);
+        let initial = GrammerSet::from([(augmented, create_initial_items(&set, augmented))]);
+        let mut states = vec![create_closure_set(&set, &initial)];
+        let mut keys = HashMap::from([(state_key(&states[0]), 0)]);
+        let mut transitions = HashMap::new();
+
+        let mut current = 0;
+        while current < states.len() {
+            let symbols = next_symbols(&states[current]);
+            for symbol in symbols {
+                let next = create_goto_set(&set, &states[current], &symbol);
+                let key = state_key(&next);
+                let target = match keys.get(&key) {
+                    Some(target) => *target,
+                    None => {
+                        states.push(next);
+                        keys.insert(key, states.len() - 1);
+                        states.len() - 1
+                    }
+                };
+                transitions.insert((current, symbol), target);
+            }
+            current += 1;
+        }
+
+        LR0Automaton {
+            set,
+            start: augmented,
+            states,
+            transitions,
+        }
+    }
+
+    // 完了項 (Dot が末尾) の (非終端記号, 生成規則の番号)
+    pub fn reductions(&self, state: usize) -> Vec<(GrammerIdentifier, usize)> {
+        let mut reductions = Vec::new();
+        for (id, items) in &self.states[state] {
+            for item in items {
+                if item.last() == Some(&Grammer::Dot) {
+                    reductions.push((*id, self.production_index(*id, item)));
+                }
+            }
+        }
+        reductions.sort();
+        reductions
+    }
+
+    pub fn production_index(&self, id: GrammerIdentifier, item: &[Grammer]) -> usize {
+        let mut grammer = item
+            .iter()
+            .filter(|g| **g != Grammer::Dot)
+            .copied()
+            .collect::<Vec<_>>();
+        if grammer.is_empty() {
+            grammer.push(Grammer::Empty);
+        }
+        self.set[&id]
+            .iter()
+            .position(|g| {
+                g.iter()
+                    .filter(|g| **g != Grammer::Empty)
+                    .eq(grammer.iter().filter(|g| **g != Grammer::Empty))
+            })
+            .unwrap()
+    }
+}
+
+fn next_symbols(state: &GrammerSet) -> BTreeSet<Grammer> {
+    let mut symbols = BTreeSet::new();
+    for items in state.values() {
+        for item in items {
+            if let Some(pos) = item.iter().position(|g| *g == Grammer::Dot) {
+                if let Some(next) = item.get(pos + 1) {
+                    symbols.insert(*next);
+                }
+            }
+        }
+    }
+    symbols
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    Shift(usize),
+    Reduce(GrammerIdentifier, usize),
+    Accept,
+}
+
+// 先読み None は入力の終端
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LRTable {
+    pub automaton: LR0Automaton,
+    #[serde(with = "sorted_map")]
+    pub action: HashMap<(usize, Option<Terminal>), Vec<Action>>,
+    #[serde(with = "sorted_map")]
+    pub goto: HashMap<(usize, GrammerIdentifier), usize>,
+    // 優先順位の宣言で解消した衝突
+    pub resolutions: Vec<Resolution>,
+}
+
+impl LRTable {
+    // 還元の先読みを与えて表を作り, 衝突を優先順位で解消する
+    pub fn from_automaton<F>(
+        automaton: LR0Automaton,
+        precedence: &PrecedenceTable,
+        mut lookaheads: F,
+    ) -> Self
+    where
+        F: FnMut(&LR0Automaton, usize, GrammerIdentifier, usize) -> BTreeSet<Option<Terminal>>,
+    {
+        let mut action: HashMap<_, Vec<Action>> = HashMap::new();
+        let mut goto = HashMap::new();
+
+        for ((state, symbol), target) in &automaton.transitions {
+            if let Some(terminal) = symbol.terminal() {
+                action
+                    .entry((*state, Some(terminal)))
+                    .or_default()
+                    .push(Action::Shift(*target));
+            } else if let Grammer::Grammer(id) = symbol {
+                goto.insert((*state, *id), *target);
+            }
+        }
+
+        for state in 0..automaton.states.len() {
+            for (id, production) in automaton.reductions(state) {
+                if id == automaton.start {
+                    action
+                        .entry((state, None))
+                        .or_default()
+                        .push(Action::Accept);
+                    continue;
+                }
+                for lookahead in lookaheads(&automaton, state, id, production) {
+                    action
+                        .entry((state, lookahead))
+                        .or_default()
+                        .push(Action::Reduce(id, production));
+                }
+            }
+        }
+        for actions in action.values_mut() {
+            actions.sort();
+            actions.dedup();
+        }
+
+        let mut table = LRTable {
+            automaton,
+            action,
+            goto,
+            resolutions: Vec::new(),
+        };
+        table.resolve_precedence(precedence);
+        table
+    }
+
+    pub fn conflicts(&self) -> Vec<(usize, Option<Terminal>, &[Action])> {
+        let mut conflicts = self
+            .action
+            .iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|((state, lookahead), actions)| (*state, *lookahead, actions.as_slice()))
+            .collect::<Vec<_>>();
+        conflicts.sort_by_key(|(state, lookahead, _)| (*state, *lookahead));
+        conflicts
+    }
+
+    // 文字の列も字句の種類の列も受け取る
+    pub fn parse<T: Copy + Into<Terminal>>(&self, input: &[T]) -> Result<ParseTree> {
+        let mut stack: Vec<(usize, Option<ParseTree>)> = vec![(0, None)];
+        let mut pos = 0;
+        loop {
+            let state = stack.last().unwrap().0;
+            let lookahead = input.get(pos).map(|t| (*t).into());
+            let action = match self.action.get(&(state, lookahead)).map(|a| a.as_slice()) {
+                Some([action]) => *action,
+                Some(_) => {
+                    return Err(anyhow!(
+                        "{} Conflict in state {} with lookahead {:?}",
+                        pos,
+                        state,
+                        lookahead
+                    ))
+                }
+                None => return Err(anyhow!("{} Unexpected {:?}", pos, lookahead)),
+            };
+
+            match action {
+                Action::Shift(next) => {
+                    stack.push((next, Some(ParseTree::Leaf(input[pos].into()))));
+                    pos += 1;
+                }
+                Action::Reduce(id, production) => {
+                    let len = self.automaton.set[&id][production]
+                        .iter()
+                        .filter(|g| **g != Grammer::Empty)
+                        .count();
+                    let children = stack
+                        .split_off(stack.len() - len)
+                        .into_iter()
+                        .map(|(_, tree)| tree.unwrap())
+                        .collect();
+                    let top = stack.last().unwrap().0;
+                    let next = *self.goto.get(&(top, id)).ok_or(anyhow!(
+                        "No goto from state {} on <{}>",
+                        top,
+                        id.0
+                    ))?;
+                    stack.push((
+                        next,
+                        Some(ParseTree::Node {
+                            id,
+                            production,
+                            children,
+                        }),
+                    ));
+                }
+                Action::Accept => return Ok(stack.pop().unwrap().1.unwrap()),
+            }
+        }
+    }
+}
+
+// 還元の先読みは FOLLOW 集合
+pub fn build_slr_table(
+    set: &GrammerSet,
+    start: GrammerIdentifier,
+    precedence: &PrecedenceTable,
+) -> LRTable {
+    let automaton = LR0Automaton::build(set, start);
+    let annotation = GrammerAnnotation::new(&automaton.set);
+    let end_follow = create_end_follow_set(&automaton.set, &annotation.nullable, automaton.start);
+
+    LRTable::from_automaton(automaton, precedence, |_, _, id, _| {
+        let mut lookaheads = annotation
+            .follow_set
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(|g| g.terminal().map(Some))
+            .collect::<BTreeSet<_>>();
+        if end_follow.contains(&id) {
+            lookaheads.insert(None);
+        }
+        lookaheads
+    })
+}
+
+#[test]
+fn slr_expression_test() {
+    let gramconv = crate::testing::process_str(
+        "expr = ( expr , '+' , term ) | term ;
+         term = ( term , '*' , factor ) | factor ;
+         factor = ( '(' , expr , ')' ) | 'x' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let expr = GrammerIdentifier(0);
+    let table = build_slr_table(set, expr, &PrecedenceTable::new());
+    assert!(table.conflicts().is_empty());
+
+    let tree = table
+        .parse(&"x+x*(x+x)".chars().collect::<Vec<_>>())
+        .unwrap();
+    assert_eq!(tree.text(), "x+x*(x+x)");
+    assert!(matches!(tree, ParseTree::Node { id, .. } if id == expr));
+    assert!(table.parse(&['x', '+']).is_err());
+}
+
+#[test]
+fn slr_nullable_test() {
+    let gramconv = crate::testing::process_str("a = [ 'x' ] , { 'y' } , 'z' ;").unwrap();
+    let set = gramconv.get_grammer_set();
+    let start = GrammerIdentifier(0);
+    let table = build_slr_table(set, start, &PrecedenceTable::new());
+    assert!(table.conflicts().is_empty());
+    for input in ["z", "xz", "yyz", "xyz"] {
+        let tree = table.parse(&input.chars().collect::<Vec<_>>()).unwrap();
+        assert_eq!(tree.text(), input);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Action, LRTable};
+use crate::export::{production_label, terminal_name};
+use crate::grammer::{GrammerIdentifier, GrammerSet, PrecedenceTable, Terminal};
+use crate::parser::Associativity;
+
+// 優先順位による移動/還元衝突の解消結果. chosen が None なら構文誤り (%nonassoc)
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Resolution {
+    pub state: usize,
+    pub lookahead: Terminal,
+    pub production: (GrammerIdentifier, usize),
+    pub chosen: Option<Action>,
+}
+
+impl Resolution {
+    // 生成規則は EBNF の名前で, 字句は tokens の名前で書く
+    pub fn display(
+        &self,
+        set: &GrammerSet,
+        names: &HashMap<GrammerIdentifier, &str>,
+        tokens: &[String],
+    ) -> String {
+        let (id, production) = self.production;
+        let chosen = match self.chosen {
+            Some(Action::Shift(_)) => "shift",
+            Some(Action::Reduce(..)) => "reduce",
+            Some(Action::Accept) => "accept",
+            None => "error",
+        };
+        format!(
+            "state {}: {} vs {}: {}",
+            self.state,
+            terminal_name(tokens, self.lookahead),
+            production_label(set, names, tokens, id, production),
+            chosen
+        )
+    }
+}
+
+impl LRTable {
+    // 生成規則の優先順位は右端の宣言済み終端記号のもの (yacc と同じ)
+    fn production_precedence(
+        &self,
+        table: &PrecedenceTable,
+        id: GrammerIdentifier,
+        production: usize,
+    ) -> Option<(usize, Associativity)> {
+        self.automaton.set[&id][production]
+            .iter()
+            .rev()
+            .find_map(|g| table.get(&g.terminal()?).copied())
+    }
+
+    // 移動と還元が一つずつの衝突を yacc と同じ規則で解消する
+    pub(super) fn resolve_precedence(&mut self, table: &PrecedenceTable) {
+        let mut resolutions = Vec::new();
+        let mut cells = self
+            .action
+            .keys()
+            .filter_map(|(state, lookahead)| lookahead.map(|c| (*state, c)))
+            .collect::<Vec<_>>();
+        cells.sort();
+
+        for (state, c) in cells {
+            let actions = &self.action[&(state, Some(c))];
+            let [shift @ Action::Shift(_), reduce @ Action::Reduce(id, production)] = actions[..]
+            else {
+                continue;
+            };
+            let (Some((token_level, _)), Some((rule_level, associativity))) = (
+                table.get(&c).copied(),
+                self.production_precedence(table, id, production),
+            ) else {
+                continue;
+            };
+            let chosen = match rule_level.cmp(&token_level) {
+                std::cmp::Ordering::Greater => Some(reduce),
+                std::cmp::Ordering::Less => Some(shift),
+                std::cmp::Ordering::Equal => match associativity {
+                    Associativity::Left => Some(reduce),
+                    Associativity::Right => Some(shift),
+                    Associativity::Nonassoc => None,
+                },
+            };
+            match chosen {
+                Some(action) => {
+                    self.action.insert((state, Some(c)), vec![action]);
+                }
+                None => {
+                    self.action.remove(&(state, Some(c)));
+                }
+            }
+            resolutions.push(Resolution {
+                state,
+                lookahead: c,
+                production: (id, production),
+                chosen,
+            });
+        }
+        self.resolutions = resolutions;
+    }
+}
+
+#[test]
+fn precedence_resolution_test() {
+    use super::{build_lalr_table, build_slr_table};
+    use crate::grammer::ParseTree;
+    use crate::testing::process_str;
+
+    let gramconv = process_str(
+        "%left '+' '-' ; %left '*' ; %right '^' ; %nonassoc '<' ;
+         e = ( e , '+' , e ) | ( e , '-' , e ) | ( e , '*' , e ) | ( e , '^' , e )
+           | ( e , '<' , e ) | 'x' ;",
+    )
+    .unwrap();
+    let set = gramconv.get_grammer_set();
+    let e = gramconv.get_identifier_map()["e"];
+    let precedence = gramconv.get_precedence_table();
+
+    // 宣言がなければ衝突が残る
+    let empty = PrecedenceTable::new();
+    assert!(!build_slr_table(set, e, &empty).conflicts().is_empty());
+    assert!(build_lalr_table(set, e, &empty).resolutions.is_empty());
+
+    for table in [
+        build_slr_table(set, e, precedence),
+        build_lalr_table(set, e, precedence),
+    ] {
+        assert!(!table.resolutions.is_empty());
+        assert!(table.conflicts().is_empty());
+        let names = crate::export::nonterminal_names(gramconv.get_identifier_map());
+        assert!(table
+            .resolutions
+            .iter()
+            .any(|r| r.display(set, &names, &[]).ends_with(" → e '+' e: reduce")));
+
+        // 括弧の選択肢は単位規則を挟むので, 子が三つの節まで降りて左の子が葉なら右結合
+        let leftmost_is_leaf = |input: &str| {
+            let mut tree = table.parse(&input.chars().collect::<Vec<_>>()).unwrap();
+            assert_eq!(tree.text(), input);
+            while let ParseTree::Node { mut children, .. } = tree {
+                if children.len() == 3 {
+                    return children[0].text() == "x";
+                }
+                tree = children.remove(0);
+            }
+            unreachable!()
+        };
+        assert!(!leftmost_is_leaf("x-x-x"));
+        assert!(leftmost_is_leaf("x^x^x"));
+        assert!(leftmost_is_leaf("x+x*x"));
+        assert!(!leftmost_is_leaf("x*x+x"));
+        assert!(table.parse(&"x<x<x".chars().collect::<Vec<_>>()).is_err());
+        assert!(table.parse(&"x<x".chars().collect::<Vec<_>>()).is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+
+use crate::grammer::Ebnf2Gram;
+use crate::lexer::Tokenizer;
+use crate::parser::{Definition, LexicalDeclaration, Parser, PrecedenceDeclaration};
+use crate::scanner::{separate, Lexicon};
+
+// EBNF の文字列から読んだ定義と宣言
+pub struct Source {
+    #[allow(clippy::vec_box)]
+    pub definitions: Vec<Box<Definition>>,
+    pub precedences: Vec<PrecedenceDeclaration>,
+    pub lexical_declarations: Vec<LexicalDeclaration>,
+}
+
+// 字句に分けられなければ panic し, 構文の誤りは Err で返す
+pub fn parse_str(input: &str) -> Result<Source> {
+    let input = input.chars().collect::<Vec<_>>();
+    let tokens = Tokenizer::new(&input).read_definition().unwrap();
+    let mut parser = Parser::new(&tokens);
+    let definitions = parser.eat()?;
+    Ok(Source {
+        definitions,
+        precedences: parser.get_precedences().to_vec(),
+        lexical_declarations: parser.get_lexical_declarations().to_vec(),
+    })
+}
+
+// 宣言した優先順位とともに単純化していない文法に変換する
+pub fn process_str(input: &str) -> Result<Ebnf2Gram> {
+    let source = parse_str(input)?;
+    Ebnf2Gram::process(source.definitions, &source.precedences)
+}
+
+// %token の字句規則を分けてから構文規則を変換する
+pub fn process_tokenized(input: &str) -> (Lexicon, Ebnf2Gram) {
+    let source = parse_str(input).unwrap();
+    let (lexicon, syntactic) = separate(&source.definitions, &source.lexical_declarations).unwrap();
+    let precedences = lexicon.rewrite_precedences(&source.precedences);
+    let gramconv = Ebnf2Gram::process(syntactic, &precedences).unwrap();
+    (lexicon, gramconv)
+}
+
+// 生成したコードを一時的なクレートでビルドして実行し, 標準出力を返す
+pub fn run_generated_crate(name: &str, files: &[(&str, &str)], args: &[&str]) -> String {
+    let dir = std::env::temp_dir().join(format!("ebnf-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"generated\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    for (path, content) in files {
+        std::fs::write(dir.join("src").join(path), content).unwrap();
+    }
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = std::process::Command::new(cargo)
+        .args(["run", "--quiet", "--offline", "--"])
+        .args(args)
+        .current_dir(&dir)
+        .env("CARGO_TARGET_DIR", dir.join("target"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
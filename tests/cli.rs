@@ -0,0 +1,109 @@
+use std::process::{Command, Output};
+
+// 文法と入力を一時ファイルに書き, ビルドしたコマンドを実行する
+fn run(name: &str, grammer: &str, args: &[&str], source: &str) -> Output {
+    let dir = std::env::temp_dir().join(format!("ebnf-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let grammer_path = dir.join("grammer.ebnf");
+    let source_path = dir.join("source.txt");
+    std::fs::write(&grammer_path, grammer).unwrap();
+    std::fs::write(&source_path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-ebnf-cc"))
+        .arg(&grammer_path)
+        .args(args)
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn lexical_exclusion_test() {
+    // 字句規則の差は文字の文法に変換しない
+    let grammer = "%token name ; %skip blank ;
+        program = 'if' , name ;
+        name = ( letter , { letter } ) - 'if' ;
+        letter = 'i' | 'f' | 'x' ;
+        blank = ' ' , { ' ' } ;";
+
+    let output = run("exclusion-lex", grammer, &["--lex"], "if fix");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).ends_with("accepted\n"));
+
+    let output = run("exclusion-reserved", grammer, &["--lex"], "if if");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("rejected"));
+
+    let output = run("exclusion-tokens", grammer, &["--tokens"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("(no scannerless grammar: Exclusion"));
+
+    // 構文規則の差は誤りとして報告する
+    let output = run("exclusion-syntax", "a = 'x' - 'y' ;", &[], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Exclusion is only supported"));
+}
+
+#[test]
+fn ast_mode_test() {
+    // 字句規則のある文法では字句規則の字句を文字列の値にする
+    let mppl = include_str!("mppl.ebnf");
+    let output = run("ast-mppl", mppl, &["--ast"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("pub struct Program {\n    pub name: String,"));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("warning"));
+    let output = run("ast-mppl-raw", mppl, &["--rust-raw"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("pub type Input = [(usize, String)];"));
+
+    let grammer = "%left '+' ; e = ( e , '+' , e ) | 'x' ;";
+    let output = run("ast-plain", grammer, &["--rust-raw"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("pub fn parse("));
+}
+
+#[test]
+fn table_format_test() {
+    let grammer = "e = ( e , '+' , 'x' ) | 'x' ;";
+    let output = run("table-csv", grammer, &["--slr-table", "csv"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("state,"));
+
+    // 知らない形式は誤りとして報告する
+    let output = run("table-xml", grammer, &["--slr-table", "xml"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown table format xml"));
+}
+
+#[test]
+fn token_name_test() {
+    // 報告の字句は番号でなく字句の種類の名前で書く
+    let grammer = "%token name ;
+        s = ( s , '+' , s ) | name ;
+        name = letter , { letter } ;
+        letter = 'a' | 'b' ;";
+    for (args, expected) in [
+        (
+            &["--lalr"][..],
+            "conflict: state 4 on \"+\": s3 / r s → s \"+\" s",
+        ),
+        (&["--slr"], "shift example: name \"+\" name • \"+\" name"),
+        (&["--slr-table", "md"], "| state | name | \"+\" | $ | s |"),
+    ] {
+        let output = run("token-names", grammer, args, "");
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = stdout(&output);
+        assert!(stdout.contains(expected), "{}", stdout);
+        assert!(!stdout.contains('#'), "{}", stdout);
+    }
+
+    let grammer = grammer.replace("( s , '+' , s )", "( name , '+' , s )");
+    let output = run("token-names-ll", &grammer, &["--ll"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("conflict: s on name\ns = [name] \"+\" s\n"));
+}